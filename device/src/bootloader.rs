@@ -0,0 +1,82 @@
+use arduino_hal::hal::Wdt;
+use shared::ResetCause;
+
+use crate::reset::reset;
+
+/// Triggers a watch dog reset that will leave the device in bootloader mode.
+pub fn enter_bootloader(watchdog: Wdt) -> ! {
+    /// Magic value that tells the bootloader to remain in bootloader mode on watchdog resets.
+    /// Taken from <https://github.com/arduino/ArduinoCore-avr/blob/c8c514c9a19602542bc32c7033f48fecbbda4401/bootloaders/caterina/Caterina.c#L68>
+    const BOOT_KEY: u16 = 0x7777;
+    /// Pointer to the address where the bootloader looks for the [`BOOT_KEY`].
+    /// Taken from <https://github.com/arduino/ArduinoCore-avr/blob/c8c514c9a19602542bc32c7033f48fecbbda4401/bootloaders/caterina/Caterina.c#L69>
+    const BOOT_KEY_PTR: *mut u16 = 0x0800 as *mut u16;
+
+    // Write the magic value
+    unsafe { core::ptr::write_volatile(BOOT_KEY_PTR, BOOT_KEY) };
+
+    reset(watchdog, ResetCause::Bootloader)
+}
+
+/// Guards the entry into bootloader mode, which drops the device off USB until the bootloader
+/// times out.
+///
+/// Bootloader mode can be entered:
+/// - through a long press on both the power and LEDs buttons, which is deliberate enough;
+/// - through a long press on the power button, but only after being armed by the host;
+/// - at the host's request, if confirmed with the last token issued by the device.
+#[derive(Debug)]
+pub(crate) struct BootloaderGuard {
+    /// Whether the next long press on the power button enters bootloader mode.
+    armed: bool,
+    /// The single use token the host must confirm the bootloader entry with.
+    token: Option<u16>,
+    /// The last generated token, used for generating the next one.
+    ///
+    /// The tokens are not meant to be secret, they only ensure that the host request was
+    /// deliberate and is fresh.
+    last_token: u16,
+}
+
+impl BootloaderGuard {
+    pub(crate) const fn new() -> Self {
+        Self {
+            armed: false,
+            token: None,
+            last_token: 0xACE1,
+        }
+    }
+
+    /// Arms the power button long press.
+    #[inline]
+    pub(crate) fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    /// Returns whether the power button long press was armed, disarming it.
+    #[inline]
+    pub(crate) fn take_armed(&mut self) -> bool {
+        core::mem::take(&mut self.armed)
+    }
+
+    /// Issues a new token, invalidating the previous one.
+    ///
+    /// Uses a 16-bit xorshift, which never yields `0` from a non-zero value.
+    #[inline]
+    pub(crate) fn issue_token(&mut self) -> u16 {
+        let mut token = self.last_token;
+        token ^= token << 7;
+        token ^= token >> 9;
+        token ^= token << 8;
+
+        self.last_token = token;
+        self.token = Some(token);
+        token
+    }
+
+    /// Checks the token against the issued one. The issued token is invalidated either way.
+    #[inline]
+    pub(crate) fn confirm(&mut self, token: u16) -> bool {
+        self.token.take() == Some(token)
+    }
+}
@@ -1,66 +1,118 @@
 mod pins;
 
+#[cfg(feature = "open-drain")]
+use arduino_hal::port::mode::OpenDrain;
+#[cfg(not(feature = "open-drain"))]
+use arduino_hal::port::mode::Output;
 use arduino_hal::{
     delay_ms,
-    port::{Pin, PinOps, mode::Output},
+    port::{
+        Pin, PinOps,
+        mode::{Floating, Input},
+    },
 };
+use avr_device::interrupt;
 use pins::{LedButtonPin, PowerButtonPin, SpeedDownButtonPin, SpeedUpButtonPin};
+use shared::PressTimings;
+
+use crate::SHARED_STATE;
 
 pub type SpeedUpButton = Button<SpeedUpButtonPin>;
 pub type SpeedDownButton = Button<SpeedDownButtonPin>;
 pub type PowerButton = Button<PowerButtonPin>;
 pub type LedButton = Button<LedButtonPin>;
 
+/// Mode of the button pins, driving the base of a transistor soldered in parallel to the push
+/// button.
+#[cfg(not(feature = "open-drain"))]
+type ButtonPinMode = Output;
+/// Mode of the button pins, sinking the cooler's pull-up button line directly.
+#[cfg(feature = "open-drain")]
+type ButtonPinMode = OpenDrain;
+
 /// Generic button struct that emulates button presses.
+///
+/// By default the pin drives a transistor soldered in parallel to the push button, so it is set
+/// high to press. With the `open-drain` feature the pin is wired to the button line directly
+/// instead and it is held as a high-impedance input when idle and set to output-low to press.
 #[allow(missing_debug_implementations, reason = "arduino_hal::port::Pin does not implement Debug")]
-pub struct Button<PIN>(Pin<Output, PIN>);
+pub struct Button<PIN>(Pin<ButtonPinMode, PIN>);
 
 impl<PIN> Button<PIN>
 where
     PIN: PinOps,
 {
+    /// Sets the pin up with the button released.
     #[inline]
-    pub fn new(pin: Pin<Output, PIN>) -> Self {
+    pub fn new(pin: Pin<Input<Floating>, PIN>) -> Self {
+        #[cfg(not(feature = "open-drain"))]
+        let pin = pin.into_output();
+        #[cfg(feature = "open-drain")]
+        let pin = pin.into_opendrain_high();
+
         Self(pin)
     }
+
+    /// Starts pressing the button.
+    #[inline]
+    fn press(&mut self) {
+        #[cfg(not(feature = "open-drain"))]
+        self.0.set_high();
+        #[cfg(feature = "open-drain")]
+        self.0.set_low();
+    }
+
+    /// Releases the button.
+    #[inline]
+    fn release(&mut self) {
+        #[cfg(not(feature = "open-drain"))]
+        self.0.set_low();
+        #[cfg(feature = "open-drain")]
+        self.0.set_high();
+    }
 }
 
 impl<PIN> Button<PIN>
 where
     PIN: PinOps,
 {
-    pub const POST_PRESS_DELAY: u16 = 10;
-    pub const SHORT_PRESS_MS: u16 = 45;
-
     /// Performs a short press on the button.
-    /// A short press has the duration of 40ms, but [`Self::SHORT_PRESS_MS`] is used to ensure the
-    /// button press gets registered.
+    /// A short press has the duration of 40ms, but [`PressTimings::short_press_ms`] is used to
+    /// ensure the button press gets registered.
     ///
-    /// A delay of [`Self::POST_PRESS_DELAY`] is used after the button press as a boundary between
-    /// subsequent presses.
+    /// A delay of [`PressTimings::post_press_delay_ms`] is used after the button press as a
+    /// boundary between subsequent presses.
     #[inline]
     pub fn short_press(&mut self) {
-        self.0.set_high();
-        delay_ms(Self::SHORT_PRESS_MS);
-        self.0.set_low();
-        delay_ms(Self::POST_PRESS_DELAY);
+        let timings = press_timings();
+
+        self.press();
+        delay_ms(timings.short_press_ms.into());
+        self.release();
+        delay_ms(timings.post_press_delay_ms.into());
     }
 }
 
 impl Button<LedButtonPin> {
-    pub const LONG_PRESS_MS: u16 = 1425;
-
     /// Performs a long press on the button.
-    /// A long press has the duration of 1400ms, but [`Self::LONG_PRESS_MS`] is used to ensure the
-    /// button press gets registered.
+    /// A long press has the duration of 1400ms, but [`PressTimings::long_press_ms`] is used to
+    /// ensure the button press gets registered.
     ///
-    /// A delay of [`Self::POST_PRESS_DELAY`] is used after the button press as a boundary between
-    /// subsequent presses.
+    /// A delay of [`PressTimings::post_press_delay_ms`] is used after the button press as a
+    /// boundary between subsequent presses.
     #[inline]
     pub fn long_press(&mut self) {
-        self.0.set_high();
-        delay_ms(Self::LONG_PRESS_MS);
-        self.0.set_low();
-        delay_ms(Self::POST_PRESS_DELAY);
+        let timings = press_timings();
+
+        self.press();
+        delay_ms(timings.long_press_ms);
+        self.release();
+        delay_ms(timings.post_press_delay_ms.into());
     }
 }
+
+/// Returns the press timings in use, which the host can change at runtime.
+#[inline]
+fn press_timings() -> PressTimings {
+    interrupt::free(|cs| SHARED_STATE.borrow(cs).borrow().press_timings())
+}
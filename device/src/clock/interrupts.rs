@@ -0,0 +1,6 @@
+use avr_device::interrupt;
+
+#[interrupt(atmega32u4)]
+fn TIMER1_COMPA() {
+    super::tick();
+}
@@ -0,0 +1,45 @@
+mod interrupts;
+
+use core::cell::Cell;
+
+use arduino_hal::pac::TC1;
+use avr_device::interrupt::{self, Mutex};
+
+use crate::SHARED_STATE;
+
+/// Seconds elapsed since the clock was set up.
+static UPTIME_S: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Sets up `TIMER1_COMPA` to trigger every second for keeping the uptime.
+///
+/// Timer comparison value formula: 16 MHz / (1024 * (1 + 15624)) = 1 Hz
+pub fn setup_clock(timer: TC1) {
+    // WGM, CTC mode with `OCR1A` as top
+    timer.tccr1a.write(|w| w.wgm1().bits(0b00));
+    timer
+        .tccr1b
+        .write(|w| w.wgm1().bits(0b01).cs1().prescale_1024());
+
+    // Comparison value
+    timer.ocr1a.write(|w| w.bits(15624));
+
+    // Enable the timer interrupt
+    timer.timsk1.write(|w| w.ocie1a().set_bit());
+}
+
+/// Returns the seconds elapsed since the clock was set up.
+#[inline]
+pub fn uptime_s() -> u32 {
+    interrupt::free(|cs| UPTIME_S.borrow(cs).get())
+}
+
+/// Advances the clock by a second, along with the heartbeat countdown.
+#[inline]
+fn tick() {
+    interrupt::free(|cs| {
+        let uptime_s = UPTIME_S.borrow(cs);
+        uptime_s.set(uptime_s.get().wrapping_add(1));
+
+        SHARED_STATE.borrow(cs).borrow_mut().clock_tick();
+    });
+}
@@ -1,4 +1,4 @@
-use shared::DeviceCommand;
+use shared::{DeviceCommand, LedColor};
 
 /// A command that the device can execute.
 #[derive(Clone, Copy, Debug)]
@@ -8,8 +8,8 @@ pub enum Command {
     /// Aritifical command.
     ///
     /// This is used to trigger a watchdog reset that leaves the device in bootloader mode, ready to
-    /// be flashed. Gets issued when a long-press on the power button (otherwise a no-op) is noticed
-    /// by the monitor.
+    /// be flashed. Gets issued on a confirmed host request or on a long press on the power button
+    /// (otherwise a no-op) that is deliberate enough. See [`crate::bootloader::BootloaderGuard`].
     EnterBootloader,
     /// Artifical command.
     ///
@@ -23,4 +23,49 @@ pub enum Command {
     /// the actual device commands, giving a chance to residual power to wear off before
     /// executing anything.
     Delay275Ms,
+    /// Artificial command.
+    ///
+    /// Steps through all the fan speeds to measure the fan voltage at each of them. Issued at the
+    /// host's request. See [`crate::fan_sense::FanSense::calibrate`].
+    #[cfg(feature = "fan-sense")]
+    CalibrateFanSense,
+    /// Artificial command.
+    ///
+    /// Re-homes the cooler when the tracked state may have drifted from the real one. Issued at the
+    /// host's request. See [`crate::discovery::resync`].
+    Resync,
+    /// Artificial command.
+    ///
+    /// Short presses the LED button until the LEDs reach the given color, as tracked by the
+    /// monitor. Issued at the host's request.
+    SetLedColor(LedColor),
+}
+
+impl Command {
+    /// Whether executing the command emulates button presses, which the monitor must not mistake
+    /// for the user's.
+    #[inline]
+    pub fn emulates_presses(self) -> bool {
+        match self {
+            Self::Device(_) => true,
+            #[cfg(feature = "fan-sense")]
+            Self::CalibrateFanSense => true,
+            Self::Resync | Self::SetLedColor(_) => true,
+            Self::EnterBootloader | Self::Delay275Ms => false,
+        }
+    }
+
+    /// Whether the command presses the speed buttons enough times to make up for the first press
+    /// only waking up the backlight, in which case the press must not be repeated.
+    #[inline]
+    pub fn covers_backlight_wake(self) -> bool {
+        match self {
+            #[cfg(feature = "fan-sense")]
+            Self::CalibrateFanSense => true,
+            Self::Resync => true,
+            Self::Device(_) | Self::EnterBootloader | Self::Delay275Ms | Self::SetLedColor(_) => {
+                false
+            }
+        }
+    }
 }
@@ -0,0 +1,231 @@
+use shared::DeviceCommand;
+use ufmt::{uWrite, uwrite};
+use usb_device::bus::{UsbBus, UsbBusAllocator};
+use usbd_serial::SerialPort;
+
+use crate::{SharedState, command::Command};
+
+/// Writes a formatted line to the console.
+macro_rules! print_line {
+    ($console:expr, $($arg:tt)*) => {{
+        let mut line = Line::new();
+        let Ok(()) = uwrite!(line, $($arg)*);
+        $console.write_line(&line);
+    }};
+}
+
+/// An entry of the trace streamed over the [`Console`].
+#[derive(Clone, Copy, Debug)]
+pub enum Trace {
+    /// The monitor moved to the named state.
+    Monitor(&'static str),
+    /// The main loop started executing the command.
+    Command(Command),
+    /// The host suspended the USB.
+    Suspend,
+    /// The host resumed the USB.
+    Resume,
+}
+
+/// Debug console over a CDC-ACM serial interface, next to the HID one.
+///
+/// Streams the [`Trace`] entries, one line each, while a terminal is open and answers the
+/// commands typed in:
+/// - `state`: the device state, along with the USB, backlight and fail-safe state
+/// - `queue`: the pending commands, the next one first, and the number of pending reports
+/// - `counters`: the lifetime usage counters
+///
+/// Output that does not fit in the serial buffer, e.g. because the terminal is not reading, gets
+/// dropped.
+#[allow(
+    missing_debug_implementations,
+    reason = "usbd_serial::SerialPort does not implement Debug"
+)]
+pub struct Console<'a, B: UsbBus> {
+    serial: SerialPort<'a, B>,
+    /// The command being typed in.
+    input: [u8; Self::INPUT_LEN],
+    input_len: usize,
+}
+
+impl<'a, B: UsbBus> Console<'a, B> {
+    /// Long enough for the longest command.
+    const INPUT_LEN: usize = 16;
+
+    #[inline]
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            serial: SerialPort::new(alloc),
+            input: [0; Self::INPUT_LEN],
+            input_len: 0,
+        }
+    }
+
+    /// The serial interface, to be polled along with the other USB classes.
+    #[inline]
+    pub fn serial(&mut self) -> &mut SerialPort<'a, B> {
+        &mut self.serial
+    }
+
+    /// Streams the queued trace and handles the commands typed in.
+    pub(crate) fn run(&mut self, shared_state: &mut SharedState) {
+        // The trace gets drained regardless, so that a terminal does not start with stale entries.
+        while let Some(trace) = shared_state.trace_queue.pop_back() {
+            if self.serial.dtr() {
+                self.trace(trace);
+            }
+        }
+
+        let mut buf = [0; Self::INPUT_LEN];
+        let Ok(len) = self.serial.read(&mut buf) else {
+            return;
+        };
+
+        for &byte in &buf[..len] {
+            match byte {
+                b'\r' | b'\n' => {
+                    // Some terminals send both.
+                    if self.input_len > 0 {
+                        self.write_line(&Line::new());
+                        let input = self.input;
+                        self.execute(&input[..self.input_len], shared_state);
+                        self.input_len = 0;
+                    }
+                }
+                _ if self.input_len < Self::INPUT_LEN => {
+                    // Terminals do not echo the input by themselves.
+                    self.serial.write(&[byte]).ok();
+                    self.input[self.input_len] = byte;
+                    self.input_len += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn trace(&mut self, trace: Trace) {
+        match trace {
+            Trace::Monitor(state) => print_line!(self, "monitor: {}", state),
+            Trace::Command(Command::SetLedColor(led_color)) => {
+                print_line!(self, "command: set LED color {}", u8::from(led_color));
+            }
+            Trace::Command(command) => print_line!(self, "command: {}", command_name(command)),
+            Trace::Suspend => print_line!(self, "usb: suspend"),
+            Trace::Resume => print_line!(self, "usb: resume"),
+        }
+    }
+
+    fn execute(&mut self, input: &[u8], shared_state: &SharedState) {
+        match input {
+            b"state" => {
+                let state = shared_state.device_state;
+                print_line!(
+                    self,
+                    "power={} leds={} speed={} color={} uncertain={}",
+                    state.power_enabled(),
+                    state.leds_enabled(),
+                    u8::from(state.fan_speed()),
+                    u8::from(state.led_color()),
+                    state.uncertain()
+                );
+                print_line!(
+                    self,
+                    "suspended={} backlight={} failsafe={}",
+                    shared_state.usb_suspended,
+                    shared_state.backlight_lit,
+                    shared_state.heartbeat.is_failsafe()
+                );
+            }
+            b"queue" => {
+                // Commands get pushed to the front and popped from the back.
+                for &command in shared_state.command_queue.iter().rev() {
+                    print_line!(self, "{}", command_name(command));
+                }
+
+                print_line!(
+                    self,
+                    "{} commands, {} reports",
+                    shared_state.command_queue.len(),
+                    shared_state.report_queue.len()
+                );
+            }
+            b"counters" => {
+                let odometer = shared_state.odometer.odometer();
+                print_line!(self, "powered on: {}s", odometer.powered_on_s);
+
+                for (speed, seconds) in (1u8..).zip(odometer.fan_speed_s) {
+                    print_line!(self, "  at fan speed {}: {}s", speed, seconds);
+                }
+
+                print_line!(self, "leds on: {}s", odometer.leds_on_s);
+                print_line!(
+                    self,
+                    "presses: {} physical, {} emulated",
+                    odometer.physical_presses,
+                    odometer.emulated_presses
+                );
+                print_line!(
+                    self,
+                    "suspends: {}, rejected glitches: {}",
+                    odometer.suspends,
+                    odometer.rejected_glitches
+                );
+            }
+            _ => print_line!(self, "commands: state, queue, counters"),
+        }
+    }
+
+    /// Writes the line, terminated the way terminals expect it.
+    fn write_line(&mut self, line: &Line) {
+        self.serial.write(&line.buf[..line.len]).ok();
+        self.serial.write(b"\r\n").ok();
+    }
+}
+
+/// A line of console output, truncated if too long.
+struct Line {
+    buf: [u8; Self::LEN],
+    len: usize,
+}
+
+impl Line {
+    const LEN: usize = 64;
+
+    #[inline]
+    fn new() -> Self {
+        Self {
+            buf: [0; Self::LEN],
+            len: 0,
+        }
+    }
+}
+
+impl uWrite for Line {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        let len = s.len().min(Self::LEN - self.len);
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+/// Returns the name of the command, as shown in the trace.
+fn command_name(command: Command) -> &'static str {
+    match command {
+        Command::Device(DeviceCommand::SpeedUp) => "speed up",
+        Command::Device(DeviceCommand::SpeedDown) => "speed down",
+        Command::Device(DeviceCommand::PowerOn) => "power on",
+        Command::Device(DeviceCommand::PowerOff) => "power off",
+        Command::Device(DeviceCommand::LedsOn) => "leds on",
+        Command::Device(DeviceCommand::LedsOff) => "leds off",
+        Command::Device(DeviceCommand::LedsColorChange) => "leds color change",
+        Command::EnterBootloader => "enter bootloader",
+        Command::Delay275Ms => "delay 275ms",
+        #[cfg(feature = "fan-sense")]
+        Command::CalibrateFanSense => "calibrate fan sense",
+        Command::Resync => "resync",
+        Command::SetLedColor(_) => "set LED color",
+    }
+}
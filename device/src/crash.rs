@@ -0,0 +1,58 @@
+use core::{cell::Cell, panic::PanicInfo};
+
+use arduino_hal::{Peripherals, hal::Wdt};
+use avr_device::interrupt::{self, Mutex};
+use shared::{CrashReport, DeviceCommand, ResetCause};
+
+use crate::{SHARED_STATE, clock, reset, storage::Storage};
+
+/// The last [`DeviceCommand`] executed by the main loop, included in crash reports.
+static LAST_COMMAND: Mutex<Cell<Option<DeviceCommand>>> = Mutex::new(Cell::new(None));
+
+/// Records the [`DeviceCommand`] about to be executed for a potential crash report.
+#[inline]
+pub fn set_last_command(command: DeviceCommand) {
+    interrupt::free(|cs| LAST_COMMAND.borrow(cs).set(Some(command)));
+}
+
+/// Hands the crash report persisted by the panic handler, if any, over for the host to read. It
+/// stays persisted until the host acknowledges it, so that it survives a host that is not running
+/// or misses it.
+pub fn report_crash(storage: &Storage) {
+    let Some(report) = storage.read_crash_report() else {
+        return;
+    };
+
+    interrupt::free(|cs| SHARED_STATE.borrow(cs).borrow_mut().restore_crash(report));
+}
+
+/// Persists a [`CrashReport`] to the EEPROM and resets the device through the watchdog, instead of
+/// halting with the device unresponsive until replugged.
+///
+/// Only the panic location is recorded as formatting the panic message would take up too much
+/// flash.
+#[panic_handler]
+fn panic(info: &PanicInfo<'_>) -> ! {
+    interrupt::disable();
+
+    // SAFETY: The firmware does not resume after a panic, so nothing else uses the peripherals.
+    let peripherals = unsafe { Peripherals::steal() };
+
+    let (location, line) = info.location().map_or((0, 0), |location| {
+        let line = u16::try_from(location.line()).unwrap_or(u16::MAX);
+        (CrashReport::hash_location(location.file()), line)
+    });
+
+    let report = CrashReport {
+        location,
+        line,
+        reset_cause: reset::reset_cause(),
+        last_command: interrupt::free(|cs| LAST_COMMAND.borrow(cs).get()),
+        uptime_s: clock::uptime_s(),
+    };
+
+    Storage::new(peripherals.EEPROM).write_crash_report(report);
+
+    let watchdog = Wdt::new(peripherals.WDT, &peripherals.CPU.mcusr);
+    reset::reset(watchdog, ResetCause::Panic)
+}
@@ -0,0 +1,162 @@
+#[cfg(feature = "fan-sense")]
+use arduino_hal::Adc;
+use arduino_hal::{
+    delay_ms,
+    port::{
+        Pin, PinOps,
+        mode::{Input, PullUp},
+    },
+};
+use avr_device::interrupt;
+use shared::FanSpeed;
+
+#[cfg(feature = "fan-sense")]
+use crate::fan_sense::FanSense;
+#[cfg(feature = "led-sense")]
+use crate::led_sense::LedSense;
+use crate::{
+    SHARED_STATE,
+    button::{LedButton, PowerButton, SpeedDownButton, SpeedUpButton},
+    watchdog::Watchdog,
+};
+
+/// How long to wait after a press for the backlight to light up.
+const BACKLIGHT_DELAY_MS: u16 = 20;
+
+/// Finds out the real cooler state on startup and brings the cooler to the lowest fan speed.
+///
+/// Meant to be called before interrupts are enabled, so that the first state report is accurate.
+/// The monitor is not running yet, so the presses done here are not tracked.
+///
+/// - Power: the cooler is on if the backlight is lit or, with fan sensing, if the fans are running.
+///   Otherwise a speed down press is used as a probe, as it lights up the backlight only if the
+///   cooler is on.
+/// - Fan speed: speed down presses, with the cooler powered on for them if needed.
+/// - LEDs: sensed with LED sensing while the cooler is powered on. Otherwise the LEDs cannot be
+///   observed and are assumed to be on, which is how the cooler starts.
+///
+/// If the cooler was off, it gets powered on for the duration of the discovery and then powered
+/// off again.
+pub fn discover_state<PIN>(
+    backlight_mon_pin: &Pin<Input<PullUp>, PIN>,
+    speed_down_btn: &mut SpeedDownButton,
+    power_btn: &mut PowerButton,
+    #[cfg(feature = "fan-sense")] fan_sense: &mut FanSense,
+    #[cfg(feature = "fan-sense")] adc: &mut Adc,
+    #[cfg(feature = "led-sense")] led_sense: &LedSense,
+) where
+    PIN: PinOps,
+{
+    // The backlight is active low.
+    let mut power_enabled = backlight_mon_pin.is_low();
+
+    #[cfg(feature = "fan-sense")]
+    {
+        power_enabled |= fan_sense.is_running(adc) == Some(true);
+    }
+
+    if !power_enabled {
+        // The probe press also counts towards bringing the fan speed down.
+        speed_down_btn.short_press();
+        delay_ms(BACKLIGHT_DELAY_MS);
+        power_enabled = backlight_mon_pin.is_low();
+    }
+
+    if !power_enabled {
+        power_btn.short_press();
+    }
+
+    // An extra press in case the first one only wakes up the backlight.
+    for _ in 0..=FanSpeed::Speed6 as u8 {
+        speed_down_btn.short_press();
+    }
+
+    #[cfg(feature = "led-sense")]
+    let leds_enabled = led_sense.is_lit();
+    #[cfg(not(feature = "led-sense"))]
+    let leds_enabled = true;
+
+    if !power_enabled {
+        power_btn.short_press();
+    }
+
+    interrupt::free(|cs| {
+        SHARED_STATE
+            .borrow(cs)
+            .borrow_mut()
+            .set_power_and_leds(power_enabled, leds_enabled);
+    });
+}
+
+/// The power and LEDs state requested by the host for a [`resync`].
+#[derive(Clone, Copy, Debug)]
+pub struct ResyncTarget {
+    pub power_enabled: bool,
+    pub leds_enabled: bool,
+}
+
+/// Re-homes the cooler at runtime, for when the tracked state may have drifted from the real one.
+///
+/// - Power: a speed down press wakes up the backlight, which only happens if the cooler is on. The
+///   cooler gets powered on if it is not.
+/// - Fan speed: speed down presses down to the lowest fan speed, which is known for sure once
+///   there, and then speed up presses back to the tracked fan speed.
+/// - LEDs: toggled if they do not match the requested state. Without LED sensing, the tracked state
+///   is trusted for this.
+/// - Power: powered off at the end if requested.
+///
+/// The uncertainty flag gets cleared afterwards. The presses take seconds in total, so the
+/// watchdog gets fed in between them.
+pub fn resync(
+    speed_up_btn: &mut SpeedUpButton,
+    speed_down_btn: &mut SpeedDownButton,
+    power_btn: &mut PowerButton,
+    led_btn: &mut LedButton,
+    watchdog: &mut Watchdog,
+    #[cfg(feature = "led-sense")] led_sense: &LedSense,
+) {
+    let (device_state, target) = interrupt::free(|cs| {
+        let shared_state = SHARED_STATE.borrow(cs).borrow();
+        (*shared_state.device_state(), shared_state.resync_target)
+    });
+
+    speed_down_btn.short_press();
+    delay_ms(BACKLIGHT_DELAY_MS);
+
+    if !interrupt::free(|cs| SHARED_STATE.borrow(cs).borrow().backlight_lit) {
+        power_btn.short_press();
+    }
+
+    // An extra press in case the first one only wakes up the backlight.
+    for _ in 0..=FanSpeed::Speed6 as u8 {
+        watchdog.feed();
+        speed_down_btn.short_press();
+    }
+
+    #[cfg(feature = "led-sense")]
+    let leds_enabled = led_sense.is_lit();
+    #[cfg(not(feature = "led-sense"))]
+    let leds_enabled = device_state.leds_enabled();
+
+    if leds_enabled != target.leds_enabled {
+        watchdog.feed();
+        led_btn.long_press();
+    }
+
+    for _ in FanSpeed::Speed1 as u8..device_state.fan_speed() as u8 {
+        watchdog.feed();
+        speed_up_btn.short_press();
+    }
+
+    if !target.power_enabled {
+        watchdog.feed();
+        power_btn.short_press();
+    }
+
+    interrupt::free(|cs| {
+        SHARED_STATE
+            .borrow(cs)
+            .borrow_mut()
+            .resynced(device_state.fan_speed(), target);
+    });
+}
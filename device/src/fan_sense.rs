@@ -0,0 +1,188 @@
+use arduino_hal::{
+    Adc, delay_ms,
+    hal::port::PF7,
+    port::{Pin, mode::Analog},
+};
+use avr_device::interrupt;
+use shared::{DeviceReport, FanSenseCalibration, FanSpeed};
+
+use crate::{
+    SHARED_STATE,
+    button::{SpeedDownButton, SpeedUpButton},
+    clock::uptime_s,
+    storage::Storage,
+    watchdog::Watchdog,
+};
+
+/// Analog pin `A0`, wired to the unused fan-grid connector.
+pub type FanSensePin = PF7;
+
+/// Fan voltage sensing through the ADC, which tells the real fan speed instead of the one inferred
+/// from the button presses.
+///
+/// Readings are mapped to a [`FanSpeed`] through a per-unit [`FanSenseCalibration`], persisted in
+/// the EEPROM. Nothing is sensed until the device got calibrated.
+///
+/// The ADC is borrowed for every reading, as it is shared with the temperature probe.
+#[allow(
+    missing_debug_implementations,
+    reason = "arduino_hal::port::Pin does not implement Debug"
+)]
+pub struct FanSense {
+    pin: Pin<Analog, FanSensePin>,
+    calibration: Option<FanSenseCalibration>,
+    /// The fan speed classified from the previous reading, as a fan speed is only trusted once two
+    /// readings in a row agree.
+    last_speed: Option<FanSpeed>,
+    /// The tracked power state and fan speed as of the previous reading.
+    last_tracked: Option<(bool, FanSpeed)>,
+    /// Uptime until which the readings are ignored, as the fans take a while to settle after a
+    /// change and would meanwhile still read as the previous speed.
+    settled_at_s: u32,
+}
+
+impl FanSense {
+    /// Number of ADC readings averaged into a single sample, to smooth out the fan noise.
+    const SAMPLES: u16 = 16;
+    /// How long to let the fans settle at each speed while calibrating, in
+    /// [`FanSense::SETTLE_STEP_MS`] steps so the watchdog can be fed in between.
+    const SETTLE_STEPS: u8 = 20;
+    const SETTLE_STEP_MS: u16 = 100;
+    /// How long to ignore the readings for after the tracked state changed or a command pressed
+    /// the buttons, matching the settling time given while calibrating.
+    const SETTLE_S: u32 = 2;
+
+    pub fn new(adc: &mut Adc, pin: Pin<Analog, FanSensePin>, storage: &Storage) -> Self {
+        // The first conversion after enabling the ADC is less accurate, so get it out of the way.
+        pin.analog_read(adc);
+
+        Self {
+            pin,
+            calibration: storage.read_fan_sense_calibration(),
+            last_speed: None,
+            last_tracked: None,
+            settled_at_s: 0,
+        }
+    }
+
+    /// Samples the fan voltage and corrects the tracked fan speed if it does not match the sensed
+    /// one. Meant to be called periodically from the main loop, in between commands.
+    ///
+    /// Readings are ignored for [`FanSense::SETTLE_S`] after the tracked state changes, e.g. on a
+    /// press seen by the monitor, which is checked in the same critical section as the correction
+    /// so that a press made after the reading cannot be reverted by it.
+    pub fn sense(&mut self, adc: &mut Adc) {
+        let Some(calibration) = self.calibration else {
+            return;
+        };
+
+        let speed = calibration.classify(self.sample(adc));
+        let now_s = uptime_s();
+
+        interrupt::free(|cs| {
+            let mut shared_state = SHARED_STATE.borrow(cs).borrow_mut();
+            let device_state = shared_state.device_state();
+            let tracked = (device_state.power_enabled(), device_state.fan_speed());
+
+            if self.last_tracked.replace(tracked) != Some(tracked) {
+                self.settled_at_s = now_s + Self::SETTLE_S;
+            }
+
+            if now_s < self.settled_at_s {
+                self.last_speed = None;
+                return;
+            }
+
+            let stable = speed == self.last_speed;
+            self.last_speed = speed;
+
+            if let (true, Some(speed)) = (stable, speed) {
+                shared_state.fan_speed_sensed(speed);
+            }
+        });
+    }
+
+    /// Ignores the readings for a while, as the fans may still be settling after the presses of a
+    /// command even if the tracked fan speed ended up unchanged, e.g. after a resync.
+    pub fn settle(&mut self) {
+        self.settled_at_s = uptime_s() + Self::SETTLE_S;
+        self.last_speed = None;
+    }
+
+    /// Returns whether the fans are running, or `None` if the device was not calibrated yet.
+    pub fn is_running(&mut self, adc: &mut Adc) -> Option<bool> {
+        let calibration = self.calibration?;
+        Some(calibration.classify(self.sample(adc)).is_some())
+    }
+
+    /// Measures the fan voltage at every fan speed, persists the calibration and reports it to the
+    /// host.
+    ///
+    /// The cooler is left at the lowest fan speed, which is also what it starts with. Refused while
+    /// the cooler is powered off, as every reading would be of stopped fans.
+    pub fn calibrate(
+        &mut self,
+        adc: &mut Adc,
+        speed_up_btn: &mut SpeedUpButton,
+        speed_down_btn: &mut SpeedDownButton,
+        watchdog: &mut Watchdog,
+        storage: &mut Storage,
+    ) {
+        let refused = interrupt::free(|cs| {
+            let mut shared_state = SHARED_STATE.borrow(cs).borrow_mut();
+            let refused = !shared_state.device_state().power_enabled();
+
+            if refused {
+                shared_state.push_report(DeviceReport::FanSenseCalibrationRefused);
+            }
+
+            refused
+        });
+
+        if refused {
+            return;
+        }
+
+        // An extra press in case the first one only wakes up the backlight.
+        for _ in 0..=FanSpeed::Speed6 as u8 {
+            speed_down_btn.short_press();
+        }
+
+        let mut calibration = FanSenseCalibration { levels: [0; 6] };
+
+        for (index, level) in calibration.levels.iter_mut().enumerate() {
+            if index > 0 {
+                speed_up_btn.short_press();
+            }
+
+            for _ in 0..Self::SETTLE_STEPS {
+                watchdog.feed();
+                delay_ms(Self::SETTLE_STEP_MS);
+            }
+
+            *level = self.sample(adc);
+        }
+
+        for _ in FanSpeed::Speed1 as u8..FanSpeed::Speed6 as u8 {
+            speed_down_btn.short_press();
+        }
+
+        storage.write_fan_sense_calibration(calibration);
+        self.calibration = Some(calibration);
+        self.last_speed = None;
+
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .push_report(DeviceReport::FanSenseCalibration(calibration));
+        });
+    }
+
+    /// Averages [`FanSense::SAMPLES`] ADC readings. The 10-bit readings cannot overflow the sum.
+    fn sample(&mut self, adc: &mut Adc) -> u16 {
+        let sum: u16 = (0..Self::SAMPLES).map(|_| self.pin.analog_read(adc)).sum();
+
+        sum / Self::SAMPLES
+    }
+}
@@ -0,0 +1,69 @@
+use shared::FanSpeed;
+
+/// Tracks the host heartbeats to tell when the host is gone, in which case the device drives the
+/// cooler to the fail-safe fan speed on its own.
+///
+/// The heartbeat monitoring is disabled until the host sends the first heartbeat with a non-zero
+/// timeout, so a host that never does is never considered gone.
+#[derive(Debug)]
+pub(crate) struct Heartbeat {
+    /// Seconds without heartbeats after which the host is considered gone. Zero when disabled.
+    timeout_s: u8,
+    /// Seconds left until the host is considered gone.
+    remaining_s: u8,
+    /// The fan speed to drive the cooler to when the host is gone.
+    failsafe_speed: FanSpeed,
+    /// Whether the host is considered gone.
+    failsafe: bool,
+}
+
+impl Heartbeat {
+    pub(crate) const fn new() -> Self {
+        Self {
+            timeout_s: 0,
+            remaining_s: 0,
+            failsafe_speed: FanSpeed::Speed6,
+            failsafe: false,
+        }
+    }
+
+    /// Registers a heartbeat from the host. Returns whether the fail-safe mode was left.
+    #[inline]
+    pub(crate) fn beat(&mut self, timeout_s: u8, failsafe_speed: FanSpeed) -> bool {
+        self.timeout_s = timeout_s;
+        self.remaining_s = timeout_s;
+        self.failsafe_speed = failsafe_speed;
+        core::mem::take(&mut self.failsafe)
+    }
+
+    /// Restarts the countdown, e.g. after a USB resume, when the host needs some time to pick up
+    /// sending heartbeats again.
+    #[inline]
+    pub(crate) fn rewind(&mut self) {
+        self.remaining_s = self.timeout_s;
+    }
+
+    /// Counts down a second. Returns the fail-safe fan speed when the countdown runs out, which
+    /// only happens once until the next heartbeat.
+    #[inline]
+    pub(crate) fn tick(&mut self) -> Option<FanSpeed> {
+        if self.remaining_s == 0 {
+            return None;
+        }
+
+        self.remaining_s -= 1;
+
+        if self.remaining_s != 0 {
+            return None;
+        }
+
+        self.failsafe = true;
+        Some(self.failsafe_speed)
+    }
+
+    /// Returns whether the host is considered gone.
+    #[inline]
+    pub(crate) fn is_failsafe(&self) -> bool {
+        self.failsafe
+    }
+}
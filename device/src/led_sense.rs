@@ -0,0 +1,61 @@
+use arduino_hal::{
+    delay_us,
+    hal::port::PF6,
+    port::{
+        Pin,
+        mode::{Floating, Input},
+    },
+};
+use avr_device::interrupt;
+
+use crate::SHARED_STATE;
+
+/// Pin `A1`, wired to the unused LED strip connector.
+pub type LedSensePin = PF6;
+
+/// LED strip sensing through a digital input, which tells whether the LEDs are really on instead
+/// of inferring it from the long presses on the LED button.
+#[allow(
+    missing_debug_implementations,
+    reason = "arduino_hal::port::Pin does not implement Debug"
+)]
+pub struct LedSense(Pin<Input<Floating>, LedSensePin>);
+
+impl LedSense {
+    /// Number of pin reads spread over the sampling window.
+    const SAMPLES: u8 = 20;
+    /// Delay between pin reads. The LED strip can be driven through PWM, so it only counts as off
+    /// if the pin stays low for the whole 5ms window, which is longer than a PWM period.
+    const SAMPLE_INTERVAL_US: u32 = 250;
+
+    #[inline]
+    pub fn new(pin: Pin<Input<Floating>, LedSensePin>) -> Self {
+        Self(pin)
+    }
+
+    /// Samples the LED strip and corrects the tracked LEDs state if it does not match the sensed
+    /// one. Meant to be called periodically from the main loop, as well as right after the LEDs
+    /// are toggled.
+    pub fn sense(&self) {
+        let leds_enabled = self.is_lit();
+
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .leds_sensed(leds_enabled);
+        });
+    }
+
+    /// Returns whether the LED strip is lit.
+    pub fn is_lit(&self) -> bool {
+        let mut lit = false;
+
+        for _ in 0..Self::SAMPLES {
+            lit |= self.0.is_high();
+            delay_us(Self::SAMPLE_INTERVAL_US);
+        }
+
+        lit
+    }
+}
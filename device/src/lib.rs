@@ -2,42 +2,47 @@
 #![no_std]
 #![feature(abi_avr_interrupt)]
 
-use arduino_hal::hal::{wdt::Timeout, Wdt};
-use core::{cell::{RefCell, UnsafeCell}, mem::MaybeUninit};
+use core::{cell::{RefCell, UnsafeCell}, cmp::Ordering, mem::MaybeUninit};
 
 use avr_device::interrupt::Mutex;
 use circular_buffer::CircularBuffer;
-use shared::DeviceState;
+use shared::{
+    ButtonEvent, CrashReport, DeviceCommand, DeviceReport, DeviceState, FanCurve, FanSpeed,
+    Odometer, PressTimings,
+};
 
-use crate::command::Command;
+#[cfg(feature = "debug-console")]
+use crate::console::Trace;
+use crate::{
+    bootloader::BootloaderGuard, command::Command, discovery::ResyncTarget, heartbeat::Heartbeat,
+    odometer::OdometerTracker,
+};
 
+pub mod bootloader;
 pub mod button;
+pub mod clock;
 pub mod command;
+#[cfg(feature = "debug-console")]
+pub mod console;
+pub mod crash;
+pub mod discovery;
+#[cfg(feature = "fan-sense")]
+pub mod fan_sense;
+pub mod heartbeat;
+#[cfg(feature = "led-sense")]
+pub mod led_sense;
 pub mod monitor;
+pub mod odometer;
+pub mod reset;
+pub mod storage;
+#[cfg(feature = "temp-probe")]
+pub mod temp_probe;
 pub mod usb;
+pub mod watchdog;
 
 /// Mutex locked shared device state across the entire program.
 pub static SHARED_STATE: Mutex<RefCell<SharedState>> = Mutex::new(RefCell::new(SharedState::new()));
 
-/// Triggers a watch dog reset that will leave the device in bootloader mode.
-pub fn enter_bootloader(mut watchdog: Wdt) -> ! {
-    /// Magic value that tells the bootloader to remain in bootloader mode on watchdog resets.
-    /// Taken from <https://github.com/arduino/ArduinoCore-avr/blob/c8c514c9a19602542bc32c7033f48fecbbda4401/bootloaders/caterina/Caterina.c#L68>
-    const BOOT_KEY: u16 = 0x7777;
-    /// Pointer to the address where the bootloader looks for the [`BOOT_KEY`].
-    /// Taken from <https://github.com/arduino/ArduinoCore-avr/blob/c8c514c9a19602542bc32c7033f48fecbbda4401/bootloaders/caterina/Caterina.c#L69>
-    const BOOT_KEY_PTR: *mut u16 = 0x0800 as *mut u16;
-
-    // Write the magic value
-    unsafe { core::ptr::write_volatile(BOOT_KEY_PTR, BOOT_KEY) };
-
-    // Set the lowest possible time value for the watchdog.
-    watchdog.start(Timeout::Ms16).ok();
-
-    // Loop until the watchdog reset happens.
-    loop {}
-}
-
 /// Shared state struct.
 #[derive(Debug)]
 pub struct SharedState {
@@ -45,21 +50,96 @@ pub struct SharedState {
     device_state: DeviceState,
     /// Whether the device state must be sent to the host, either due to an update or a retry.
     send_state: bool,
+    /// FIFO queue of reports other than the device state that must be sent to the host, backed by
+    /// a [`CircularBuffer`] of length [`SharedState::REPORT_QUEUE_SIZE`].
+    report_queue: CircularBuffer<{ Self::REPORT_QUEUE_SIZE }, DeviceReport>,
     /// FIFO command queue backed by a [`CircularBuffer`] of length [`SharedState::COMMAND_QUEUE_SIZE`].
     /// Acts as a command backlog when under high load.
     command_queue: CircularBuffer<{ Self::COMMAND_QUEUE_SIZE }, Command>,
+    /// The command being executed by the main loop, if it emulates button presses, meaning that
+    /// the button presses seen by the monitor are emulated by the device itself.
+    emulating: Option<Command>,
+    /// Guards the entry into bootloader mode.
+    bootloader_guard: BootloaderGuard,
+    /// Tracks the host heartbeats for the fail-safe mode.
+    heartbeat: Heartbeat,
+    /// Whether the USB is suspended, in which case the host is not expected to send heartbeats.
+    usb_suspended: bool,
+    /// The fan curve the fan speed is picked with when the host sends temperatures.
+    fan_curve: FanCurve,
+    /// Whether the fan curve was changed by the host and must be persisted.
+    fan_curve_changed: bool,
+    /// The timings of the emulated presses and the monitor thresholds.
+    press_timings: PressTimings,
+    /// Whether the press timings were changed by the host and must be persisted.
+    press_timings_changed: bool,
+    /// Whether a power button press wakes up the host while the USB is suspended.
+    remote_wakeup: bool,
+    /// Whether the remote wakeup setting was changed by the host and must be persisted.
+    remote_wakeup_changed: bool,
+    /// The crash report of a previous run, kept until the host acknowledges it.
+    crash: Option<CrashReport>,
+    /// Whether the host acknowledged the crash report, which must then be cleared from the EEPROM.
+    crash_acknowledged: bool,
+    /// Whether the current run was started by a watchdog reset, kept until the host acknowledges
+    /// it.
+    watchdog_recovered: bool,
+    /// Whether the monitor saw a power button press that must wake up the host.
+    remote_wakeup_pending: bool,
+    /// Whether the host allowed the device to wake it up, as last seen by the USB interrupts.
+    remote_wakeup_allowed: bool,
+    /// Whether the backlight is lit, as last seen by the monitor.
+    backlight_lit: bool,
+    /// The power and LEDs state requested by the host for the next [`Command::Resync`].
+    resync_target: ResyncTarget,
+    /// Lifetime usage counters.
+    odometer: OdometerTracker,
+    /// FIFO queue of the trace entries yet to be streamed over the debug console, backed by a
+    /// [`CircularBuffer`] of length [`SharedState::TRACE_QUEUE_SIZE`].
+    #[cfg(feature = "debug-console")]
+    trace_queue: CircularBuffer<{ Self::TRACE_QUEUE_SIZE }, Trace>,
 }
 
 impl SharedState {
     /// Arbitrarily chosen to just be big enough to provide some command backlog when under high load.
     /// [`Command`] is one byte, so this isn't too much out of the total 2560 RAM available.
     const COMMAND_QUEUE_SIZE: usize = 64;
+    /// Reports are only produced by infrequent events, such as button gestures, so only a small
+    /// backlog is needed.
+    const REPORT_QUEUE_SIZE: usize = 8;
+    /// Enough for the bursts of a press sequence, such as a resync, between two USB polls.
+    #[cfg(feature = "debug-console")]
+    const TRACE_QUEUE_SIZE: usize = 16;
 
     const fn new() -> Self {
         Self {
             device_state: DeviceState::new(),
             send_state: true,
+            report_queue: CircularBuffer::new(),
             command_queue: CircularBuffer::new(),
+            emulating: None,
+            bootloader_guard: BootloaderGuard::new(),
+            heartbeat: Heartbeat::new(),
+            usb_suspended: false,
+            fan_curve: FanCurve::DEFAULT,
+            fan_curve_changed: false,
+            press_timings: PressTimings::DEFAULT,
+            press_timings_changed: false,
+            remote_wakeup: false,
+            remote_wakeup_changed: false,
+            crash: None,
+            crash_acknowledged: false,
+            watchdog_recovered: false,
+            remote_wakeup_pending: false,
+            remote_wakeup_allowed: false,
+            backlight_lit: false,
+            resync_target: ResyncTarget {
+                power_enabled: true,
+                leds_enabled: true,
+            },
+            odometer: OdometerTracker::new(),
+            #[cfg(feature = "debug-console")]
+            trace_queue: CircularBuffer::new(),
         }
     }
 
@@ -75,6 +155,171 @@ impl SharedState {
         self.command_queue.pop_back()
     }
 
+    /// Sets the power and LEDs state found out by the device itself, e.g. on startup instead of the
+    /// assumed defaults.
+    #[inline]
+    pub fn set_power_and_leds(&mut self, power_enabled: bool, leds_enabled: bool) {
+        if self.device_state.power_enabled() != power_enabled {
+            self.device_state.toggle_power();
+        }
+
+        if self.device_state.leds_enabled() != leds_enabled {
+            self.device_state.toggle_leds();
+        }
+    }
+
+    /// Restores the fan curve persisted by a previous run.
+    #[inline]
+    pub fn restore_fan_curve(&mut self, fan_curve: FanCurve) {
+        self.fan_curve = fan_curve;
+    }
+
+    /// Returns the fan curve if it was changed by the host since the last call, so it can be
+    /// persisted.
+    #[inline]
+    pub fn take_changed_fan_curve(&mut self) -> Option<FanCurve> {
+        core::mem::take(&mut self.fan_curve_changed).then_some(self.fan_curve)
+    }
+
+    /// Returns the timings of the emulated presses and the monitor thresholds.
+    #[inline]
+    pub fn press_timings(&self) -> PressTimings {
+        self.press_timings
+    }
+
+    /// Restores the press timings persisted by a previous run.
+    #[inline]
+    pub fn restore_press_timings(&mut self, press_timings: PressTimings) {
+        self.press_timings = press_timings;
+    }
+
+    /// Returns the press timings if they were changed by the host since the last call, so they can
+    /// be persisted.
+    #[inline]
+    pub fn take_changed_press_timings(&mut self) -> Option<PressTimings> {
+        core::mem::take(&mut self.press_timings_changed).then_some(self.press_timings)
+    }
+
+    /// Restores the remote wakeup setting persisted by a previous run.
+    #[inline]
+    pub fn restore_remote_wakeup(&mut self, enabled: bool) {
+        self.remote_wakeup = enabled;
+    }
+
+    /// Returns the remote wakeup setting if it was changed by the host since the last call, so it
+    /// can be persisted.
+    #[inline]
+    pub fn take_changed_remote_wakeup(&mut self) -> Option<bool> {
+        core::mem::take(&mut self.remote_wakeup_changed).then_some(self.remote_wakeup)
+    }
+
+    /// Restores the crash report persisted by a previous run, for the host to read.
+    #[inline]
+    pub fn restore_crash(&mut self, report: CrashReport) {
+        self.crash = Some(report);
+    }
+
+    /// Whether the host acknowledged the crash report since the last call, in which case it can be
+    /// cleared from the EEPROM.
+    #[inline]
+    pub fn take_crash_acknowledged(&mut self) -> bool {
+        core::mem::take(&mut self.crash_acknowledged)
+    }
+
+    /// Whether the host must be woken up, clearing the request. Requests the host cannot be woken up
+    /// for, because the USB got resumed or the host does not allow it, get dropped.
+    #[inline]
+    pub fn take_remote_wakeup_pending(&mut self) -> bool {
+        core::mem::take(&mut self.remote_wakeup_pending)
+            && self.usb_suspended
+            && self.remote_wakeup_allowed
+    }
+
+    /// Queues an entry of the debug console trace, dropping the oldest one if the console falls
+    /// behind.
+    #[cfg(feature = "debug-console")]
+    #[inline]
+    pub fn trace(&mut self, trace: Trace) {
+        self.trace_queue.push_front(trace);
+    }
+
+    /// Restores the odometer persisted by a previous run.
+    #[inline]
+    pub fn restore_odometer(&mut self, odometer: Odometer) {
+        self.odometer.restore(odometer);
+    }
+
+    /// Returns the odometer if it is due for a flush to the EEPROM.
+    #[inline]
+    pub fn take_odometer_flush(&mut self) -> Option<Odometer> {
+        self.odometer.take_flush()
+    }
+
+    /// Corrects the tracked fan speed with the one sensed from the fan voltage, sending a state
+    /// update if they differ.
+    ///
+    /// Pending commands are about to change the fan speed, so the sensed one is only trusted when
+    /// there are none.
+    #[cfg(feature = "fan-sense")]
+    #[inline]
+    pub fn fan_speed_sensed(&mut self, fan_speed: FanSpeed) {
+        if !self.device_state.power_enabled() || !self.command_queue.is_empty() {
+            return;
+        }
+
+        if self.device_state.fan_speed() != fan_speed {
+            self.update_device_state(|ds| ds.set_fan_speed(fan_speed));
+        }
+    }
+
+    /// Corrects the tracked LEDs state with the one sensed from the LED strip, sending a state
+    /// update if they differ.
+    ///
+    /// The LED strip is unlit while the cooler is powered off, regardless of the LEDs state, so
+    /// nothing is corrected then.
+    #[cfg(feature = "led-sense")]
+    #[inline]
+    pub fn leds_sensed(&mut self, leds_enabled: bool) {
+        if !self.device_state.power_enabled() {
+            return;
+        }
+
+        if self.device_state.leds_enabled() != leds_enabled {
+            self.update_device_state(DeviceState::toggle_leds);
+        }
+    }
+
+    /// Sets the command about to be executed, if it emulates button presses.
+    #[inline]
+    pub fn set_emulating(&mut self, emulating: Option<Command>) {
+        self.emulating = emulating;
+    }
+
+    /// Sets the state the cooler was re-homed to, clearing the uncertainty flag.
+    #[inline]
+    pub fn resynced(&mut self, fan_speed: FanSpeed, target: ResyncTarget) {
+        self.set_power_and_leds(target.power_enabled, target.leds_enabled);
+        self.update_device_state(|ds| {
+            ds.set_fan_speed(fan_speed);
+            ds.set_uncertain(false);
+        });
+    }
+
+    /// Returns whether the buttons are currently being pressed by the device itself.
+    #[inline]
+    fn is_emulating(&self) -> bool {
+        self.emulating.is_some()
+    }
+
+    /// Flags the tracked state as possibly drifted from the real one, until the next resync.
+    #[inline]
+    fn flag_uncertain(&mut self) {
+        if !self.device_state.uncertain() {
+            self.device_state.set_uncertain(true);
+            self.send_state = true;
+        }
+    }
+
     /// Pushes a [`Command`] to the front of the queue.
     #[inline]
     fn push_command(&mut self, command: Command) {
@@ -93,15 +338,173 @@ impl SharedState {
         self.send_state = true;
     }
 
-    /// Executes the closure if [`SharedState::send_state`] is `true` and, if the closure returns
-    /// `true`, sets [`SharedState::send_state`] to false.
+    /// Queues a [`Command::Resync`] towards the power and LEDs state requested by the host.
+    #[inline]
+    fn request_resync(&mut self, target: ResyncTarget) {
+        self.resync_target = target;
+        self.push_command(Command::Resync);
+    }
+
+    /// Queues the [`DeviceReport::OdometerChunk`] reports of the odometer.
+    #[inline]
+    fn report_odometer(&mut self) {
+        let odometer = self.odometer.odometer();
+
+        for index in 0..Odometer::CHUNKS {
+            if let Some(chunk) = odometer.chunk(index) {
+                self.push_report(DeviceReport::OdometerChunk { index, chunk });
+            }
+        }
+    }
+
+    /// Pushes a [`DeviceReport`] to the front of the report queue.
+    #[inline]
+    fn push_report(&mut self, report: DeviceReport) {
+        self.report_queue.push_front(report);
+    }
+
+    /// Sets the fan curve sent by the host.
+    #[inline]
+    fn set_fan_curve(&mut self, fan_curve: FanCurve) {
+        // Spare the EEPROM from writing the same curve on every host start.
+        if self.fan_curve != fan_curve {
+            self.fan_curve = fan_curve;
+            self.fan_curve_changed = true;
+        }
+    }
+
+    /// Sets the press timings written by the host through the feature report.
+    #[inline]
+    fn set_press_timings(&mut self, press_timings: PressTimings) {
+        if self.press_timings != press_timings {
+            self.press_timings = press_timings;
+            self.press_timings_changed = true;
+        }
+    }
+
+    /// Sets whether a power button press wakes up the host, reporting it back once applied.
+    #[inline]
+    fn set_remote_wakeup(&mut self, enabled: bool) {
+        if self.remote_wakeup != enabled {
+            self.remote_wakeup = enabled;
+            self.remote_wakeup_changed = true;
+        }
+
+        self.push_report(DeviceReport::RemoteWakeup(enabled));
+    }
+
+    /// Registers a power button press of the user, which wakes up the host if enabled and the USB
+    /// is suspended.
+    #[inline]
+    fn power_pressed(&mut self) {
+        if self.remote_wakeup && self.usb_suspended && !self.is_emulating() {
+            self.remote_wakeup_pending = true;
+        }
+    }
+
+    /// Picks the fan speed for the host temperature through the fan curve, moving a step towards
+    /// it.
+    ///
+    /// Nothing is done while the cooler is powered off, as the speed presses are ignored, or while
+    /// commands are still pending, as the tracked fan speed is not up to date until they execute.
+    #[inline]
+    fn temperature(&mut self, temp_c: u8) {
+        if !self.device_state.power_enabled() || !self.command_queue.is_empty() {
+            return;
+        }
+
+        let current = self.device_state.fan_speed();
+        let next = self.fan_curve.next_speed(current, temp_c);
+
+        let command = match (next as u8).cmp(&(current as u8)) {
+            Ordering::Greater => DeviceCommand::SpeedUp,
+            Ordering::Less => DeviceCommand::SpeedDown,
+            Ordering::Equal => return,
+        };
+
+        self.push_command(Command::Device(command));
+    }
+
+    /// Registers a heartbeat from the host, reporting the end of the fail-safe mode if it was
+    /// active.
+    #[inline]
+    fn heartbeat(&mut self, timeout_s: u8, failsafe_speed: FanSpeed) {
+        if self.heartbeat.beat(timeout_s, failsafe_speed) {
+            self.push_report(DeviceReport::Failsafe(false));
+        }
+    }
+
+    /// Marks whether the USB is suspended. The heartbeat countdown starts over on resume.
+    #[inline]
+    fn set_usb_suspended(&mut self, suspended: bool) {
+        self.usb_suspended = suspended;
+
+        if !suspended {
+            self.heartbeat.rewind();
+        }
+    }
+
+    /// Accounts for a second in the odometer and advances the heartbeat countdown by a second,
+    /// entering the fail-safe mode when it runs out.
+    ///
+    /// The fail-safe mode powers the cooler on, if needed, and queues the speed presses that get
+    /// it to the fail-safe fan speed.
+    #[inline]
+    fn clock_tick(&mut self) {
+        self.odometer.tick(&self.device_state);
+
+        if self.usb_suspended {
+            return;
+        }
+
+        let Some(failsafe_speed) = self.heartbeat.tick() else {
+            return;
+        };
+
+        self.push_report(DeviceReport::Failsafe(true));
+
+        if !self.device_state.power_enabled() {
+            self.push_command(Command::Device(DeviceCommand::PowerOn));
+        }
+
+        let current = u8::from(self.device_state.fan_speed());
+        let target = u8::from(failsafe_speed);
+        let command = if target > current {
+            DeviceCommand::SpeedUp
+        } else {
+            DeviceCommand::SpeedDown
+        };
+
+        for _ in 0..current.abs_diff(target) {
+            self.push_command(Command::Device(command));
+        }
+    }
+
+    /// Pushes a [`ButtonEvent`] report unless the buttons are being pressed by the device itself.
+    #[inline]
+    fn push_button_event(&mut self, event: ButtonEvent) {
+        if !self.is_emulating() {
+            self.push_report(DeviceReport::ButtonEvent(event));
+        }
+    }
+
+    /// Executes the closure with the next report to send, if any, and, if the closure returns
+    /// `true`, marks the report as sent.
+    ///
+    /// The device state takes precedence over the queued reports.
     #[inline]
-    fn if_send_state<F>(&mut self, f: F)
+    fn if_send_report<F>(&mut self, f: F)
     where
-        F: FnOnce() -> bool,
+        F: FnOnce(DeviceReport) -> bool,
     {
-        if self.send_state && f() {
-            self.send_state = false;
+        if self.send_state {
+            if f(DeviceReport::State(self.device_state)) {
+                self.send_state = false;
+            }
+        } else if let Some(report) = self.report_queue.back().copied() {
+            if f(report) {
+                self.report_queue.pop_back();
+            }
         }
     }
 }
@@ -110,6 +513,8 @@ impl SharedState {
 /// dealing with the underlying type.
 ///
 /// The purpose of this cell is to initialize statics that will get used exclusively in interrupts.
+/// Their contents must not be accessed from the main loop, not even within a critical section, as
+/// nothing keeps an interrupt from holding a reference to them at the same time.
 pub struct InterruptCell<T>(UnsafeCell<MaybeUninit<T>>);
 
 /// This implementation does not rely on `T: Sync` as well because of
@@ -4,27 +4,47 @@
 #![no_std]
 #![no_main]
 
-use arduino_hal::{Pins, delay_ms, hal::Wdt};
+use arduino_hal::{Pins, delay_ms};
 use avr_device::{asm::sleep, interrupt};
+#[cfg(any(feature = "fan-sense", feature = "led-sense", feature = "temp-probe"))]
+use device::clock::uptime_s;
+#[cfg(feature = "debug-console")]
+use device::console::Trace;
+#[cfg(feature = "fan-sense")]
+use device::fan_sense::FanSense;
+#[cfg(feature = "led-sense")]
+use device::led_sense::LedSense;
+#[cfg(feature = "temp-probe")]
+use device::temp_probe::TempProbe;
 use device::{
     SHARED_STATE,
+    bootloader::enter_bootloader,
     button::{LedButton, PowerButton, SpeedDownButton, SpeedUpButton},
+    clock::setup_clock,
     command::Command,
-    enter_bootloader,
-    monitor::setup_timed_monitor,
-    usb::setup_usb,
+    crash::{self, report_crash},
+    discovery::{discover_state, resync},
+    monitor::setup_monitor,
+    reset::setup_reset_cause,
+    storage::Storage,
+    usb::{remote_wakeup, setup_usb},
+    watchdog::{Watchdog, report_recovery},
 };
-use panic_halt as _;
-use shared::{DeviceCommand, FanSpeed};
+use shared::DeviceCommand;
 
 #[arduino_hal::entry]
 fn main() -> ! {
     let peripherals = arduino_hal::Peripherals::take().unwrap();
     // Disable the analog comparator
     peripherals.AC.acsr.write(|w| w.acd().set_bit());
-    // Disable ADC
-    peripherals.ADC.adcsra.write(|w| w.aden().clear_bit());
-    peripherals.CPU.prr0.write(|w| w.pradc().set_bit());
+    // Disable ADC, unless used for sensing the fan voltage or the temperature
+    #[cfg(not(any(feature = "fan-sense", feature = "temp-probe")))]
+    {
+        peripherals.ADC.adcsra.write(|w| w.aden().clear_bit());
+        peripherals.CPU.prr0.write(|w| w.pradc().set_bit());
+    }
+    #[cfg(any(feature = "fan-sense", feature = "temp-probe"))]
+    let mut adc = arduino_hal::Adc::new(peripherals.ADC, Default::default());
     // Disable the on-chip debug system
     peripherals.CPU.mcucr.write(|w| w.jtd().set_bit());
     // Disable TWI
@@ -38,18 +58,32 @@ fn main() -> ! {
     peripherals.USART1.ucsr1b.write(|w| w.txen1().clear_bit());
     peripherals.CPU.prr1.write(|w| w.prusart1().set_bit());
     // Disable power to unused timers
-    peripherals.CPU.prr0.write(|w| w.prtim1().set_bit());
     peripherals.CPU.prr1.write(|w| w.prtim3().set_bit());
     peripherals.CPU.prr1.write(|w| w.prtim4().set_bit());
 
     let pll = peripherals.PLL;
     let timer = peripherals.TC0;
+    let clock_timer = peripherals.TC1;
+    let eeprom = peripherals.EEPROM;
     let usb = peripherals.USB_DEVICE;
     let wdt = peripherals.WDT;
 
+    let pins = arduino_hal::pins!(peripherals);
+
+    #[cfg(feature = "fan-sense")]
+    let fan_sense_pin = pins.a0.into_analog_input(&mut adc);
+    #[cfg(feature = "temp-probe")]
+    let temp_probe_pin = pins.a2.into_analog_input(&mut adc);
+    #[cfg(feature = "led-sense")]
+    let led_sense = LedSense::new(pins.a1.into_floating_input());
+
+    // The event driven monitor needs interrupt capable pins for these.
+    #[cfg(not(feature = "event-monitor"))]
+    let (backlight_mon_pin, speed_down_mon_pin) = (pins.d5, pins.d6);
+    #[cfg(feature = "event-monitor")]
+    let (backlight_mon_pin, speed_down_mon_pin) = (pins.d2, pins.d3);
+
     let Pins {
-        d5: backlight_mon_pin,
-        d6: speed_down_mon_pin,
         d7: speed_up_mon_pin,
         d8: led_mon_pin,
         d9: power_mon_pin,
@@ -58,42 +92,190 @@ fn main() -> ! {
         miso: speed_up_btn_pin,
         sck: speed_down_btn_pin,
         ..
-    } = arduino_hal::pins!(peripherals);
+    } = pins;
 
     // Create buttons
-    let mut speed_up_btn = SpeedUpButton::new(speed_up_btn_pin.into_output());
-    let mut speed_down_btn = SpeedDownButton::new(speed_down_btn_pin.into_output());
-    let mut power_btn = PowerButton::new(power_btn_pin.into_output());
-    let mut led_btn = LedButton::new(led_btn_pin.into_output());
+    let mut speed_up_btn = SpeedUpButton::new(speed_up_btn_pin);
+    let mut speed_down_btn = SpeedDownButton::new(speed_down_btn_pin);
+    let mut power_btn = PowerButton::new(power_btn_pin);
+    let mut led_btn = LedButton::new(led_btn_pin);
 
-    // Create the watchdog timer
-    let watchdog = Wdt::new(wdt, &peripherals.CPU.mcusr);
+    // Find out why the device was reset, before the watchdog timer clears the reset flags
+    setup_reset_cause(&peripherals.CPU.mcusr);
 
-    // Setup the timed monitor
-    setup_timed_monitor(
-        &timer,
+    // Start the liveness watchdog
+    let mut watchdog = Watchdog::new(wdt, &peripherals.CPU.mcusr);
+
+    // Report a crash from the previous run, if any.
+    let mut storage = Storage::new(eeprom);
+    report_crash(&storage);
+    report_recovery();
+
+    // Restore the press timings set by the host in a previous run, if any, before the first
+    // emulated press.
+    if let Some(press_timings) = storage.read_press_timings() {
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .restore_press_timings(press_timings)
+        });
+    }
+
+    #[cfg(feature = "fan-sense")]
+    let mut fan_sense = FanSense::new(&mut adc, fan_sense_pin, &storage);
+    #[cfg(feature = "temp-probe")]
+    let mut temp_probe = TempProbe::new(&mut adc, temp_probe_pin);
+    #[cfg(any(feature = "fan-sense", feature = "led-sense", feature = "temp-probe"))]
+    let mut last_sense_s = 0;
+
+    // Find out the real cooler state before the first state report goes out. This also ensures a
+    // consistent lowest fan speed on startup, which is what brings the cooler back to a known state
+    // after a watchdog reset.
+    let backlight_mon_pin = backlight_mon_pin.into_pull_up_input();
+    discover_state(
+        &backlight_mon_pin,
+        &mut speed_down_btn,
+        &mut power_btn,
+        #[cfg(feature = "fan-sense")]
+        &mut fan_sense,
+        #[cfg(feature = "fan-sense")]
+        &mut adc,
+        #[cfg(feature = "led-sense")]
+        &led_sense,
+    );
+
+    // Setup the monitor
+    setup_monitor(
+        timer,
+        #[cfg(feature = "event-monitor")]
+        &peripherals.EXINT,
         speed_up_mon_pin.into_pull_up_input(),
         speed_down_mon_pin.into_pull_up_input(),
         power_mon_pin.into_pull_up_input(),
         led_mon_pin.into_pull_up_input(),
-        backlight_mon_pin.into_pull_up_input(),
+        backlight_mon_pin,
     );
 
+    // Setup the uptime clock
+    setup_clock(clock_timer);
+
     // Setup USB.
     //
     // For reasons beyond my understanding the USB must get setup AFTER the timer or it won't work
     // correctly.
     setup_usb(pll, usb);
 
-    // Do some speed down button presses to always ensure a consistent lowest fan speed on startup.
-    for _ in 0..FanSpeed::Speed6 as u8 {
-        speed_down_btn.short_press();
+    // Restore the fan curve set by the host in a previous run, if any.
+    if let Some(fan_curve) = storage.read_fan_curve() {
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .restore_fan_curve(fan_curve)
+        });
+    }
+
+    // Restore the remote wakeup setting.
+    let remote_wakeup_enabled = storage.read_remote_wakeup();
+    interrupt::free(|cs| {
+        SHARED_STATE
+            .borrow(cs)
+            .borrow_mut()
+            .restore_remote_wakeup(remote_wakeup_enabled)
+    });
+
+    // Carry on with the lifetime usage counters of the previous runs.
+    if let Some(odometer) = storage.read_odometer() {
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .restore_odometer(odometer)
+        });
     }
 
     // Enable interrupts globally.
     unsafe { interrupt::enable() };
 
     loop {
+        // Each iteration is progress for the main loop.
+        watchdog.feed();
+
+        // Persist the fan curve outside of the critical section, as EEPROM writes are slow.
+        let fan_curve = interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .take_changed_fan_curve()
+        });
+
+        if let Some(fan_curve) = fan_curve {
+            storage.write_fan_curve(fan_curve);
+        }
+
+        let press_timings = interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .take_changed_press_timings()
+        });
+
+        if let Some(press_timings) = press_timings {
+            storage.write_press_timings(press_timings);
+        }
+
+        if interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .take_crash_acknowledged()
+        }) {
+            storage.clear_crash_report();
+        }
+
+        let remote_wakeup_enabled = interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .take_changed_remote_wakeup()
+        });
+
+        if let Some(enabled) = remote_wakeup_enabled {
+            storage.write_remote_wakeup(enabled);
+        }
+
+        // Wake up the host on a power button press while suspended, if enabled. The resume
+        // handling takes over from there.
+        if interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .take_remote_wakeup_pending()
+        }) {
+            remote_wakeup();
+        }
+
+        let odometer =
+            interrupt::free(|cs| SHARED_STATE.borrow(cs).borrow_mut().take_odometer_flush());
+
+        if let Some(odometer) = odometer {
+            storage.write_odometer(odometer);
+        }
+
+        // Sense the cooler state once per second, in between commands.
+        #[cfg(any(feature = "fan-sense", feature = "led-sense", feature = "temp-probe"))]
+        if uptime_s() != last_sense_s {
+            last_sense_s = uptime_s();
+
+            #[cfg(feature = "fan-sense")]
+            fan_sense.sense(&mut adc);
+            #[cfg(feature = "led-sense")]
+            led_sense.sense();
+            #[cfg(feature = "temp-probe")]
+            temp_probe.sense(&mut adc);
+        }
+
         // Check if a command has been received.
         //
         // NOTE: We try to keep the critical section as short as possible here and do the
@@ -103,16 +285,32 @@ fn main() -> ! {
             let power_enabled = shared_state.device_state().power_enabled();
             let leds_enabled = shared_state.device_state().leds_enabled();
 
-            loop {
+            let command = loop {
                 // Ignore commands that are inconsistent with the current state.
                 break match shared_state.pop_command() {
                     Some(Command::Device(DeviceCommand::PowerOn)) if power_enabled => continue,
                     Some(Command::Device(DeviceCommand::PowerOff)) if !power_enabled => continue,
                     Some(Command::Device(DeviceCommand::LedsOn)) if leds_enabled => continue,
                     Some(Command::Device(DeviceCommand::LedsOff)) if !leds_enabled => continue,
+                    // The color only cycles while the cooler and its LEDs are on.
+                    Some(Command::SetLedColor(_)) if !power_enabled || !leds_enabled => continue,
                     command => command,
                 };
+            };
+
+            // Let the monitor know whether the upcoming presses are our own.
+            shared_state.set_emulating(command.filter(|command| command.emulates_presses()));
+
+            #[cfg(feature = "debug-console")]
+            if let Some(command) = command {
+                shared_state.trace(Trace::Command(command));
+            }
+
+            if let Some(Command::Device(command)) = command {
+                crash::set_last_command(command);
             }
+
+            command
         });
 
         // Execute the command outside of the critical section.
@@ -123,12 +321,52 @@ fn main() -> ! {
                 power_btn.short_press()
             }
             Some(Command::Device(DeviceCommand::LedsOn | DeviceCommand::LedsOff)) => {
-                led_btn.long_press()
+                led_btn.long_press();
+
+                // The monitor does not track the LEDs state with LED sensing, so sense it right
+                // away for a timely state update.
+                #[cfg(feature = "led-sense")]
+                led_sense.sense();
             }
             Some(Command::Device(DeviceCommand::LedsColorChange)) => led_btn.short_press(),
+            Some(Command::SetLedColor(led_color)) => {
+                let presses = interrupt::free(|cs| {
+                    let shared_state = SHARED_STATE.borrow(cs).borrow();
+                    shared_state
+                        .device_state()
+                        .led_color()
+                        .presses_to(led_color)
+                });
+
+                for _ in 0..presses {
+                    led_btn.short_press();
+                }
+            }
             Some(Command::Delay275Ms) => delay_ms(275),
-            Some(Command::EnterBootloader) => enter_bootloader(watchdog),
+            Some(Command::EnterBootloader) => enter_bootloader(watchdog.into_inner()),
+            Some(Command::Resync) => resync(
+                &mut speed_up_btn,
+                &mut speed_down_btn,
+                &mut power_btn,
+                &mut led_btn,
+                &mut watchdog,
+                #[cfg(feature = "led-sense")]
+                &led_sense,
+            ),
+            #[cfg(feature = "fan-sense")]
+            Some(Command::CalibrateFanSense) => fan_sense.calibrate(
+                &mut adc,
+                &mut speed_up_btn,
+                &mut speed_down_btn,
+                &mut watchdog,
+                &mut storage,
+            ),
             None => sleep(),
         }
+
+        #[cfg(feature = "fan-sense")]
+        if command.is_some_and(Command::emulates_presses) {
+            fan_sense.settle();
+        }
     }
 }
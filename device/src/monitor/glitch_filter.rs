@@ -0,0 +1,75 @@
+use shared::PressTimings;
+
+use super::mask;
+
+/// Glitch filter for a button monitor pin.
+///
+/// The samples come out of the filter [`GlitchFilter::DELAY_MS`] late, which leaves room for
+/// telling whether a dropout in a press is the button getting released or a glitch, such as a
+/// contact bounce or an EMI spike: the press resuming within the tolerance means a glitch, which
+/// gets filled in as if the button was held all along. Both the press and the release get delayed
+/// the same, so the press lengths, and with them the cooler's 40ms/1400ms semantics, are preserved.
+pub(super) struct GlitchFilter {
+    /// Raw samples, the newest one in the lowest bit.
+    window: u64,
+    /// The last sample that came out of the filter.
+    pressed: bool,
+    /// Glitches filled in since the last [`GlitchFilter::take_rejected`] call.
+    rejected: u8,
+}
+
+impl GlitchFilter {
+    /// How late the samples come out of the filter, enough for seeing the end of the longest
+    /// dropout that can be tolerated.
+    pub(super) const DELAY_MS: u8 = *PressTimings::DROPOUT_RANGE.end();
+
+    #[inline]
+    pub(super) const fn new() -> Self {
+        Self {
+            window: 0,
+            pressed: false,
+            rejected: 0,
+        }
+    }
+
+    /// Pushes a raw sample and returns the one from [`GlitchFilter::DELAY_MS`] ago, with a dropout
+    /// in a press filled in if the press resumes within `tolerance_ms`.
+    ///
+    /// The tolerance must not exceed [`GlitchFilter::DELAY_MS`].
+    #[inline]
+    pub(super) fn filter(&mut self, raw: bool, tolerance_ms: u8) -> bool {
+        self.window = (self.window << 1) | u64::from(raw);
+        let mut sample = (self.window >> Self::DELAY_MS) & 1 != 0;
+
+        if self.pressed && !sample {
+            // A dropout starts. Look for the press resuming within the tolerance.
+            let resumed = self.window
+                & mask(Self::DELAY_MS.into())
+                & !mask((Self::DELAY_MS - tolerance_ms).into());
+
+            if resumed != 0 {
+                // Fill the dropout in, up to where the press resumes.
+                let resumed_at = u64::BITS - resumed.leading_zeros();
+                self.window |= mask((Self::DELAY_MS + 1).into()) & !mask(resumed_at);
+                self.rejected = self.rejected.saturating_add(1);
+                sample = true;
+            }
+        }
+
+        self.pressed = sample;
+        sample
+    }
+
+    /// Returns the number of glitches filled in since the last call.
+    #[inline]
+    pub(super) fn take_rejected(&mut self) -> u8 {
+        core::mem::take(&mut self.rejected)
+    }
+
+    /// Whether the button was released for the whole filter window.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    pub(super) fn is_idle(&self) -> bool {
+        self.window == 0
+    }
+}
@@ -6,3 +6,31 @@ use crate::monitor::MONITOR_CTX;
 fn TIMER0_COMPA() {
     MONITOR_CTX.as_inner_mut().monitor();
 }
+
+/// Power and LED monitor pins.
+#[cfg(feature = "event-monitor")]
+#[interrupt(atmega32u4)]
+fn PCINT0() {
+    MONITOR_CTX.as_inner_mut().wake();
+}
+
+/// Speed down monitor pin.
+#[cfg(feature = "event-monitor")]
+#[interrupt(atmega32u4)]
+fn INT0() {
+    MONITOR_CTX.as_inner_mut().wake();
+}
+
+/// Backlight monitor pin.
+#[cfg(feature = "event-monitor")]
+#[interrupt(atmega32u4)]
+fn INT1() {
+    MONITOR_CTX.as_inner_mut().wake();
+}
+
+/// Speed up monitor pin.
+#[cfg(feature = "event-monitor")]
+#[interrupt(atmega32u4)]
+fn INT6() {
+    MONITOR_CTX.as_inner_mut().wake();
+}
@@ -1,6 +1,9 @@
+mod glitch_filter;
 mod interrupts;
 mod pins;
 
+#[cfg(feature = "event-monitor")]
+use arduino_hal::pac::EXINT;
 use arduino_hal::{
     pac::TC0,
     port::{
@@ -9,42 +12,65 @@ use arduino_hal::{
     },
 };
 use avr_device::interrupt;
+use glitch_filter::GlitchFilter;
 use pins::{
     BacklightMonitorPin, LedMonitorPin, PowerMonitorPin, SpeedDownMonitorPin, SpeedUpMonitorPin,
 };
-use shared::{DeviceCommand, DeviceState};
+use shared::{ButtonEvent, DeviceCommand, DeviceReport, DeviceState, PressTimings};
 
-use crate::{InterruptCell, SHARED_STATE, SharedState, command::Command};
+#[cfg(feature = "debug-console")]
+use crate::console::Trace;
+use crate::{InterruptCell, SHARED_STATE, SharedState, command::Command, watchdog::Watchdog};
 
 /// Monitor context that gets setup prior to enabling interrupts and is used exclusively from the
-/// `TIMER0_COMPA` interrupt.
+/// `TIMER0_COMPA` interrupt and, with the `event-monitor` feature, the pin interrupts.
 static MONITOR_CTX: InterruptCell<MonitorContext> = InterruptCell::uninit();
 
 /// Sets up `TIMER0_COMPA` interrupt to trigger every millisecond for time tracking and constructs
 /// the [`InterruptCell`] used exclusively within it.
 ///
+/// With the `event-monitor` feature the timer is not started here. Instead, the `PCINT0`, `INT0`,
+/// `INT1` and `INT6` interrupts are armed on the monitor pins and the timer only runs while a press
+/// is being classified.
+///
 /// Timer comparison value formula: 16 MHz / (64 * (1 + 249)) = 1000 Hz
-pub fn setup_timed_monitor(
-    timer: &TC0,
+pub fn setup_monitor(
+    timer: TC0,
+    #[cfg(feature = "event-monitor")] exint: &EXINT,
     speed_up_mon_pin: Pin<Input<PullUp>, SpeedUpMonitorPin>,
     speed_down_mon_pin: Pin<Input<PullUp>, SpeedDownMonitorPin>,
     power_mon_pin: Pin<Input<PullUp>, PowerMonitorPin>,
     led_mon_pin: Pin<Input<PullUp>, LedMonitorPin>,
     backlight_mon_pin: Pin<Input<PullUp>, BacklightMonitorPin>,
 ) {
-    // WGM
-    timer.tccr0a.write(|w| w.wgm0().bits(0b10));
-    timer.tccr0b.write(|w| w.wgm02().clear_bit());
+    let timer = MonitorTimer::new(timer);
 
-    // Prescaler
-    timer.tccr0b.write(|w| w.cs0().prescale_64());
-    timer.ocr0a.write(|w| w.bits(249));
+    #[cfg(not(feature = "event-monitor"))]
+    timer.start();
 
-    // Enable the timer interrupt
-    timer.timsk0.write(|w| w.ocie0a().set_bit());
+    #[cfg(feature = "event-monitor")]
+    {
+        // Trigger `INT0`, `INT1` and `INT6` on any logical change.
+        exint.eicra.write(|w| unsafe { w.bits(0b0000_0101) });
+        exint.eicrb.write(|w| unsafe { w.bits(0b0001_0000) });
+        // Trigger `PCINT0` on changes of `PCINT4` and `PCINT5`.
+        exint.pcmsk0.write(|w| unsafe { w.bits(0b0011_0000) });
+
+        // Clear any pending flags so that no spurious wake happens once interrupts get enabled.
+        exint.eifr.write(|w| unsafe { w.bits(0b0100_0011) });
+        exint.pcifr.write(|w| unsafe { w.bits(0b0000_0001) });
+
+        // Enable the interrupts.
+        exint.eimsk.write(|w| unsafe { w.bits(0b0100_0011) });
+        exint.pcicr.write(|w| unsafe { w.bits(0b0000_0001) });
+
+        // The monitor starts out idle.
+        Watchdog::set_monitor_idle(true);
+    }
 
     // Initialize the timer context.
     MONITOR_CTX.init(MonitorContext::new(
+        timer,
         SpeedUpButtonMonitor::new(speed_up_mon_pin),
         SpeedDownButtonMonitor::new(speed_down_mon_pin),
         PowerButtonMonitor::new(power_mon_pin),
@@ -68,6 +94,7 @@ type LedButtonMonitor = ButtonMonitor<LedMonitorPin>;
 /// Notable mentions:
 /// - Short presses are presses at least as long as 40ms.
 /// - Long presses are presses at least as long as 1400ms.
+/// - Both thresholds are [`PressTimings`] that the host can tune for units that differ.
 /// - Buttons are not handled individually; their state is shared. This means that pressing a button
 ///   for 10ms and another one for 30ms will result in a button priority being enforced.
 /// - Button priority is: Speed Up > Speed Down > Power > LED
@@ -78,7 +105,30 @@ type LedButtonMonitor = ButtonMonitor<LedMonitorPin>;
 ///   press!
 /// - After a short/long press being triggered, no button presses get registered anymore until all
 ///   buttons get released. Not even the backlight gets woken up!
+///
+/// On top of that, gestures without a dedicated meaning for the cooler are detected and reported
+/// to the host as [`ButtonEvent`] values. Presses emulated by the device itself are not considered
+/// for gestures.
+///
+/// The monitor also flags the tracked state as uncertain when it sees signs of the cooler
+/// registering a press that the monitor did not, see [`DriftDetector`], or when a press on the
+/// power or LED button gets interrupted by another button, which leaves the outcome up to the
+/// cooler.
+///
+/// The button pins go through a [`GlitchFilter`] each, which fills in dropouts too short to be a
+/// release, e.g. contact bounces or EMI spikes, and counts them in the odometer. The backlight pin
+/// gets delayed the same as the buttons to stay in sync with them.
+///
+/// With the `event-monitor` feature, the timer gets started by [`MonitorContext::wake`] on any
+/// monitor pin change and is stopped again as soon as the monitor is idle, which is whenever it is
+/// [`MonitorState::Active`] with no button pressed. The press detection itself is identical.
 struct MonitorContext {
+    /// The timer calling [`MonitorContext::monitor`].
+    #[cfg_attr(
+        not(feature = "event-monitor"),
+        expect(dead_code, reason = "the timer is never stopped")
+    )]
+    timer: MonitorTimer,
     /// Speed up button monitor.
     speed_up_monitor: SpeedUpButtonMonitor,
     /// Speed down button monitor.
@@ -100,11 +150,16 @@ struct MonitorContext {
     /// reaches max value and is reset. It helps with tracking potential long presses.
     /// Similar to [`buttons_state`], this makes part of the button state shared by all buttons.
     buttons_history: u8,
+    /// Tracks short presses for detecting double presses.
+    double_press: DoublePressTracker,
+    /// Tracks presses and backlight changes for detecting a drift of the tracked state.
+    drift: DriftDetector,
 }
 
 impl MonitorContext {
     #[inline]
     fn new(
+        timer: MonitorTimer,
         speed_up_monitor: SpeedUpButtonMonitor,
         speed_down_monitor: SpeedDownButtonMonitor,
         power_monitor: PowerButtonMonitor,
@@ -112,9 +167,12 @@ impl MonitorContext {
         backlight_monitor: BacklightMonitor,
     ) -> Self {
         Self {
+            timer,
             monitor_state: MonitorState::Active,
             buttons_state: 0,
             buttons_history: 0,
+            double_press: DoublePressTracker::new(),
+            drift: DriftDetector::new(),
             speed_up_monitor,
             speed_down_monitor,
             power_monitor,
@@ -126,19 +184,59 @@ impl MonitorContext {
     /// Run the monitor over the physical components. Meant to be ran exactly once per millisecond.
     #[inline]
     fn monitor(&mut self) {
+        Watchdog::monitor_ticked();
+
         interrupt::free(|cs| {
             let shared_state = &mut *SHARED_STATE.borrow(cs).borrow_mut();
-
-            let speed_up_pressed = self.speed_up_monitor.is_pressed();
-            let speed_down_pressed = self.speed_down_monitor.is_pressed();
-            let power_pressed = self.power_monitor.is_pressed();
-            let led_pressed = self.led_monitor.is_pressed();
+            #[cfg(feature = "debug-console")]
+            let previous_state = core::mem::discriminant(&self.monitor_state);
+
+            let timings = shared_state.press_timings;
+
+            // Once a press got past the short press threshold, longer dropouts are tolerated as
+            // the button is known to be held.
+            let tolerance_ms = match self.monitor_state {
+                MonitorState::Active => timings.sub_threshold_dropout_ms,
+                MonitorState::Paused | MonitorState::Focused(_) => timings.within_press_dropout_ms,
+            };
+
+            let speed_up_pressed = self.speed_up_monitor.is_pressed(tolerance_ms);
+            let speed_down_pressed = self.speed_down_monitor.is_pressed(tolerance_ms);
+            let power_pressed = self.power_monitor.is_pressed(tolerance_ms);
+            let led_pressed = self.led_monitor.is_pressed(tolerance_ms);
+
+            let rejected_glitches = self.speed_up_monitor.take_rejected()
+                + self.speed_down_monitor.take_rejected()
+                + self.power_monitor.take_rejected()
+                + self.led_monitor.take_rejected();
+            shared_state
+                .odometer
+                .count_rejected_glitches(rejected_glitches);
 
             let backlight_active = self.backlight_monitor.is_active();
+            let backlight_woke = self.backlight_monitor.take_woke();
+            shared_state.backlight_lit = self.backlight_monitor.is_lit();
 
             let any_button_pressed =
                 speed_up_pressed || speed_down_pressed || power_pressed || led_pressed;
 
+            self.double_press.tick();
+
+            if self.drift.tick(
+                any_button_pressed,
+                backlight_woke,
+                timings.monitor_short_press_ms,
+            ) {
+                shared_state.flag_uncertain();
+            }
+
+            // The power and LEDs chord requires the LEDs button to be held for the entire press.
+            if let MonitorState::Focused(MonitorFocusTarget::Power { with_leds }) =
+                &mut self.monitor_state
+            {
+                *with_leds &= led_pressed;
+            }
+
             match &self.monitor_state {
                 MonitorState::Active => {
                     self.buttons_state = (self.buttons_state << 1) ^ u64::from(any_button_pressed);
@@ -146,9 +244,17 @@ impl MonitorContext {
                     // A button short press requires the state to be low for 40ms. We therefore
                     // look for a sequence of a 0 bit followed by 40 `1` bits and handle that
                     // depending on the button priority and which ones are pressed.
-                    if self.buttons_state << 23 == 0x7FFF_FFFF_FF80_0000 {
+                    let short_press_ms = u32::from(timings.monitor_short_press_ms);
+
+                    if self.buttons_state & mask(short_press_ms + 1) == mask(short_press_ms) {
                         if speed_up_pressed {
-                            self.monitor_state = MonitorState::Paused;
+                            self.press_registered(shared_state);
+
+                            if speed_down_pressed {
+                                shared_state.push_button_event(ButtonEvent::SpeedChord);
+                            } else {
+                                self.short_press(shared_state, ButtonEvent::SpeedUpDoublePress);
+                            }
 
                             Self::speed_button_pressed(
                                 shared_state,
@@ -157,7 +263,8 @@ impl MonitorContext {
                                 DeviceCommand::SpeedUp,
                             );
                         } else if speed_down_pressed {
-                            self.monitor_state = MonitorState::Paused;
+                            self.press_registered(shared_state);
+                            self.short_press(shared_state, ButtonEvent::SpeedDownDoublePress);
 
                             Self::speed_button_pressed(
                                 shared_state,
@@ -166,7 +273,9 @@ impl MonitorContext {
                                 DeviceCommand::SpeedDown,
                             );
                         } else if power_pressed {
-                            self.monitor_state = MonitorState::Focused(MonitorFocusTarget::Power);
+                            self.monitor_state = MonitorState::Focused(MonitorFocusTarget::Power {
+                                with_leds: led_pressed,
+                            });
                         } else if led_pressed {
                             self.monitor_state = MonitorState::Focused(MonitorFocusTarget::Leds);
                         }
@@ -180,53 +289,164 @@ impl MonitorContext {
                     }
                 }
                 MonitorState::Focused(kind) => {
-                    let (button_pressed, short_press_fn_opt, long_press_fn_opt, command) =
-                        match kind {
-                            MonitorFocusTarget::Power => (
-                                power_pressed,
-                                Some(DeviceState::toggle_power),
-                                None,
-                                Some(Command::EnterBootloader),
-                            ),
-                            MonitorFocusTarget::Leds => {
-                                (led_pressed, None, Some(DeviceState::toggle_leds), None)
-                            }
-                        };
+                    let (
+                        button_pressed,
+                        other_button_pressed,
+                        short_press_fn,
+                        long_press_fn_opt,
+                        double_press_event,
+                        long_press_event,
+                    ) = match kind {
+                        MonitorFocusTarget::Power { with_leds } => (
+                            power_pressed,
+                            // The LEDs button is part of the power and LEDs chord.
+                            speed_up_pressed || speed_down_pressed,
+                            DeviceState::toggle_power,
+                            None,
+                            ButtonEvent::PowerDoublePress,
+                            Some(if *with_leds {
+                                ButtonEvent::PowerLedsChord
+                            } else {
+                                ButtonEvent::PowerLongPress
+                            }),
+                        ),
+                        MonitorFocusTarget::Leds => (
+                            led_pressed,
+                            speed_up_pressed || speed_down_pressed || power_pressed,
+                            // Counts the emulated presses as well, as they go through the monitor.
+                            Self::led_button_pressed,
+                            // With LED sensing the LEDs state comes from the LED strip instead.
+                            if cfg!(feature = "led-sense") {
+                                None
+                            } else {
+                                Some(DeviceState::toggle_leds)
+                            },
+                            ButtonEvent::LedsDoublePress,
+                            None,
+                        ),
+                    };
+
+                    if other_button_pressed {
+                        shared_state.flag_uncertain();
+                    }
 
                     self.buttons_state = (self.buttons_state << 1) ^ u64::from(button_pressed);
 
-                    if self.buttons_history < 21 {
+                    // A long press requires the state to be low for 1400ms, which is 21 full
+                    // buttons states followed by 56 `1` bits.
+                    let long_press_ms = timings.monitor_long_press_ms;
+                    let long_press_history = u8::try_from(long_press_ms / 64).unwrap_or(u8::MAX);
+                    let long_press_state = mask((long_press_ms % 64).into());
+
+                    if self.buttons_history < long_press_history {
                         if self.buttons_state == u64::MAX {
                             self.buttons_state = 0;
                             self.buttons_history += 1;
                         } else if !button_pressed {
                             // Short press triggered
-                            self.monitor_state = MonitorState::Paused;
+                            self.press_registered(shared_state);
+                            self.short_press(shared_state, double_press_event);
+                            shared_state.update_device_state(short_press_fn);
 
-                            if let Some(short_press_fn) = short_press_fn_opt {
-                                shared_state.update_device_state(short_press_fn);
-                            } else {
-                                // LED button short press
-                                //
-                                // For visibility, we still want to the state to be sent.
-                                shared_state.update_device_state(|_| ());
+                            if matches!(kind, MonitorFocusTarget::Power { .. }) {
+                                shared_state.power_pressed();
                             }
                         }
-                    } else if self.buttons_state == 0x00FF_FFFF_FFFF_FFFF {
+                    }
+
+                    if self.buttons_history == long_press_history
+                        && self.buttons_state == long_press_state
+                    {
                         // Long press triggered
-                        self.monitor_state = MonitorState::Paused;
+                        self.press_registered(shared_state);
 
                         if let Some(long_press_fn) = long_press_fn_opt {
                             shared_state.update_device_state(long_press_fn);
                         }
 
-                        if let Some(command) = command {
-                            shared_state.push_command(command);
+                        if let Some(event) = long_press_event {
+                            let enter_bootloader = match event {
+                                ButtonEvent::PowerLedsChord => true,
+                                _ => shared_state.bootloader_guard.take_armed(),
+                            };
+
+                            if enter_bootloader {
+                                shared_state.push_command(Command::EnterBootloader);
+                            }
+
+                            shared_state.push_button_event(event);
                         }
                     }
                 }
             }
+
+            #[cfg(feature = "debug-console")]
+            if core::mem::discriminant(&self.monitor_state) != previous_state {
+                shared_state.trace(Trace::Monitor(self.monitor_state.name()));
+            }
         });
+
+        // In the active state the last bit of the buttons state tells whether any button is
+        // pressed. If none is, there's nothing to classify until the next pin change.
+        // The timer is also kept running while a double press can still happen or while the glitch
+        // filters still hold presses that did not come out of them yet.
+        #[cfg(feature = "event-monitor")]
+        if matches!(self.monitor_state, MonitorState::Active)
+            && self.buttons_state & 1 == 0
+            && self.double_press.is_idle()
+            && self.speed_up_monitor.is_idle()
+            && self.speed_down_monitor.is_idle()
+            && self.power_monitor.is_idle()
+            && self.led_monitor.is_idle()
+        {
+            self.buttons_state = 0;
+            self.timer.stop();
+            Watchdog::set_monitor_idle(true);
+        }
+    }
+
+    /// Starts the timer, if not already running, on monitor pin changes.
+    ///
+    /// The backlight state gets refreshed before the timer starts as it is not tracked while the
+    /// monitor is idle.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    fn wake(&mut self) {
+        if !self.timer.is_running() {
+            Watchdog::set_monitor_idle(false);
+            self.backlight_monitor.refresh();
+            self.timer.start();
+        }
+    }
+
+    /// Pauses the monitor once a press got registered, counting the press in the odometer.
+    #[inline]
+    fn press_registered(&mut self, shared_state: &mut SharedState) {
+        self.monitor_state = MonitorState::Paused;
+        let emulated = shared_state.is_emulating();
+        shared_state.odometer.count_press(emulated);
+    }
+
+    /// Registers a short press for double press detection, reporting the double press event if
+    /// completed.
+    #[inline]
+    fn short_press(&mut self, shared_state: &mut SharedState, double_press_event: ButtonEvent) {
+        if shared_state.is_emulating() {
+            return;
+        }
+
+        if let Some(event) = self.double_press.short_press(double_press_event) {
+            shared_state.push_report(DeviceReport::ButtonEvent(event));
+        }
+    }
+
+    /// Handles the device state change when a short press gets registered on the LEDs button, which
+    /// only cycles the color while the cooler and its LEDs are on.
+    #[inline]
+    fn led_button_pressed(device_state: &mut DeviceState) {
+        if device_state.power_enabled() && device_state.leds_enabled() {
+            device_state.next_led_color();
+        }
     }
 
     /// Dedicated method that handles the device state changes when a short press gets registered on
@@ -248,6 +468,16 @@ impl MonitorContext {
         if backlight_active {
             // The backlight being active means the device will register the command.
             shared_state.update_device_state(state_change_fn);
+        } else if shared_state
+            .emulating
+            .is_some_and(Command::covers_backlight_wake)
+        {
+            // The backlight gets woken up but the command itself gets ignored. The command being
+            // executed already makes up for it.
+        } else if shared_state.is_emulating() && shared_state.heartbeat.is_failsafe() {
+            // The backlight gets woken up but the command itself gets ignored. There's no host to
+            // repeat our own press in fail-safe mode, so it gets repeated locally.
+            shared_state.push_command(Command::Device(repeat_command));
         } else {
             // The backlight gets woken up but the command itself gets ignored.
             shared_state.update_device_state(|ds: &mut DeviceState| {
@@ -267,6 +497,9 @@ impl MonitorContext {
 struct BacklightMonitor {
     /// Physical pin
     pin: Pin<Input<PullUp>, BacklightMonitorPin>,
+    /// Raw samples, the newest one in the lowest bit. They get used [`GlitchFilter::DELAY_MS`]
+    /// late, the same as the button samples.
+    window: u64,
     /// The last known state of the backlight.
     ///
     /// Helps avoid situations when the backlight is not initially active but a speed button press
@@ -274,13 +507,19 @@ struct BacklightMonitor {
     /// speed commands are not being registered when the backlight was initially off at the
     /// beginning of the press.
     was_active: bool,
+    /// Whether the backlight lit up since the last [`BacklightMonitor::take_woke`] call.
+    woke: bool,
 }
 
 impl BacklightMonitor {
     #[inline]
     fn new(pin: Pin<Input<PullUp>, BacklightMonitorPin>) -> Self {
+        let was_active = pin.is_low();
+
         Self {
-            was_active: pin.is_low(),
+            window: if was_active { u64::MAX } else { 0 },
+            was_active,
+            woke: false,
             pin,
         }
     }
@@ -293,13 +532,204 @@ impl BacklightMonitor {
     #[inline]
     fn is_active(&mut self) -> bool {
         let prev_state = self.was_active;
-        self.was_active = self.pin.is_low();
+        self.was_active = self.sample();
+        self.woke |= !prev_state && self.was_active;
         prev_state && self.was_active
     }
+
+    /// Pushes a raw sample and returns the one from [`GlitchFilter::DELAY_MS`] ago.
+    #[inline]
+    fn sample(&mut self) -> bool {
+        self.window = (self.window << 1) | u64::from(self.pin.is_low());
+        (self.window >> GlitchFilter::DELAY_MS) & 1 != 0
+    }
+
+    /// Returns whether the backlight is lit, as of the last check.
+    #[inline]
+    fn is_lit(&self) -> bool {
+        self.was_active
+    }
+
+    /// Returns whether the backlight lit up since the last call.
+    #[inline]
+    fn take_woke(&mut self) -> bool {
+        core::mem::take(&mut self.woke)
+    }
+
+    /// Refreshes the last known state of the backlight without reporting it.
+    ///
+    /// The samples are not delayed here as the buttons were released for the whole delay.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    fn refresh(&mut self) {
+        let prev_state = self.was_active;
+        self.was_active = self.pin.is_low();
+        self.window = if self.was_active { u64::MAX } else { 0 };
+        self.woke |= !prev_state && self.was_active;
+    }
+}
+
+/// Wrapper over the `TC0` timer configured to trigger `TIMER0_COMPA` every millisecond.
+struct MonitorTimer(TC0);
+
+impl MonitorTimer {
+    /// Configures the timer in CTC mode without starting it.
+    #[inline]
+    fn new(timer: TC0) -> Self {
+        // WGM
+        timer.tccr0a.write(|w| w.wgm0().bits(0b10));
+        timer
+            .tccr0b
+            .write(|w| w.wgm02().clear_bit().cs0().no_clock());
+
+        // Comparison value
+        timer.ocr0a.write(|w| w.bits(249));
+
+        // Enable the timer interrupt
+        timer.timsk0.write(|w| w.ocie0a().set_bit());
+
+        Self(timer)
+    }
+
+    /// Starts the timer from scratch by resetting its counter and setting the prescaler.
+    #[inline]
+    fn start(&self) {
+        self.0.tcnt0.write(|w| w.bits(0));
+        self.0.tifr0.write(|w| w.ocf0a().set_bit());
+        self.0.tccr0b.write(|w| w.cs0().prescale_64());
+    }
+
+    /// Stops the timer by removing its clock source.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    fn stop(&self) {
+        self.0.tccr0b.write(|w| w.cs0().no_clock());
+    }
+
+    /// Returns whether the timer has a clock source.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    fn is_running(&self) -> bool {
+        !self.0.tccr0b.read().cs0().is_no_clock()
+    }
+}
+
+/// Tracker of short presses that reports double presses.
+///
+/// A double press is two short presses of the same button, with the second one getting registered
+/// at most [`DoublePressTracker::WINDOW_MS`] after the first one.
+struct DoublePressTracker {
+    /// The double press event that the last short press would complete.
+    last_press: Option<ButtonEvent>,
+    /// Milliseconds left for a short press to complete a double press.
+    window_ms: u16,
+}
+
+impl DoublePressTracker {
+    const WINDOW_MS: u16 = 500;
+
+    #[inline]
+    fn new() -> Self {
+        Self {
+            last_press: None,
+            window_ms: 0,
+        }
+    }
+
+    /// Advances the tracker by one millisecond.
+    #[inline]
+    fn tick(&mut self) {
+        self.window_ms = self.window_ms.saturating_sub(1);
+    }
+
+    /// Whether no double press can currently happen.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.window_ms == 0
+    }
+
+    /// Registers a short press, returning the double press event if this press completes one.
+    #[inline]
+    fn short_press(&mut self, double_press_event: ButtonEvent) -> Option<ButtonEvent> {
+        if self.window_ms > 0 && self.last_press == Some(double_press_event) {
+            self.last_press = None;
+            self.window_ms = 0;
+            Some(double_press_event)
+        } else {
+            self.last_press = Some(double_press_event);
+            self.window_ms = Self::WINDOW_MS;
+            None
+        }
+    }
+}
+
+/// Detector of a drift of the tracked state from the real cooler state.
+///
+/// The backlight lighting up is how the cooler acknowledges a press. It lighting up with no press
+/// at all, or for a press shorter than a short press, means that the cooler registered a press
+/// that the monitor did not.
+struct DriftDetector {
+    /// Length of the ongoing or last press, in milliseconds.
+    press_ms: u8,
+    /// Milliseconds since the last press got released, zero while a press is ongoing.
+    release_ms: u8,
+    /// Whether the backlight lit up during the ongoing or last press.
+    woke_during_press: bool,
+}
+
+impl DriftDetector {
+    /// How long after a release the backlight lighting up is still attributed to the press, as it
+    /// can take a moment, e.g. when powering on.
+    const RELEASE_WINDOW_MS: u8 = 100;
+
+    #[inline]
+    fn new() -> Self {
+        Self {
+            press_ms: u8::MAX,
+            release_ms: u8::MAX,
+            woke_during_press: false,
+        }
+    }
+
+    /// Advances the detector by one millisecond, returning whether the tracked state may have
+    /// drifted.
+    ///
+    /// Presses shorter than `short_press_ms` do not get registered by the monitor.
+    #[inline]
+    fn tick(&mut self, button_pressed: bool, backlight_woke: bool, short_press_ms: u8) -> bool {
+        if button_pressed {
+            if self.release_ms > 0 {
+                // A new press started.
+                self.press_ms = 0;
+                self.release_ms = 0;
+                self.woke_during_press = false;
+            }
+
+            self.press_ms = self.press_ms.saturating_add(1);
+            self.woke_during_press |= backlight_woke;
+            return false;
+        }
+
+        let just_released = self.release_ms == 0;
+        self.release_ms = self.release_ms.saturating_add(1);
+        let too_short = self.press_ms < short_press_ms;
+
+        if just_released {
+            too_short && self.woke_during_press
+        } else {
+            backlight_woke && (too_short || self.release_ms > Self::RELEASE_WINDOW_MS)
+        }
+    }
 }
 
 /// A physical button monitor.
-struct ButtonMonitor<PIN>(Pin<Input<PullUp>, PIN>);
+struct ButtonMonitor<PIN> {
+    /// Physical pin
+    pin: Pin<Input<PullUp>, PIN>,
+    /// Filter of the glitches on the pin.
+    filter: GlitchFilter,
+}
 
 impl<PIN> ButtonMonitor<PIN>
 where
@@ -307,16 +737,39 @@ where
 {
     #[inline]
     fn new(pin: Pin<Input<PullUp>, PIN>) -> Self {
-        Self(pin)
+        Self {
+            pin,
+            filter: GlitchFilter::new(),
+        }
+    }
+
+    /// Returns whether the button is pressed, as of [`GlitchFilter::DELAY_MS`] ago, tolerating
+    /// dropouts up to `tolerance_ms` long. Meant to be called exactly once per millisecond.
+    #[inline]
+    fn is_pressed(&mut self, tolerance_ms: u8) -> bool {
+        self.filter.filter(self.pin.is_low(), tolerance_ms)
+    }
+
+    /// Returns the number of glitches rejected since the last call.
+    #[inline]
+    fn take_rejected(&mut self) -> u8 {
+        self.filter.take_rejected()
     }
 
-    /// Returns whether the button is pressed.
+    /// Whether the button was released for the whole glitch filter window.
+    #[cfg(feature = "event-monitor")]
     #[inline]
-    fn is_pressed(&self) -> bool {
-        self.0.is_low()
+    fn is_idle(&self) -> bool {
+        self.filter.is_idle()
     }
 }
 
+/// Mask of the given number of lowest bits.
+#[inline]
+const fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
 /// Monitor state enum.
 enum MonitorState {
     /// The monitor is active and listening for interactions. This could mean that a button press is
@@ -333,9 +786,26 @@ enum MonitorState {
     Focused(MonitorFocusTarget),
 }
 
+#[cfg(feature = "debug-console")]
+impl MonitorState {
+    /// Returns the name of the state, as shown in the debug console trace.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Paused => "paused",
+            Self::Focused(MonitorFocusTarget::Power { .. }) => "focused on power",
+            Self::Focused(MonitorFocusTarget::Leds) => "focused on leds",
+        }
+    }
+}
+
 /// What button the monitor is focused on. This enum contains variants only for buttons that have a
 /// long press.
 enum MonitorFocusTarget {
-    Power,
+    /// Whether the LEDs button was also held for the whole press is tracked for detecting
+    /// [`ButtonEvent::PowerLedsChord`].
+    Power {
+        with_leds: bool,
+    },
     Leds,
 }
@@ -1,7 +1,19 @@
-use arduino_hal::hal::port::{PB4, PB5, PC6, PD7, PE6};
+use arduino_hal::hal::port::{PB4, PB5, PE6};
+#[cfg(not(feature = "event-monitor"))]
+use arduino_hal::hal::port::{PC6, PD7};
+#[cfg(feature = "event-monitor")]
+use arduino_hal::hal::port::{PD0, PD1};
 
 pub type SpeedUpMonitorPin = PE6;
+#[cfg(not(feature = "event-monitor"))]
 pub type SpeedDownMonitorPin = PD7;
+/// `PD7` cannot trigger interrupts, so the event driven monitor uses `INT0` instead.
+#[cfg(feature = "event-monitor")]
+pub type SpeedDownMonitorPin = PD0;
 pub type PowerMonitorPin = PB5;
 pub type LedMonitorPin = PB4;
+#[cfg(not(feature = "event-monitor"))]
 pub type BacklightMonitorPin = PC6;
+/// `PC6` cannot trigger interrupts, so the event driven monitor uses `INT1` instead.
+#[cfg(feature = "event-monitor")]
+pub type BacklightMonitorPin = PD1;
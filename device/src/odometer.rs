@@ -0,0 +1,97 @@
+use shared::{DeviceState, Odometer};
+
+/// Keeps the lifetime usage counters up to date and schedules their flushes to the EEPROM.
+///
+/// The counters live in RAM and only get flushed every [`OdometerTracker::FLUSH_INTERVAL_S`], on
+/// host suspend and on reset, and only if they changed. Together with the rotation of the EEPROM
+/// slots done by [`crate::storage::Storage::write_odometer`], this keeps the EEPROM well within
+/// its write endurance over the lifetime of the cooler, at the cost of losing up to the flush
+/// interval worth of usage on power loss.
+#[derive(Debug)]
+pub(crate) struct OdometerTracker {
+    odometer: Odometer,
+    /// Whether the counters changed since the last flush.
+    changed: bool,
+    /// Seconds since the last flush.
+    unflushed_s: u16,
+    /// Whether the counters must be flushed without waiting for the flush interval.
+    flush_now: bool,
+}
+
+impl OdometerTracker {
+    const FLUSH_INTERVAL_S: u16 = 15 * 60;
+
+    pub(crate) const fn new() -> Self {
+        Self {
+            odometer: Odometer::new(),
+            changed: false,
+            unflushed_s: 0,
+            flush_now: false,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn odometer(&self) -> Odometer {
+        self.odometer
+    }
+
+    /// Restores the counters persisted by a previous run.
+    #[inline]
+    pub(crate) fn restore(&mut self, odometer: Odometer) {
+        self.odometer = odometer;
+    }
+
+    /// Accounts for a second spent in the given state.
+    #[inline]
+    pub(crate) fn tick(&mut self, device_state: &DeviceState) {
+        self.unflushed_s = self.unflushed_s.saturating_add(1);
+        self.changed |= device_state.power_enabled();
+        self.odometer.tick(device_state);
+    }
+
+    #[inline]
+    pub(crate) fn count_press(&mut self, emulated: bool) {
+        self.changed = true;
+        self.odometer.count_press(emulated);
+    }
+
+    #[inline]
+    pub(crate) fn count_rejected_glitches(&mut self, glitches: u8) {
+        if glitches > 0 {
+            self.changed = true;
+            self.odometer.count_rejected_glitches(glitches);
+        }
+    }
+
+    /// Counts a host suspend and flushes the counters, as the host suspending is a good hint that
+    /// the power may go away.
+    #[inline]
+    pub(crate) fn count_suspend(&mut self) {
+        self.changed = true;
+        self.flush_now = true;
+        self.odometer.count_suspend();
+    }
+
+    /// Resets all the counters to zero and flushes them.
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        self.odometer = Odometer::new();
+        self.changed = true;
+        self.flush_now = true;
+    }
+
+    /// Returns the counters if they are due for a flush.
+    #[inline]
+    pub(crate) fn take_flush(&mut self) -> Option<Odometer> {
+        let due = self.flush_now || self.unflushed_s >= Self::FLUSH_INTERVAL_S;
+
+        if !due || !self.changed {
+            return None;
+        }
+
+        self.changed = false;
+        self.unflushed_s = 0;
+        self.flush_now = false;
+        Some(self.odometer)
+    }
+}
@@ -0,0 +1,71 @@
+use core::{cell::Cell, mem::MaybeUninit, ptr};
+
+use arduino_hal::{
+    hal::{Wdt, wdt::Timeout},
+    pac::cpu::MCUSR,
+};
+use avr_device::interrupt::{self, Mutex};
+use shared::ResetCause;
+
+/// Marker kept in RAM across resets, telling what the firmware was doing when the reset happened.
+///
+/// The `.noinit` section is neither zeroed nor initialized on startup, so the value survives
+/// anything but a power loss.
+#[unsafe(link_section = ".noinit")]
+static mut RESET_MARKER: MaybeUninit<u32> = MaybeUninit::uninit();
+
+/// Cause of the reset that started the current firmware run.
+static RESET_CAUSE: Mutex<Cell<ResetCause>> = Mutex::new(Cell::new(ResetCause::PowerOn));
+
+/// Arbitrary marker values, unlikely to be found in RAM after a power on.
+const RUNNING: u32 = 0x5255_4E21;
+const PANIC: u32 = 0x5041_4E21;
+const BOOTLOADER: u32 = 0x424F_4F54;
+
+/// Determines the cause of the reset that started the firmware and marks the firmware as running.
+///
+/// Must be called before [`Wdt::new`], which clears the watchdog reset flag. The Caterina
+/// bootloader clears all the reset flags anyway, so they are only trusted when set.
+pub fn setup_reset_cause(mcusr: &MCUSR) {
+    let flags = mcusr.read();
+    // SAFETY: Reading the marker might yield garbage after a power on, but any value is valid.
+    let marker = unsafe { ptr::read_volatile(&raw const RESET_MARKER).assume_init() };
+
+    let cause = match marker {
+        _ if flags.porf().bit_is_set() || flags.borf().bit_is_set() => ResetCause::PowerOn,
+        PANIC => ResetCause::Panic,
+        BOOTLOADER => ResetCause::Bootloader,
+        RUNNING => ResetCause::Watchdog,
+        _ if flags.wdrf().bit_is_set() => ResetCause::Watchdog,
+        _ => ResetCause::PowerOn,
+    };
+
+    write_marker(RUNNING);
+    interrupt::free(|cs| RESET_CAUSE.borrow(cs).set(cause));
+}
+
+/// Returns the cause of the reset that started the current firmware run.
+#[inline]
+pub fn reset_cause() -> ResetCause {
+    interrupt::free(|cs| RESET_CAUSE.borrow(cs).get())
+}
+
+/// Triggers a watchdog reset, recording the cause for the next firmware run.
+pub fn reset(mut watchdog: Wdt, cause: ResetCause) -> ! {
+    write_marker(match cause {
+        ResetCause::Panic => PANIC,
+        ResetCause::Bootloader => BOOTLOADER,
+        ResetCause::PowerOn | ResetCause::Watchdog => RUNNING,
+    });
+
+    // Set the lowest possible time value for the watchdog.
+    watchdog.start(Timeout::Ms16).ok();
+
+    // Loop until the watchdog reset happens.
+    loop {}
+}
+
+#[inline]
+fn write_marker(marker: u32) {
+    unsafe { ptr::write_volatile(&raw mut RESET_MARKER, MaybeUninit::new(marker)) };
+}
@@ -0,0 +1,191 @@
+use arduino_hal::{Eeprom, pac::EEPROM};
+use shared::{CrashReport, FanCurve, FanSenseCalibration, Odometer, PressTimings};
+
+/// Data persisted in the EEPROM.
+///
+/// Layout:
+/// - `0x000`: crash record, a [`Storage::VALID`] byte followed by a serialized [`CrashReport`].
+/// - `0x010`: fan curve, a [`Storage::VALID`] byte followed by a serialized [`FanCurve`].
+/// - `0x020`: fan voltage sensing calibration, a [`Storage::VALID`] byte followed by a serialized
+///   [`FanSenseCalibration`].
+/// - `0x030`: press timings, a [`Storage::VALID`] byte followed by serialized [`PressTimings`].
+/// - `0x03A`: remote wakeup setting, a [`Storage::VALID`] byte if enabled.
+/// - `0x040`: odometer, [`Storage::ODOMETER_SLOTS`] slots up to the end of the EEPROM, each a
+///   little endian `u32` sequence number followed by a serialized [`Odometer`]. The slot with the
+///   highest sequence number holds the latest counters. Every write goes to the next slot, which
+///   spreads the wear over all of them. Erased sequence numbers mark empty slots.
+#[allow(
+    missing_debug_implementations,
+    reason = "arduino_hal::Eeprom does not implement Debug"
+)]
+pub struct Storage(Eeprom);
+
+impl Storage {
+    /// Marks a record as present. Erased EEPROM bytes read as `0xFF`.
+    const VALID: u8 = 0xA5;
+    const CRASH_RECORD: u16 = 0x000;
+    const FAN_CURVE: u16 = 0x010;
+    const FAN_SENSE_CALIBRATION: u16 = 0x020;
+    const PRESS_TIMINGS: u16 = 0x030;
+    const REMOTE_WAKEUP: u16 = 0x03A;
+    const ODOMETER: u16 = 0x040;
+    const ODOMETER_SLOT_LEN: u16 = 4 + Odometer::LEN as u16;
+    const ODOMETER_SLOTS: u16 = 18;
+
+    #[inline]
+    pub fn new(eeprom: EEPROM) -> Self {
+        Self(Eeprom::new(eeprom))
+    }
+
+    /// Reads the crash record, if one was written.
+    pub fn read_crash_report(&self) -> Option<CrashReport> {
+        if self.0.read_byte(Self::CRASH_RECORD) != Self::VALID {
+            return None;
+        }
+
+        let mut buf = [0; CrashReport::LEN];
+        self.0.read(Self::CRASH_RECORD + 1, &mut buf).ok()?;
+        buf[..].try_into().ok()
+    }
+
+    /// Writes the crash record, replacing any previous one.
+    pub fn write_crash_report(&mut self, report: CrashReport) {
+        let buf = <[u8; CrashReport::LEN]>::from(report);
+        self.0.write(Self::CRASH_RECORD + 1, &buf).ok();
+        // Only mark the record as present once it is complete.
+        self.0.write_byte(Self::CRASH_RECORD, Self::VALID);
+    }
+
+    /// Clears the crash record.
+    #[inline]
+    pub fn clear_crash_report(&mut self) {
+        self.0.erase_byte(Self::CRASH_RECORD);
+    }
+
+    /// Reads the fan curve, if one was written.
+    pub fn read_fan_curve(&self) -> Option<FanCurve> {
+        if self.0.read_byte(Self::FAN_CURVE) != Self::VALID {
+            return None;
+        }
+
+        let mut buf = [0; FanCurve::LEN];
+        self.0.read(Self::FAN_CURVE + 1, &mut buf).ok()?;
+        buf[..].try_into().ok()
+    }
+
+    /// Writes the fan curve, replacing any previous one.
+    pub fn write_fan_curve(&mut self, fan_curve: FanCurve) {
+        // Invalidate the record first so that a reset halfway through does not leave a mix of the
+        // old and the new curve behind.
+        self.0.erase_byte(Self::FAN_CURVE);
+        let buf = <[u8; FanCurve::LEN]>::from(fan_curve);
+        self.0.write(Self::FAN_CURVE + 1, &buf).ok();
+        self.0.write_byte(Self::FAN_CURVE, Self::VALID);
+    }
+
+    /// Reads the fan voltage sensing calibration, if one was written.
+    pub fn read_fan_sense_calibration(&self) -> Option<FanSenseCalibration> {
+        if self.0.read_byte(Self::FAN_SENSE_CALIBRATION) != Self::VALID {
+            return None;
+        }
+
+        let mut buf = [0; FanSenseCalibration::LEN];
+        self.0
+            .read(Self::FAN_SENSE_CALIBRATION + 1, &mut buf)
+            .ok()?;
+        buf[..].try_into().ok()
+    }
+
+    /// Writes the fan voltage sensing calibration, replacing any previous one.
+    pub fn write_fan_sense_calibration(&mut self, calibration: FanSenseCalibration) {
+        self.0.erase_byte(Self::FAN_SENSE_CALIBRATION);
+        let buf = <[u8; FanSenseCalibration::LEN]>::from(calibration);
+        self.0.write(Self::FAN_SENSE_CALIBRATION + 1, &buf).ok();
+        self.0.write_byte(Self::FAN_SENSE_CALIBRATION, Self::VALID);
+    }
+
+    /// Reads the press timings, if some were written.
+    pub fn read_press_timings(&self) -> Option<PressTimings> {
+        if self.0.read_byte(Self::PRESS_TIMINGS) != Self::VALID {
+            return None;
+        }
+
+        let mut buf = [0; PressTimings::LEN];
+        self.0.read(Self::PRESS_TIMINGS + 1, &mut buf).ok()?;
+        buf[..].try_into().ok()
+    }
+
+    /// Writes the press timings, replacing any previous ones.
+    pub fn write_press_timings(&mut self, timings: PressTimings) {
+        self.0.erase_byte(Self::PRESS_TIMINGS);
+        let buf = <[u8; PressTimings::LEN]>::from(timings);
+        self.0.write(Self::PRESS_TIMINGS + 1, &buf).ok();
+        self.0.write_byte(Self::PRESS_TIMINGS, Self::VALID);
+    }
+
+    /// Reads whether remote wakeup is enabled, which it is not unless written.
+    #[inline]
+    pub fn read_remote_wakeup(&self) -> bool {
+        self.0.read_byte(Self::REMOTE_WAKEUP) == Self::VALID
+    }
+
+    /// Writes whether remote wakeup is enabled.
+    #[inline]
+    pub fn write_remote_wakeup(&mut self, enabled: bool) {
+        if enabled {
+            self.0.write_byte(Self::REMOTE_WAKEUP, Self::VALID);
+        } else {
+            self.0.erase_byte(Self::REMOTE_WAKEUP);
+        }
+    }
+
+    /// Reads the latest odometer, if one was written.
+    pub fn read_odometer(&self) -> Option<Odometer> {
+        let (slot, _) = self.latest_odometer_slot()?;
+
+        let mut buf = [0; Odometer::LEN];
+        self.0
+            .read(Self::odometer_slot_address(slot) + 4, &mut buf)
+            .ok()?;
+        buf[..].try_into().ok()
+    }
+
+    /// Writes the odometer to the slot after the latest one.
+    pub fn write_odometer(&mut self, odometer: Odometer) {
+        let (slot, seq) = match self.latest_odometer_slot() {
+            Some((slot, seq)) => ((slot + 1) % Self::ODOMETER_SLOTS, seq.wrapping_add(1)),
+            None => (0, 0),
+        };
+
+        let address = Self::odometer_slot_address(slot);
+
+        // Mark the slot as empty first so that a reset halfway through leaves the latest complete
+        // slot in charge.
+        for offset in 0..4 {
+            self.0.erase_byte(address + offset);
+        }
+
+        let buf = <[u8; Odometer::LEN]>::from(odometer);
+        self.0.write(address + 4, &buf).ok();
+        self.0.write(address, &seq.to_le_bytes()).ok();
+    }
+
+    /// Returns the slot holding the latest odometer, along with its sequence number.
+    fn latest_odometer_slot(&self) -> Option<(u16, u32)> {
+        (0..Self::ODOMETER_SLOTS)
+            .filter_map(|slot| {
+                let mut buf = [0; 4];
+                self.0
+                    .read(Self::odometer_slot_address(slot), &mut buf)
+                    .ok()?;
+                let seq = u32::from_le_bytes(buf);
+                (seq != u32::MAX).then_some((slot, seq))
+            })
+            .max_by_key(|(_, seq)| *seq)
+    }
+
+    #[inline]
+    fn odometer_slot_address(slot: u16) -> u16 {
+        Self::ODOMETER + slot * Self::ODOMETER_SLOT_LEN
+    }
+}
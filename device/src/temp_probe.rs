@@ -0,0 +1,77 @@
+use arduino_hal::{
+    Adc,
+    hal::port::PF5,
+    port::{Pin, mode::Analog},
+};
+use avr_device::interrupt;
+use shared::{DeviceReport, Ntc};
+
+use crate::SHARED_STATE;
+
+/// Analog pin `A2`, wired to an NTC thermistor, as described by [`Ntc`].
+pub type TempProbePin = PF5;
+
+/// Temperature sensing through an NTC thermistor probe, e.g. taped to the laptop's exhaust, which
+/// gives the host one more input for controlling the fans.
+///
+/// The ADC is borrowed for every reading, as it is shared with the fan sensing.
+#[allow(
+    missing_debug_implementations,
+    reason = "arduino_hal::port::Pin does not implement Debug"
+)]
+pub struct TempProbe {
+    pin: Pin<Analog, TempProbePin>,
+    /// The temperature last reported, in tenths of a degree, `None` until the first reading.
+    last_temp_dc: Option<Option<u16>>,
+}
+
+impl TempProbe {
+    /// Number of ADC readings averaged into a single sample, to smooth out the noise.
+    const SAMPLES: u16 = 16;
+    /// How far the temperature must move from the one last reported, in tenths of a degree, for it
+    /// to be reported again. Keeps a temperature hovering around a rounding boundary from flooding
+    /// the host with reports.
+    const HYSTERESIS_DC: u16 = 10;
+
+    pub fn new(adc: &mut Adc, pin: Pin<Analog, TempProbePin>) -> Self {
+        // The first conversion after switching the ADC channel is less accurate, so get it out of
+        // the way.
+        pin.analog_read(adc);
+
+        Self {
+            pin,
+            last_temp_dc: None,
+        }
+    }
+
+    /// Samples the probe and reports the temperature, rounded to the nearest degree, if it moved
+    /// by at least [`TempProbe::HYSTERESIS_DC`] or the probe got connected or disconnected. Meant
+    /// to be called periodically from the main loop, in between commands.
+    pub fn sense(&mut self, adc: &mut Adc) {
+        let sum: u16 = (0..Self::SAMPLES).map(|_| self.pin.analog_read(adc)).sum();
+        let reading = sum / Self::SAMPLES;
+        let temp_dc = Ntc::temperature_dc(reading);
+
+        let changed = match (self.last_temp_dc, temp_dc) {
+            (Some(Some(last_dc)), Some(temp_dc)) => {
+                last_dc.abs_diff(temp_dc) >= Self::HYSTERESIS_DC
+            }
+            (Some(last_dc), temp_dc) => last_dc.is_some() != temp_dc.is_some(),
+            (None, _) => true,
+        };
+
+        if !changed {
+            return;
+        }
+
+        self.last_temp_dc = Some(temp_dc);
+        let temp_c = Ntc::temperature_c(reading);
+
+        interrupt::free(|cs| {
+            SHARED_STATE
+                .borrow(cs)
+                .borrow_mut()
+                .push_report(DeviceReport::ProbeTemperature(temp_c));
+        });
+    }
+}
@@ -0,0 +1,86 @@
+use shared::PressTimings;
+use usb_device::{
+    class_prelude::*,
+    control::{Recipient, Request, RequestType},
+};
+
+/// Answers the HID feature report requests, which [`usbd_hid::hid_class::HIDClass`] leaves
+/// unhandled. The feature report holds the [`PressTimings`] and is only ever exchanged through
+/// control transfers, so that reading and writing the timings does not queue up behind the
+/// commands.
+///
+/// Lives in the USB interrupts and cannot access the [`crate::SharedState`] while answering, so it
+/// keeps a copy of the timings that [`super::UsbContext::poll`] syncs.
+#[derive(Debug)]
+pub struct FeatureReport {
+    press_timings: PressTimings,
+    received: Option<PressTimings>,
+}
+
+impl FeatureReport {
+    /// The HID interface gets allocated first.
+    const INTERFACE: u16 = 0;
+    const GET_REPORT: u8 = 0x01;
+    const SET_REPORT: u8 = 0x09;
+    const FEATURE: u16 = 0x03;
+
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            press_timings: PressTimings::DEFAULT,
+            received: None,
+        }
+    }
+
+    /// Updates the timings answered to the host, unless the host has just set some that are yet to
+    /// be taken.
+    #[inline]
+    pub fn set_press_timings(&mut self, press_timings: PressTimings) {
+        if self.received.is_none() {
+            self.press_timings = press_timings;
+        }
+    }
+
+    /// Takes the timings set by the host since the last call.
+    #[inline]
+    pub fn take_received(&mut self) -> Option<PressTimings> {
+        self.received.take()
+    }
+
+    fn is_feature_request(req: &Request, request: u8) -> bool {
+        req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == Self::INTERFACE
+            && req.request == request
+            && req.value >> 8 == Self::FEATURE
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for FeatureReport {
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        if !Self::is_feature_request(xfer.request(), Self::GET_REPORT) {
+            return;
+        }
+
+        let buf = <[u8; PressTimings::LEN]>::from(self.press_timings);
+        xfer.accept_with(&buf).ok();
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        if !Self::is_feature_request(xfer.request(), Self::SET_REPORT) {
+            return;
+        }
+
+        // Invalid timings get rejected, which the host sees as a stalled transfer.
+        match PressTimings::try_from(xfer.data()) {
+            Ok(press_timings) => {
+                self.press_timings = press_timings;
+                self.received = Some(press_timings);
+                xfer.accept().ok();
+            }
+            Err(_) => {
+                xfer.reject().ok();
+            }
+        }
+    }
+}
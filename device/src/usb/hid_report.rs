@@ -1,20 +1,32 @@
+use shared::{PressTimings, REPORT_LEN};
 use usbd_hid::descriptor::{gen_hid_descriptor, generator_prelude::*};
+
 /// USB HID report.
 ///
-/// The device can only send its state when it changes (packed in a single byte) and can only
-/// receive a byte representing a command to execute.
+/// The device sends [`shared::DeviceReport`] values, such as its state when it changes, and
+/// receives [`shared::HostReport`] values, such as commands to execute. Both are serialized into
+/// [`shared::REPORT_LEN`] bytes.
+///
+/// The feature report holds the [`shared::PressTimings`], read and written by the host through
+/// control transfers. See [`super::feature_report::FeatureReport`].
 #[gen_hid_descriptor(
     (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = 0x0B) = {
         (usage_page = VENDOR_DEFINED_START, usage = 0x01) = {
-            #[item_settings data,variable,absolute] state=input;
+            #[item_settings data,variable,absolute] device_report=input;
         };
         (usage_page = VENDOR_DEFINED_START, usage = 0x02) = {
-            #[item_settings data,variable,absolute] command=output;
+            #[item_settings data,variable,absolute] host_report=output;
+        };
+        (usage_page = VENDOR_DEFINED_START, usage = 0x03) = {
+            #[item_settings data,variable,absolute] press_timings=feature;
         };
-
     }
 )]
 pub struct HidReport {
-    state: u8,
-    command: u8,
+    device_report: [u8; 16],
+    host_report: [u8; 16],
+    press_timings: [u8; 9],
 }
+
+// The descriptor macro needs literal array lengths.
+const _: () = assert!(size_of::<HidReport>() == 2 * REPORT_LEN + PressTimings::LEN);
@@ -1,3 +1,4 @@
+mod feature_report;
 mod hid_report;
 mod interrupts;
 mod suspender;
@@ -7,8 +8,12 @@ use arduino_hal::{
     usb::AvrGenericUsbBus,
 };
 use avr_device::interrupt;
+use feature_report::FeatureReport;
 use hid_report::HidReport;
-use shared::{USB_MANUFACTURER, USB_PID, USB_PRODUCT, USB_VID};
+use shared::{
+    DeviceReport, HostReport, REPORT_LEN, USB_DEVICE_RELEASE, USB_MANUFACTURER, USB_PID,
+    USB_PRODUCT, USB_VID,
+};
 use suspender::Suspender;
 use usb_device::{
     LangID,
@@ -17,7 +22,9 @@ use usb_device::{
 };
 use usbd_hid::{descriptor::SerializedDescriptor, hid_class::HIDClass};
 
-use crate::{InterruptCell, SHARED_STATE, command::Command};
+#[cfg(feature = "debug-console")]
+use crate::console::Console;
+use crate::{InterruptCell, SHARED_STATE, command::Command, discovery::ResyncTarget};
 
 type UsbBus = AvrGenericUsbBus<Suspender>;
 
@@ -53,28 +60,84 @@ pub fn setup_usb(pll: PLL, usb: USB_DEVICE) {
         .product(USB_PRODUCT);
 
     let hid_class = HIDClass::new(usb_bus, HidReport::desc(), USB_POLL_MS);
-    let usb_device = UsbDeviceBuilder::new(usb_bus, UsbVidPid(USB_VID, USB_PID))
+    #[cfg(feature = "debug-console")]
+    let console = Console::new(usb_bus);
+
+    let usb_device_builder = UsbDeviceBuilder::new(usb_bus, UsbVidPid(USB_VID, USB_PID))
+        .device_release(USB_DEVICE_RELEASE)
         .strings(&[strings])
         .unwrap()
         .max_power(500)
         .unwrap()
-        .build();
+        .supports_remote_wakeup(true);
+
+    // The CDC-ACM interface is made of two interfaces, which must be grouped for the host to tell
+    // them apart from the HID one.
+    #[cfg(feature = "debug-console")]
+    let usb_device_builder = usb_device_builder.composite_with_iads();
+
+    USB_DEVICE.init(UsbContext::new(
+        usb_device_builder.build(),
+        hid_class,
+        #[cfg(feature = "debug-console")]
+        console,
+    ));
+}
+
+/// Signals a remote wakeup to the host, provided that the USB is still suspended. Meant to be
+/// called once [`crate::SharedState::take_remote_wakeup_pending`] says so, which also checks that
+/// the host allowed it. The host then resumes the bus, which runs the resume handling of
+/// [`Suspender`].
+///
+/// The USB interrupts do not fire while the bus is suspended, so the main loop drives the upstream
+/// resume, which the bus has no support for, through the registers rather than through the
+/// [`UsbContext`].
+pub fn remote_wakeup() {
+    // SAFETY: Only the bits involved in the upstream resume are touched, within critical sections
+    // so that the USB interrupts cannot interleave.
+    let (pll, usb) = unsafe { (&*PLL::ptr(), &*USB_DEVICE::ptr()) };
+
+    // The PLL and the USB clock got stopped on suspend. The PLL lock gets waited for with the
+    // interrupts enabled, as it takes a while.
+    interrupt::free(|_| pll.pllcsr.modify(|_, w| w.plle().set_bit()));
+    while pll.pllcsr.read().plock().bit_is_clear() {}
 
-    USB_DEVICE.init(UsbContext::new(usb_device, hid_class));
+    interrupt::free(|cs| {
+        // The host may have resumed the bus in the meantime.
+        if !SHARED_STATE.borrow(cs).borrow().usb_suspended {
+            return;
+        }
+
+        usb.usbcon.modify(|_, w| w.frzclk().clear_bit());
+
+        // Cleared by the hardware once the resume signaling got sent.
+        usb.udcon.modify(|_, w| w.rmwkup().set_bit());
+    });
 }
 
-/// USB context. Contains components used exclusively in the `USB_GEN` and `USB_COM` interrupts.
+/// USB context. Contains components used exclusively in the `USB_GEN` and `USB_COM` interrupts,
+/// see [`InterruptCell`].
 struct UsbContext {
     usb_device: UsbDevice<'static, UsbBus>,
     hid_class: HIDClass<'static, UsbBus>,
+    feature_report: FeatureReport,
+    #[cfg(feature = "debug-console")]
+    console: Console<'static, UsbBus>,
 }
 
 impl UsbContext {
     #[inline]
-    fn new(usb_device: UsbDevice<'static, UsbBus>, hid_class: HIDClass<'static, UsbBus>) -> Self {
+    fn new(
+        usb_device: UsbDevice<'static, UsbBus>,
+        hid_class: HIDClass<'static, UsbBus>,
+        #[cfg(feature = "debug-console")] console: Console<'static, UsbBus>,
+    ) -> Self {
         Self {
             usb_device,
             hid_class,
+            feature_report: FeatureReport::new(),
+            #[cfg(feature = "debug-console")]
+            console,
         }
     }
 
@@ -83,8 +146,17 @@ impl UsbContext {
     fn poll(&mut self) {
         // Because this code gets called from both USB interrupts, we want to continue
         // regardless of what this function returns. Otherwise, failing to access the
-        // [`SHARED_STATE`] would result in no polling being performed.
-        self.usb_device.poll(&mut [&mut self.hid_class]);
+        // [`SHARED_STATE`] would result in no polling being performed. The feature report comes
+        // first, so that it answers its requests before the HID class gets to them.
+        #[cfg(not(feature = "debug-console"))]
+        self.usb_device
+            .poll(&mut [&mut self.feature_report, &mut self.hid_class]);
+        #[cfg(feature = "debug-console")]
+        self.usb_device.poll(&mut [
+            &mut self.feature_report,
+            &mut self.hid_class,
+            self.console.serial(),
+        ]);
 
         interrupt::free(|cs| {
             // For reasons beyond my understanding, the two USB interrupts seem to contend on the
@@ -101,20 +173,95 @@ impl UsbContext {
                 return;
             };
 
-            let device_state = *shared_state.device_state();
+            shared_state.remote_wakeup_allowed = self.usb_device.remote_wakeup_enabled();
+
+            if let Some(press_timings) = self.feature_report.take_received() {
+                shared_state.set_press_timings(press_timings);
+            }
+            self.feature_report
+                .set_press_timings(shared_state.press_timings());
 
-            shared_state.if_send_state(|| {
-                let res = self.hid_class.push_raw_input(&[device_state.into()]);
-                matches!(res, Ok(1))
+            shared_state.if_send_report(|report| {
+                let buf = <[u8; REPORT_LEN]>::from(report);
+                matches!(self.hid_class.push_raw_input(&buf), Ok(REPORT_LEN))
             });
 
-            let mut report_buf = [0u8; 1];
+            let mut report_buf = [0u8; REPORT_LEN];
+
+            if let Ok(len) = self.hid_class.pull_raw_output(&mut report_buf) {
+                match HostReport::try_from(&report_buf[..len]) {
+                    Ok(HostReport::Command(command)) => {
+                        shared_state.push_command(Command::Device(command));
+                    }
+                    Ok(HostReport::EnterBootloader(token)) => {
+                        if shared_state.bootloader_guard.confirm(token) {
+                            shared_state.push_command(Command::EnterBootloader);
+                        }
+                    }
+                    Ok(HostReport::RequestBootloaderToken) => {
+                        let token = shared_state.bootloader_guard.issue_token();
+                        shared_state.push_report(DeviceReport::BootloaderToken(token));
+                    }
+                    Ok(HostReport::ArmBootloader) => shared_state.bootloader_guard.arm(),
+                    Ok(HostReport::Heartbeat {
+                        timeout_s,
+                        failsafe_speed,
+                    }) => shared_state.heartbeat(timeout_s, failsafe_speed),
+                    Ok(HostReport::FanCurve(fan_curve)) => shared_state.set_fan_curve(fan_curve),
+                    Ok(HostReport::Temperature(temp_c)) => shared_state.temperature(temp_c),
+                    Ok(HostReport::CalibrateFanSense) => {
+                        #[cfg(feature = "fan-sense")]
+                        shared_state.push_command(Command::CalibrateFanSense);
+                    }
+                    Ok(HostReport::Resync {
+                        power_enabled,
+                        leds_enabled,
+                    }) => shared_state.request_resync(ResyncTarget {
+                        power_enabled,
+                        leds_enabled,
+                    }),
+                    Ok(HostReport::ReadOdometer) => shared_state.report_odometer(),
+                    Ok(HostReport::ResetOdometer) => shared_state.odometer.reset(),
+                    Ok(HostReport::SetLedColor(led_color)) => {
+                        let device_state = shared_state.device_state();
+                        let leds_lit = device_state.power_enabled() && device_state.leds_enabled();
 
-            if let Ok(1) = self.hid_class.pull_raw_output(&mut report_buf) {
-                if let Ok(command) = report_buf[0].try_into() {
-                    shared_state.push_command(Command::Device(command));
+                        if !leds_lit || device_state.led_color() == led_color {
+                            // Nothing to press, or the color would not cycle, but the host still
+                            // awaits the state as a confirmation.
+                            shared_state.update_device_state(|_| ());
+                        } else {
+                            shared_state.push_command(Command::SetLedColor(led_color));
+                        }
+                    }
+                    Ok(HostReport::ReadRemoteWakeup) => {
+                        let enabled = shared_state.remote_wakeup;
+                        shared_state.push_report(DeviceReport::RemoteWakeup(enabled));
+                    }
+                    Ok(HostReport::RemoteWakeup(enabled)) => {
+                        shared_state.set_remote_wakeup(enabled)
+                    }
+                    Ok(HostReport::ReadCrash) => {
+                        if let Some(report) = shared_state.crash {
+                            shared_state.push_report(DeviceReport::Crash(report));
+                        }
+
+                        if shared_state.watchdog_recovered {
+                            shared_state.push_report(DeviceReport::WatchdogRecovery);
+                        }
+                    }
+                    Ok(HostReport::AcknowledgeCrash) => {
+                        shared_state.crash_acknowledged |= shared_state.crash.take().is_some();
+                    }
+                    Ok(HostReport::AcknowledgeWatchdogRecovery) => {
+                        shared_state.watchdog_recovered = false;
+                    }
+                    Err(_) => (),
                 }
             }
+
+            #[cfg(feature = "debug-console")]
+            self.console.run(shared_state);
         });
     }
 }
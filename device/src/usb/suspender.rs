@@ -2,6 +2,8 @@ use arduino_hal::{pac::PLL, usb::SuspendNotifier};
 use avr_device::interrupt;
 use shared::DeviceCommand;
 
+#[cfg(feature = "debug-console")]
+use crate::console::Trace;
 use crate::{SHARED_STATE, command::Command};
 
 /// Implementor of [`SuspendNotifier`] whose job is to turn the LEDs and power off when the device
@@ -32,6 +34,10 @@ impl SuspendNotifier for Suspender {
             //
             // Delaying the command execution allows for the left over power to deplete, and avoid
             // initiating a long press to turn the LEDs off that will not complete.
+            #[cfg(feature = "debug-console")]
+            shared_state.trace(Trace::Suspend);
+            shared_state.set_usb_suspended(true);
+            shared_state.odometer.count_suspend();
             shared_state.push_command(Command::Delay275Ms);
             shared_state.push_command(Command::Device(DeviceCommand::LedsOff));
             shared_state.push_command(Command::Device(DeviceCommand::PowerOff));
@@ -43,6 +49,9 @@ impl SuspendNotifier for Suspender {
 
         interrupt::free(|cs| {
             let mut shared_state = SHARED_STATE.borrow(cs).borrow_mut();
+            #[cfg(feature = "debug-console")]
+            shared_state.trace(Trace::Resume);
+            shared_state.set_usb_suspended(false);
             shared_state.push_command(Command::Device(DeviceCommand::LedsOn));
             shared_state.push_command(Command::Device(DeviceCommand::PowerOn));
         });
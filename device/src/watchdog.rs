@@ -0,0 +1,114 @@
+use core::cell::Cell;
+
+use arduino_hal::{
+    hal::{Wdt, wdt::Timeout},
+    pac::{WDT, cpu::MCUSR},
+};
+use avr_device::interrupt::{self, Mutex};
+use shared::{PressTimings, ResetCause};
+
+use crate::{SHARED_STATE, reset};
+
+/// Progress flags of the monitor since the watchdog was last fed.
+static MONITOR_PROGRESS: Mutex<Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// Liveness watchdog that runs all the time and resets the device unless fed at least every
+/// [`Watchdog::TIMEOUT`].
+///
+/// It only gets fed by the main loop, and only if the monitor made progress as well, so a hang in
+/// either the main loop or an interrupt handler ends up resetting the device.
+#[allow(
+    missing_debug_implementations,
+    reason = "arduino_hal::hal::Wdt does not implement Debug"
+)]
+pub struct Watchdog(Wdt);
+
+impl Watchdog {
+    /// Comfortably longer than the longest command the main loop executes, a LEDs long press.
+    const TIMEOUT: Timeout = Timeout::Ms4000;
+    /// How long the longest runs of presses that do not feed the watchdog take at most, whatever
+    /// the press timings set by the host: the startup discovery with its 10 short presses, which
+    /// runs before the monitor does, and a single long press with its delay.
+    const WORST_CASE_UNFED_MS: u16 = {
+        let short_press_ms = *PressTimings::SHORT_PRESS_RANGE.end() as u16
+            + *PressTimings::POST_PRESS_DELAY_RANGE.end() as u16;
+        let long_press_ms = *PressTimings::LONG_PRESS_RANGE.end()
+            + *PressTimings::POST_PRESS_DELAY_RANGE.end() as u16;
+        let discovery_ms = 10 * short_press_ms;
+
+        if discovery_ms > long_press_ms {
+            discovery_ms
+        } else {
+            long_press_ms
+        }
+    };
+    /// The monitor ran since the watchdog was last fed.
+    const TICKED: u8 = 0b01;
+    /// The monitor timer is stopped because the buttons are idle, which counts as progress.
+    const IDLE: u8 = 0b10;
+
+    /// Creates and starts the watchdog.
+    ///
+    /// Must be created after [`crate::reset::setup_reset_cause`], as this clears the watchdog reset
+    /// flag.
+    pub fn new(wdt: WDT, mcusr: &MCUSR) -> Self {
+        let mut wdt = Wdt::new(wdt, mcusr);
+        wdt.start(Self::TIMEOUT).ok();
+        Self(wdt)
+    }
+
+    /// Feeds the watchdog if the monitor made progress since the last time it was fed. Meant to be
+    /// called on every main loop iteration, which is the main loop's own progress.
+    #[inline]
+    pub fn feed(&mut self) {
+        let monitor_alive = interrupt::free(|cs| {
+            let progress = MONITOR_PROGRESS.borrow(cs);
+            let flags = progress.get();
+            progress.set(flags & Self::IDLE);
+            flags != 0
+        });
+
+        if monitor_alive {
+            self.0.feed();
+        }
+    }
+
+    /// Returns the underlying [`Wdt`], e.g. for triggering a reset.
+    #[inline]
+    pub fn into_inner(self) -> Wdt {
+        self.0
+    }
+
+    /// Records that the monitor ran.
+    #[inline]
+    pub(crate) fn monitor_ticked() {
+        interrupt::free(|cs| {
+            let progress = MONITOR_PROGRESS.borrow(cs);
+            progress.set(progress.get() | Self::TICKED);
+        });
+    }
+
+    /// Records whether the monitor timer is stopped because the buttons are idle.
+    #[cfg(feature = "event-monitor")]
+    #[inline]
+    pub(crate) fn set_monitor_idle(idle: bool) {
+        interrupt::free(|cs| {
+            let progress = MONITOR_PROGRESS.borrow(cs);
+            let flags = progress.get() & !Self::IDLE;
+            progress.set(if idle { flags | Self::IDLE } else { flags });
+        });
+    }
+}
+
+// Well within the 4s timeout, so that no press timings the host sets can trigger a reset.
+const _: () = assert!(Watchdog::WORST_CASE_UNFED_MS <= 2600);
+
+/// Flags the recovery if the current firmware run was started by a watchdog reset, for the host to
+/// read as a [`shared::DeviceReport::WatchdogRecovery`] until it acknowledges it.
+pub fn report_recovery() {
+    if reset::reset_cause() != ResetCause::Watchdog {
+        return;
+    }
+
+    interrupt::free(|cs| SHARED_STATE.borrow(cs).borrow_mut().watchdog_recovered = true);
+}
@@ -0,0 +1,69 @@
+use thiserror::Error as ThisError;
+
+/// Gestures detected by the device on the physical buttons that have no dedicated meaning for the
+/// cooler itself. They are reported to the host, which is free to map them to actions.
+///
+/// The cooler still reacts to the presses making up a gesture as it normally would, e.g. a double
+/// press on the power button turns the cooler off and back on.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(test, derive(strum::EnumIter))]
+pub enum ButtonEvent {
+    /// Long press on the power button, which is a no-op for the cooler.
+    PowerLongPress = 1,
+    /// Two short presses on the power button in quick succession.
+    PowerDoublePress,
+    /// Two short presses on the LEDs button in quick succession.
+    LedsDoublePress,
+    /// Two short presses on the `+` button in quick succession.
+    SpeedUpDoublePress,
+    /// Two short presses on the `-` button in quick succession.
+    SpeedDownDoublePress,
+    /// The `+` and `-` buttons pressed together. The cooler registers a `+` press.
+    SpeedChord,
+    /// The power and LEDs buttons held together for a long press. This is a no-op for the cooler,
+    /// but the device enters bootloader mode.
+    PowerLedsChord,
+}
+
+impl From<ButtonEvent> for u8 {
+    fn from(value: ButtonEvent) -> Self {
+        value as Self
+    }
+}
+
+impl TryFrom<u8> for ButtonEvent {
+    type Error = ButtonEventConvError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ButtonEvent::PowerLongPress),
+            2 => Ok(ButtonEvent::PowerDoublePress),
+            3 => Ok(ButtonEvent::LedsDoublePress),
+            4 => Ok(ButtonEvent::SpeedUpDoublePress),
+            5 => Ok(ButtonEvent::SpeedDownDoublePress),
+            6 => Ok(ButtonEvent::SpeedChord),
+            7 => Ok(ButtonEvent::PowerLedsChord),
+            _ => Err(ButtonEventConvError),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("integer to button event conversion failed")]
+pub struct ButtonEventConvError;
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use crate::ButtonEvent;
+
+    #[test]
+    fn test_button_event_conversion() {
+        for event in ButtonEvent::iter() {
+            assert_eq!((event as u8).try_into(), Ok(event));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use thiserror::Error as ThisError;
+
+use crate::{
+    DeviceCommand, ResetCause, device_command::CommandConvError, reset_cause::ResetCauseConvError,
+};
+
+/// Compact record of a firmware panic, persisted by the device before resetting and reported to
+/// the host after the reboot.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct CrashReport {
+    /// 16-bit FNV-1a hash of the path of the source file the panic occurred in.
+    pub location: u16,
+    /// Line of the source file the panic occurred at.
+    pub line: u16,
+    /// Cause of the reset that started the firmware run which panicked.
+    pub reset_cause: ResetCause,
+    /// The last command executed before the panic.
+    pub last_command: Option<DeviceCommand>,
+    /// Seconds elapsed since the firmware started.
+    pub uptime_s: u32,
+}
+
+impl CrashReport {
+    /// Length of the serialized report.
+    pub const LEN: usize = 10;
+
+    /// Hashes the source file path of a panic location into [`CrashReport::location`].
+    #[must_use]
+    pub fn hash_location(file: &str) -> u16 {
+        let hash = file.bytes().fold(0x811C_9DC5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+
+        // XOR folding of the 32-bit FNV-1a hash, as recommended for smaller hash sizes.
+        #[expect(clippy::cast_possible_truncation, reason = "intended")]
+        let location = ((hash >> 16) ^ hash) as u16;
+        location
+    }
+}
+
+impl From<CrashReport> for [u8; CrashReport::LEN] {
+    fn from(value: CrashReport) -> Self {
+        let mut buf = [0; CrashReport::LEN];
+
+        buf[0..2].copy_from_slice(&value.location.to_le_bytes());
+        buf[2..4].copy_from_slice(&value.line.to_le_bytes());
+        buf[4] = value.reset_cause.into();
+        buf[5] = value.last_command.map(u8::from).unwrap_or_default();
+        buf[6..10].copy_from_slice(&value.uptime_s.to_le_bytes());
+
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for CrashReport {
+    type Error = CrashReportConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let [
+            l0,
+            l1,
+            n0,
+            n1,
+            reset_cause,
+            last_command,
+            u0,
+            u1,
+            u2,
+            u3,
+            ..,
+        ] = *value
+        else {
+            return Err(CrashReportConvError::Length);
+        };
+
+        let last_command = (last_command != 0)
+            .then(|| DeviceCommand::try_from(last_command))
+            .transpose()?;
+
+        Ok(Self {
+            location: u16::from_le_bytes([l0, l1]),
+            line: u16::from_le_bytes([n0, n1]),
+            reset_cause: reset_cause.try_into()?,
+            last_command,
+            uptime_s: u32::from_le_bytes([u0, u1, u2, u3]),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum CrashReportConvError {
+    #[error("crash report too short")]
+    Length,
+    #[error(transparent)]
+    ResetCause(#[from] ResetCauseConvError),
+    #[error(transparent)]
+    Command(#[from] CommandConvError),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CrashReport, DeviceCommand, ResetCause, crash_report::CrashReportConvError};
+
+    #[test]
+    fn test_crash_report_conversion() {
+        let report = CrashReport {
+            location: CrashReport::hash_location("device/src/main.rs"),
+            line: 42,
+            reset_cause: ResetCause::Watchdog,
+            last_command: Some(DeviceCommand::LedsOff),
+            uptime_s: 123_456,
+        };
+
+        let buf = <[u8; CrashReport::LEN]>::from(report);
+        assert_eq!(buf[..].try_into(), Ok(report));
+
+        let report = CrashReport {
+            last_command: None,
+            ..report
+        };
+
+        let buf = <[u8; CrashReport::LEN]>::from(report);
+        assert_eq!(buf[..].try_into(), Ok(report));
+        assert_eq!(
+            CrashReport::try_from(&buf[..CrashReport::LEN - 1]),
+            Err(CrashReportConvError::Length)
+        );
+    }
+}
@@ -4,13 +4,15 @@ use crate::{
     DeviceCommand,
     device_command::CommandConvError,
     fan_speed::{FanSpeed, FanSpeedConvError},
+    led_color::LedColor,
 };
 
 /// Struct representing the device state. It is meant to be sent to the host when updated as both a
 /// confirmation for the last command as well as the current state of the device after the command
 /// was executed.
 ///
-/// It gets packed into a single byte when sent to the host.
+/// It gets packed into a single byte when sent to the host, except for the uncertainty flag and the
+/// LEDs color which are sent alongside it in the [`crate::DeviceReport::State`] report.
 #[derive(Clone, Copy, Debug)]
 pub struct DeviceState {
     /// Whether power is currently enabled.
@@ -19,10 +21,15 @@ pub struct DeviceState {
     leds_enabled: bool,
     /// The current fan speed.
     fan_speed: FanSpeed,
+    /// The current LEDs color, tracked by counting the LED button short presses.
+    led_color: LedColor,
     /// A command that must be repeated. This typically happens when a speed button gets pressed
     /// but the backlight is inactive. In that case, we store the command in the state and send it
     /// back to the host so it can be retried (with an active backlight now).
     command_to_repeat: Option<DeviceCommand>,
+    /// Whether the state may have drifted from the real cooler state, because the device saw signs
+    /// of the cooler registering a press that the monitor did not. Cleared by a resync.
+    uncertain: bool,
 }
 
 impl PartialEq for DeviceState {
@@ -30,6 +37,8 @@ impl PartialEq for DeviceState {
         self.power_enabled == other.power_enabled()
             && self.leds_enabled == other.leds_enabled()
             && self.fan_speed == other.fan_speed
+            && self.led_color == other.led_color
+            && self.uncertain == other.uncertain
     }
 }
 
@@ -41,7 +50,9 @@ impl DeviceState {
             power_enabled: true,
             leds_enabled: true,
             fan_speed: FanSpeed::Speed1,
+            led_color: LedColor::Red,
             command_to_repeat: None,
+            uncertain: false,
         }
     }
 
@@ -63,12 +74,24 @@ impl DeviceState {
         self.fan_speed
     }
 
+    #[inline]
+    #[must_use]
+    pub fn led_color(&self) -> LedColor {
+        self.led_color
+    }
+
     #[inline]
     #[must_use]
     pub fn command_to_repeat(&self) -> Option<DeviceCommand> {
         self.command_to_repeat
     }
 
+    #[inline]
+    #[must_use]
+    pub fn uncertain(&self) -> bool {
+        self.uncertain
+    }
+
     #[inline]
     pub fn toggle_power(&mut self) {
         self.power_enabled = !self.power_enabled;
@@ -89,10 +112,32 @@ impl DeviceState {
         self.fan_speed.decrease();
     }
 
+    #[inline]
+    pub fn next_led_color(&mut self) {
+        self.led_color.next();
+    }
+
+    /// Sets the LEDs color, for when it is received alongside the packed state.
+    #[inline]
+    pub fn set_led_color(&mut self, led_color: LedColor) {
+        self.led_color = led_color;
+    }
+
+    /// Sets the fan speed, for when it is known from a source other than the button presses.
+    #[inline]
+    pub fn set_fan_speed(&mut self, fan_speed: FanSpeed) {
+        self.fan_speed = fan_speed;
+    }
+
     #[inline]
     pub fn set_repeat_command(&mut self, command: Option<DeviceCommand>) {
         self.command_to_repeat = command;
     }
+
+    #[inline]
+    pub fn set_uncertain(&mut self, uncertain: bool) {
+        self.uncertain = uncertain;
+    }
 }
 
 impl From<DeviceState> for u8 {
@@ -123,7 +168,9 @@ impl TryFrom<u8> for DeviceState {
             power_enabled,
             leds_enabled,
             fan_speed,
+            led_color: LedColor::Red,
             command_to_repeat,
+            uncertain: false,
         })
     }
 }
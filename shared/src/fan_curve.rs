@@ -0,0 +1,195 @@
+use core::cmp::Ordering;
+
+use thiserror::Error as ThisError;
+
+use crate::FanSpeed;
+
+/// Temperature thresholds, in whole degrees Celsius, for picking the fan speed.
+///
+/// Going over the threshold at index `i` raises the fan speed above `Speed{i + 1}`. Going back down
+/// requires the temperature to drop below the threshold by the hysteresis, so that a temperature
+/// hovering around a threshold does not keep changing the fan speed.
+///
+/// The curve is evaluated by the device, from the temperatures the host pushes, when the automatic
+/// fan speed adjustment runs there. The host evaluates its own curve otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FanCurve {
+    thresholds: [u8; 5],
+    hysteresis: u8,
+}
+
+impl FanCurve {
+    /// Length of the serialized curve.
+    pub const LEN: usize = 6;
+
+    /// The curve used until the host provides one.
+    pub const DEFAULT: Self = Self {
+        thresholds: [60, 65, 70, 75, 80],
+        hysteresis: 2,
+    };
+
+    /// Creates a fan curve.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thresholds are not in ascending order.
+    pub fn new(thresholds: [u8; 5], hysteresis: u8) -> Result<Self, FanCurveConvError> {
+        if !thresholds.is_sorted() {
+            return Err(FanCurveConvError::Order);
+        }
+
+        Ok(Self {
+            thresholds,
+            hysteresis,
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn thresholds(&self) -> [u8; 5] {
+        self.thresholds
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hysteresis(&self) -> u8 {
+        self.hysteresis
+    }
+
+    /// Returns the fan speed to move to from the current one at the given temperature, which is
+    /// at most one step away. Changing the speed a step at a time gives the temperature a chance to
+    /// react before going further.
+    #[must_use]
+    pub fn next_speed(&self, current: FanSpeed, temp_c: u8) -> FanSpeed {
+        let mut speed = current;
+
+        match (self.target_speed(current, temp_c) as u8).cmp(&(current as u8)) {
+            Ordering::Greater => speed.increase(),
+            Ordering::Less => speed.decrease(),
+            Ordering::Equal => (),
+        }
+
+        speed
+    }
+
+    /// Returns the fan speed the temperature calls for, coming from the current one: the speed
+    /// above every threshold the temperature is over, or a lower one only once the temperature
+    /// dropped below the thresholds in between by the hysteresis.
+    #[must_use]
+    pub fn target_speed(&self, current: FanSpeed, temp_c: u8) -> FanSpeed {
+        let up = self.speed_above(|threshold| temp_c > threshold);
+        let down = self.speed_above(|threshold| temp_c.saturating_add(self.hysteresis) > threshold);
+
+        let target = if up > current as u8 {
+            up
+        } else if down < current as u8 {
+            down
+        } else {
+            return current;
+        };
+
+        // There is a threshold for every step, so the target is always a valid fan speed.
+        FanSpeed::try_from(target).unwrap_or(current)
+    }
+
+    /// Returns the fan speed, as a number, for the thresholds that the predicate holds for.
+    fn speed_above<F>(self, f: F) -> u8
+    where
+        F: Fn(u8) -> bool,
+    {
+        let mut speed = FanSpeed::Speed1 as u8;
+
+        for threshold in self.thresholds {
+            speed += u8::from(f(threshold));
+        }
+
+        speed
+    }
+}
+
+impl From<FanCurve> for [u8; FanCurve::LEN] {
+    fn from(value: FanCurve) -> Self {
+        let [t0, t1, t2, t3, t4] = value.thresholds;
+        [t0, t1, t2, t3, t4, value.hysteresis]
+    }
+}
+
+impl TryFrom<&[u8]> for FanCurve {
+    type Error = FanCurveConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let [t0, t1, t2, t3, t4, hysteresis, ..] = *value else {
+            return Err(FanCurveConvError::Length);
+        };
+
+        Self::new([t0, t1, t2, t3, t4], hysteresis)
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FanCurveConvError {
+    #[error("fan curve too short")]
+    Length,
+    #[error("fan curve thresholds are not in ascending order")]
+    Order,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FanCurve, FanSpeed, fan_curve::FanCurveConvError};
+
+    #[test]
+    fn test_fan_curve_conversion() {
+        let buf = <[u8; FanCurve::LEN]>::from(FanCurve::DEFAULT);
+        assert_eq!(buf[..].try_into(), Ok(FanCurve::DEFAULT));
+        assert_eq!(
+            FanCurve::try_from(&buf[..FanCurve::LEN - 1]),
+            Err(FanCurveConvError::Length)
+        );
+        assert_eq!(
+            FanCurve::try_from(&[60, 65, 80, 75, 80, 2][..]),
+            Err(FanCurveConvError::Order)
+        );
+    }
+
+    #[test]
+    fn test_fan_curve_next_speed() {
+        let curve = FanCurve::DEFAULT;
+
+        // Going up a step at a time, only once over the threshold.
+        assert_eq!(curve.next_speed(FanSpeed::Speed1, 60), FanSpeed::Speed1);
+        assert_eq!(curve.next_speed(FanSpeed::Speed1, 61), FanSpeed::Speed2);
+        assert_eq!(curve.next_speed(FanSpeed::Speed1, 90), FanSpeed::Speed2);
+        assert_eq!(curve.next_speed(FanSpeed::Speed5, 81), FanSpeed::Speed6);
+        assert_eq!(curve.next_speed(FanSpeed::Speed6, 90), FanSpeed::Speed6);
+
+        // Going down only once under the threshold by the hysteresis.
+        assert_eq!(curve.next_speed(FanSpeed::Speed2, 59), FanSpeed::Speed2);
+        assert_eq!(curve.next_speed(FanSpeed::Speed2, 58), FanSpeed::Speed1);
+        assert_eq!(curve.next_speed(FanSpeed::Speed6, 79), FanSpeed::Speed6);
+        assert_eq!(curve.next_speed(FanSpeed::Speed6, 20), FanSpeed::Speed5);
+        assert_eq!(curve.next_speed(FanSpeed::Speed1, 0), FanSpeed::Speed1);
+
+        // Without hysteresis the speed goes down as soon as it is not over the threshold.
+        let curve = FanCurve::new(FanCurve::DEFAULT.thresholds(), 0).unwrap();
+        assert_eq!(curve.next_speed(FanSpeed::Speed2, 60), FanSpeed::Speed1);
+        assert_eq!(curve.next_speed(FanSpeed::Speed2, 61), FanSpeed::Speed2);
+    }
+
+    #[test]
+    fn test_fan_curve_target_speed() {
+        let curve = FanCurve::DEFAULT;
+
+        // Going up straight to the speed above the thresholds the temperature is over.
+        assert_eq!(curve.target_speed(FanSpeed::Speed1, 60), FanSpeed::Speed1);
+        assert_eq!(curve.target_speed(FanSpeed::Speed1, 72), FanSpeed::Speed4);
+        assert_eq!(curve.target_speed(FanSpeed::Speed2, 90), FanSpeed::Speed6);
+
+        // Going down only as far as the hysteresis allows.
+        assert_eq!(curve.target_speed(FanSpeed::Speed6, 79), FanSpeed::Speed6);
+        assert_eq!(curve.target_speed(FanSpeed::Speed6, 67), FanSpeed::Speed3);
+        assert_eq!(curve.target_speed(FanSpeed::Speed6, 20), FanSpeed::Speed1);
+        assert_eq!(curve.target_speed(FanSpeed::Speed4, 69), FanSpeed::Speed4);
+    }
+}
@@ -0,0 +1,105 @@
+use thiserror::Error as ThisError;
+
+use crate::FanSpeed;
+
+/// Per-unit calibration of the fan voltage sensing: the ADC reading of the fan-grid connector
+/// voltage at each fan speed.
+///
+/// The voltage at a given speed varies between cooler units and power supplies, so the readings
+/// are measured on the device itself instead of being hardcoded.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct FanSenseCalibration {
+    /// ADC readings, indexed by fan speed, starting with [`FanSpeed::Speed1`].
+    pub levels: [u16; 6],
+}
+
+impl FanSenseCalibration {
+    /// Length of the serialized calibration.
+    pub const LEN: usize = 12;
+
+    /// Returns the fan speed whose calibrated reading is closest to the given one, or `None` if the
+    /// reading is closer to zero than to the lowest speed reading, meaning the fans are not
+    /// running.
+    #[must_use]
+    pub fn classify(&self, reading: u16) -> Option<FanSpeed> {
+        if reading < self.levels[0] / 2 {
+            return None;
+        }
+
+        let (index, _) = self
+            .levels
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| level.abs_diff(reading))?;
+
+        // There are as many levels as fan speeds.
+        #[expect(clippy::cast_possible_truncation, reason = "index is less than 6")]
+        FanSpeed::try_from(FanSpeed::Speed1 as u8 + index as u8).ok()
+    }
+}
+
+impl From<FanSenseCalibration> for [u8; FanSenseCalibration::LEN] {
+    fn from(value: FanSenseCalibration) -> Self {
+        let mut buf = [0; FanSenseCalibration::LEN];
+
+        for (chunk, level) in buf.chunks_exact_mut(2).zip(value.levels) {
+            chunk.copy_from_slice(&level.to_le_bytes());
+        }
+
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for FanSenseCalibration {
+    type Error = FanSenseCalibrationConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let buf = value.get(..Self::LEN).ok_or(FanSenseCalibrationConvError)?;
+
+        let mut levels = [0; 6];
+
+        for (level, chunk) in levels.iter_mut().zip(buf.chunks_exact(2)) {
+            *level = u16::from_le_bytes([chunk[0], chunk[1]]);
+        }
+
+        Ok(Self { levels })
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("fan sense calibration too short")]
+pub struct FanSenseCalibrationConvError;
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FanSenseCalibration, FanSpeed, fan_sense_calibration::FanSenseCalibrationConvError,
+    };
+
+    const CALIBRATION: FanSenseCalibration = FanSenseCalibration {
+        levels: [300, 380, 460, 540, 620, 700],
+    };
+
+    #[test]
+    fn test_fan_sense_calibration_conversion() {
+        let buf = <[u8; FanSenseCalibration::LEN]>::from(CALIBRATION);
+        assert_eq!(buf[..].try_into(), Ok(CALIBRATION));
+        assert_eq!(
+            FanSenseCalibration::try_from(&buf[..FanSenseCalibration::LEN - 1]),
+            Err(FanSenseCalibrationConvError)
+        );
+    }
+
+    #[test]
+    fn test_fan_sense_classify() {
+        assert_eq!(CALIBRATION.classify(0), None);
+        assert_eq!(CALIBRATION.classify(149), None);
+        assert_eq!(CALIBRATION.classify(150), Some(FanSpeed::Speed1));
+        assert_eq!(CALIBRATION.classify(339), Some(FanSpeed::Speed1));
+        assert_eq!(CALIBRATION.classify(341), Some(FanSpeed::Speed2));
+        assert_eq!(CALIBRATION.classify(540), Some(FanSpeed::Speed4));
+        assert_eq!(CALIBRATION.classify(1023), Some(FanSpeed::Speed6));
+    }
+}
@@ -0,0 +1,102 @@
+use thiserror::Error as ThisError;
+
+/// Enum representing the LEDs color, in the order the cooler cycles through its palette on every
+/// LED button short press. The cooler starts with the first color when powered up.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(test, derive(strum::EnumIter))]
+pub enum LedColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Purple,
+    White,
+}
+
+impl LedColor {
+    /// Number of colors in the cooler's palette.
+    pub const COUNT: u8 = 7;
+
+    /// All the colors, in palette order.
+    pub const ALL: [Self; Self::COUNT as usize] = [
+        Self::Red,
+        Self::Green,
+        Self::Blue,
+        Self::Yellow,
+        Self::Cyan,
+        Self::Purple,
+        Self::White,
+    ];
+
+    /// Moves to the next color in the palette, wrapping around after the last one.
+    pub fn next(&mut self) {
+        *self = Self::ALL[usize::from((*self as u8 + 1) % Self::COUNT)];
+    }
+
+    /// Returns the number of LED button short presses it takes to get from this color to the
+    /// given one.
+    #[must_use]
+    pub fn presses_to(self, color: Self) -> u8 {
+        (color as u8 + Self::COUNT - self as u8) % Self::COUNT
+    }
+}
+
+impl From<LedColor> for u8 {
+    fn from(value: LedColor) -> Self {
+        value as Self
+    }
+}
+
+impl TryFrom<u8> for LedColor {
+    type Error = LedColorConvError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        LedColor::ALL
+            .get(usize::from(value))
+            .copied()
+            .ok_or(LedColorConvError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("integer to LED color conversion failed")]
+pub struct LedColorConvError;
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use crate::LedColor;
+
+    #[test]
+    fn test_led_color_conversion() {
+        assert_eq!(LedColor::iter().count(), usize::from(LedColor::COUNT));
+
+        for (index, led_color) in LedColor::iter().enumerate() {
+            assert_eq!(LedColor::ALL[index], led_color);
+            assert_eq!((led_color as u8).try_into(), Ok(led_color));
+        }
+
+        assert!(LedColor::try_from(LedColor::COUNT).is_err());
+    }
+
+    #[test]
+    fn test_led_color_presses() {
+        for from in LedColor::iter() {
+            for to in LedColor::iter() {
+                let mut led_color = from;
+                for _ in 0..from.presses_to(to) {
+                    led_color.next();
+                }
+                assert_eq!(led_color, to);
+            }
+        }
+
+        let mut led_color = LedColor::White;
+        led_color.next();
+        assert_eq!(led_color, LedColor::Red);
+    }
+}
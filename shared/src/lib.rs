@@ -1,15 +1,63 @@
 #![doc = include_str!("../README.md")]
 #![no_std]
 
+mod button_event;
+mod crash_report;
 mod device_command;
 mod device_state;
+mod fan_curve;
+mod fan_sense_calibration;
 mod fan_speed;
+mod led_color;
+mod ntc;
+mod odometer;
+mod press_timings;
+mod report;
+mod reset_cause;
 
+pub use button_event::ButtonEvent;
+pub use crash_report::CrashReport;
 pub use device_command::DeviceCommand;
 pub use device_state::DeviceState;
+pub use fan_curve::FanCurve;
+pub use fan_sense_calibration::FanSenseCalibration;
 pub use fan_speed::FanSpeed;
+pub use led_color::LedColor;
+pub use ntc::Ntc;
+pub use odometer::{Odometer, OdometerChunks};
+pub use press_timings::PressTimings;
+pub use report::{DeviceReport, HostReport, REPORT_LEN, ReportConvError};
+pub use reset_cause::ResetCause;
 
 pub const USB_VID: u16 = 0x16C0;
 pub const USB_PID: u16 = 0x05df;
 pub const USB_MANUFACTURER: &str = "mirceapetrebogdan@gmail.com";
 pub const USB_PRODUCT: &str = "Cooler Than You";
+
+/// Firmware version in the binary-coded decimal format of the `bcdDevice` field of the USB device
+/// descriptor, e.g. `0x0123` for version `1.2.3`.
+pub const USB_DEVICE_RELEASE: u16 = {
+    let major = parse_version(env!("CARGO_PKG_VERSION_MAJOR"));
+    let minor = parse_version(env!("CARGO_PKG_VERSION_MINOR"));
+    let patch = parse_version(env!("CARGO_PKG_VERSION_PATCH"));
+    assert!(
+        major < 100 && minor < 10 && patch < 10,
+        "version does not fit in BCD"
+    );
+
+    (major / 10) << 12 | (major % 10) << 8 | minor << 4 | patch
+};
+
+/// Parses a version number at compile time.
+const fn parse_version(number: &str) -> u16 {
+    let digits = number.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+
+    while i < digits.len() {
+        value = value * 10 + (digits[i] - b'0') as u16;
+        i += 1;
+    }
+
+    value
+}
@@ -0,0 +1,59 @@
+/// Conversion of the readings of an NTC thermistor temperature probe to degrees Celsius.
+///
+/// The probe is expected to be a 10kΩ thermistor with a B value of 3950K, wired between the analog
+/// pin and ground, with a 10kΩ resistor between the analog pin and 5V. Readings are 10-bit ADC
+/// conversions of the pin voltage, which drops as the temperature rises.
+#[derive(Clone, Copy, Debug)]
+pub struct Ntc;
+
+impl Ntc {
+    /// Step between the temperatures of [`Ntc::READINGS`].
+    const STEP_C: u8 = 5;
+    /// Readings at every [`Ntc::STEP_C`] degrees from 0°C to 100°C.
+    const READINGS: [u16; 21] = [
+        788, 738, 684, 627, 569, 512, 456, 403, 354, 310, 270, 235, 204, 177, 153, 133, 115, 100,
+        87, 76, 67,
+    ];
+
+    /// Returns the temperature rounded to the nearest degree, see [`Ntc::temperature_dc`].
+    #[must_use]
+    pub fn temperature_c(reading: u16) -> Option<u8> {
+        u8::try_from((Self::temperature_dc(reading)? + 5) / 10).ok()
+    }
+
+    /// Returns the temperature in tenths of a degree, interpolated between the known readings, or
+    /// `None` if the reading is outside of the 0°C to 100°C range, which is also the case if the
+    /// probe is disconnected or shorted.
+    #[must_use]
+    pub fn temperature_dc(reading: u16) -> Option<u16> {
+        let index = Self::READINGS
+            .windows(2)
+            .position(|pair| pair[0] >= reading && reading >= pair[1])?;
+
+        let (high, low) = (Self::READINGS[index], Self::READINGS[index + 1]);
+        let step_dc = u16::from(Self::STEP_C) * 10;
+        // Rounded to the nearest tenth of a degree.
+        let offset_dc = ((high - reading) * step_dc * 2 + (high - low)) / ((high - low) * 2);
+
+        Some(u16::try_from(index).ok()? * step_dc + offset_dc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Ntc;
+
+    #[test]
+    fn test_ntc_temperature() {
+        for (index, reading) in Ntc::READINGS.into_iter().enumerate() {
+            let expected = u8::try_from(index).unwrap() * Ntc::STEP_C;
+            assert_eq!(Ntc::temperature_c(reading), Some(expected));
+        }
+
+        // Halfway between 25°C and 30°C.
+        assert_eq!(Ntc::temperature_dc(484), Some(275));
+        assert_eq!(Ntc::temperature_c(484), Some(28));
+        assert_eq!(Ntc::temperature_c(1023), None);
+        assert_eq!(Ntc::temperature_c(0), None);
+    }
+}
@@ -0,0 +1,272 @@
+use thiserror::Error as ThisError;
+
+use crate::{DeviceState, FanSpeed};
+
+/// Lifetime usage counters kept by the device, meant for replacement planning and for checking
+/// how hard the fans get driven.
+///
+/// Durations are in seconds and only accumulate while the cooler is powered on. The counters
+/// saturate instead of wrapping around.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Odometer {
+    /// Time spent powered on.
+    pub powered_on_s: u32,
+    /// Time spent at each fan speed, indexed by fan speed, starting with [`FanSpeed::Speed1`].
+    pub fan_speed_s: [u32; 6],
+    /// Time spent with the LEDs on.
+    pub leds_on_s: u32,
+    /// Button presses done by hand.
+    pub physical_presses: u32,
+    /// Button presses emulated by the device.
+    pub emulated_presses: u32,
+    /// Host suspends.
+    pub suspends: u32,
+    /// Glitches on the button monitor pins rejected by the press classifier, such as contact
+    /// bounces or EMI spikes.
+    pub rejected_glitches: u32,
+}
+
+impl Odometer {
+    /// Length of the serialized counters.
+    pub const LEN: usize = 48;
+    /// The serialized counters do not fit in a single report, so they get sent in chunks of this
+    /// length.
+    pub const CHUNK_LEN: usize = 12;
+    /// Number of chunks the serialized counters get sent in, the last one padded with zeroes if
+    /// needed.
+    pub const CHUNKS: u8 = 4;
+
+    /// Creates an odometer with all the counters at zero.
+    #[must_use]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            powered_on_s: 0,
+            fan_speed_s: [0; 6],
+            leds_on_s: 0,
+            physical_presses: 0,
+            emulated_presses: 0,
+            suspends: 0,
+            rejected_glitches: 0,
+        }
+    }
+
+    /// Accounts for a second spent in the given state.
+    pub fn tick(&mut self, device_state: &DeviceState) {
+        if !device_state.power_enabled() {
+            return;
+        }
+
+        self.powered_on_s = self.powered_on_s.saturating_add(1);
+
+        let index = usize::from(device_state.fan_speed() as u8 - FanSpeed::Speed1 as u8);
+        self.fan_speed_s[index] = self.fan_speed_s[index].saturating_add(1);
+
+        if device_state.leds_enabled() {
+            self.leds_on_s = self.leds_on_s.saturating_add(1);
+        }
+    }
+
+    /// Counts a button press registered by the cooler.
+    pub fn count_press(&mut self, emulated: bool) {
+        let presses = if emulated {
+            &mut self.emulated_presses
+        } else {
+            &mut self.physical_presses
+        };
+
+        *presses = presses.saturating_add(1);
+    }
+
+    /// Counts a host suspend.
+    pub fn count_suspend(&mut self) {
+        self.suspends = self.suspends.saturating_add(1);
+    }
+
+    /// Counts glitches rejected by the press classifier.
+    pub fn count_rejected_glitches(&mut self, glitches: u8) {
+        self.rejected_glitches = self.rejected_glitches.saturating_add(u32::from(glitches));
+    }
+
+    /// Returns the chunk at the given index of the serialized counters, or `None` if the index is
+    /// out of bounds.
+    #[must_use]
+    pub fn chunk(&self, index: u8) -> Option<[u8; Self::CHUNK_LEN]> {
+        let buf = <[u8; Self::LEN]>::from(*self);
+        let bytes = buf.chunks(Self::CHUNK_LEN).nth(usize::from(index))?;
+
+        let mut chunk = [0; Self::CHUNK_LEN];
+        chunk[..bytes.len()].copy_from_slice(bytes);
+        Some(chunk)
+    }
+}
+
+impl From<Odometer> for [u8; Odometer::LEN] {
+    fn from(value: Odometer) -> Self {
+        let counters = [value.powered_on_s]
+            .into_iter()
+            .chain(value.fan_speed_s)
+            .chain([
+                value.leds_on_s,
+                value.physical_presses,
+                value.emulated_presses,
+                value.suspends,
+                value.rejected_glitches,
+            ]);
+
+        let mut buf = [0; Odometer::LEN];
+
+        for (chunk, counter) in buf.chunks_exact_mut(4).zip(counters) {
+            chunk.copy_from_slice(&counter.to_le_bytes());
+        }
+
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for Odometer {
+    type Error = OdometerConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let buf = value.get(..Self::LEN).ok_or(OdometerConvError)?;
+
+        let mut counters = [0; Self::LEN / 4];
+
+        for (counter, chunk) in counters.iter_mut().zip(buf.chunks_exact(4)) {
+            *counter = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        let [
+            powered_on_s,
+            s1,
+            s2,
+            s3,
+            s4,
+            s5,
+            s6,
+            leds_on_s,
+            physical_presses,
+            emulated_presses,
+            suspends,
+            rejected_glitches,
+        ] = counters;
+
+        Ok(Self {
+            powered_on_s,
+            fan_speed_s: [s1, s2, s3, s4, s5, s6],
+            leds_on_s,
+            physical_presses,
+            emulated_presses,
+            suspends,
+            rejected_glitches,
+        })
+    }
+}
+
+/// Puts the [`Odometer`] back together from the chunks it gets sent in, whatever their order.
+#[derive(Clone, Copy, Debug)]
+pub struct OdometerChunks {
+    buf: [u8; Odometer::CHUNKS as usize * Odometer::CHUNK_LEN],
+    /// Bit set of the chunk indexes received so far.
+    received: u8,
+}
+
+impl OdometerChunks {
+    const ALL_RECEIVED: u8 = (1 << Odometer::CHUNKS) - 1;
+
+    #[must_use]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; Odometer::CHUNKS as usize * Odometer::CHUNK_LEN],
+            received: 0,
+        }
+    }
+
+    /// Stores the chunk at the given index and returns the counters once all the chunks were
+    /// received. Chunks with an out of bounds index get ignored.
+    pub fn insert(&mut self, index: u8, chunk: [u8; Odometer::CHUNK_LEN]) -> Option<Odometer> {
+        let start = usize::from(index) * Odometer::CHUNK_LEN;
+        let dest = self.buf.get_mut(start..start + Odometer::CHUNK_LEN)?;
+        dest.copy_from_slice(&chunk);
+        self.received |= 1 << index;
+
+        if self.received == Self::ALL_RECEIVED {
+            Odometer::try_from(&self.buf[..]).ok()
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("odometer too short")]
+pub struct OdometerConvError;
+
+#[cfg(test)]
+mod tests {
+    use crate::{DeviceState, FanSpeed, Odometer, OdometerChunks, odometer::OdometerConvError};
+
+    const ODOMETER: Odometer = Odometer {
+        powered_on_s: 3600,
+        fan_speed_s: [600, 600, 600, 600, 600, 600],
+        leds_on_s: 1800,
+        physical_presses: 42,
+        emulated_presses: u32::MAX,
+        suspends: 7,
+        rejected_glitches: 3,
+    };
+
+    #[test]
+    fn test_odometer_conversion() {
+        let buf = <[u8; Odometer::LEN]>::from(ODOMETER);
+        assert_eq!(buf[..].try_into(), Ok(ODOMETER));
+        assert_eq!(
+            Odometer::try_from(&buf[..Odometer::LEN - 1]),
+            Err(OdometerConvError)
+        );
+
+        let mut chunks = OdometerChunks::new();
+        assert_eq!(
+            chunks.insert(Odometer::CHUNKS, [0xff; Odometer::CHUNK_LEN]),
+            None
+        );
+
+        // The last chunk completes the counters, whatever the order.
+        for index in (1..Odometer::CHUNKS).rev() {
+            assert_eq!(chunks.insert(index, ODOMETER.chunk(index).unwrap()), None);
+        }
+
+        assert_eq!(chunks.insert(0, ODOMETER.chunk(0).unwrap()), Some(ODOMETER));
+        assert_eq!(ODOMETER.chunk(Odometer::CHUNKS), None);
+    }
+
+    #[test]
+    fn test_odometer_tick() {
+        let mut odometer = Odometer::new();
+        let mut state = DeviceState::new();
+
+        state.set_fan_speed(FanSpeed::Speed3);
+        odometer.tick(&state);
+        state.toggle_leds();
+        odometer.tick(&state);
+        state.toggle_power();
+        odometer.tick(&state);
+
+        assert_eq!(odometer.powered_on_s, 2);
+        assert_eq!(odometer.fan_speed_s, [0, 0, 2, 0, 0, 0]);
+        assert_eq!(odometer.leds_on_s, 1);
+
+        odometer.emulated_presses = u32::MAX;
+        odometer.count_press(true);
+        odometer.count_press(false);
+        assert_eq!(odometer.emulated_presses, u32::MAX);
+        assert_eq!(odometer.physical_presses, 1);
+
+        odometer.count_rejected_glitches(2);
+        odometer.count_rejected_glitches(0);
+        assert_eq!(odometer.rejected_glitches, 2);
+    }
+}
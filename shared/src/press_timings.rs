@@ -0,0 +1,181 @@
+use core::ops::RangeInclusive;
+
+use thiserror::Error as ThisError;
+
+/// Timings of the button presses emulated by the device and the thresholds the device monitors the
+/// buttons with.
+///
+/// The cooler registers short presses from 40ms and long presses from 1400ms. The emulated presses
+/// are held a bit longer to ensure they get registered, while the monitor thresholds follow the
+/// cooler's own. Units of the same cooler may need more margin, hence the timings being tunable by
+/// the host. They get persisted by the device.
+///
+/// The host reads and writes them through the HID feature report, serialized into
+/// [`PressTimings::LEN`] bytes, rather than through the input and output reports, so that they do
+/// not queue up behind the commands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PressTimings {
+    /// How long an emulated short press is held.
+    pub short_press_ms: u8,
+    /// How long an emulated long press is held.
+    pub long_press_ms: u16,
+    /// Delay after an emulated press, as a boundary between subsequent presses.
+    pub post_press_delay_ms: u8,
+    /// How long a press must be held for the monitor to register a short press.
+    pub monitor_short_press_ms: u8,
+    /// How long a press must be held for the monitor to register a long press.
+    pub monitor_long_press_ms: u16,
+    /// Longest dropout the monitor tolerates while a press builds up to a short press.
+    pub sub_threshold_dropout_ms: u8,
+    /// Longest dropout the monitor tolerates once a press got past the short press threshold.
+    pub within_press_dropout_ms: u8,
+}
+
+impl PressTimings {
+    /// Length of the serialized timings.
+    pub const LEN: usize = 9;
+
+    /// The timings used until the host provides some.
+    pub const DEFAULT: Self = Self {
+        short_press_ms: 45,
+        long_press_ms: 1425,
+        post_press_delay_ms: 10,
+        monitor_short_press_ms: 40,
+        monitor_long_press_ms: 1400,
+        sub_threshold_dropout_ms: 2,
+        within_press_dropout_ms: 5,
+    };
+
+    pub const SHORT_PRESS_RANGE: RangeInclusive<u8> = 30..=100;
+    pub const LONG_PRESS_RANGE: RangeInclusive<u16> = 1000..=2500;
+    pub const POST_PRESS_DELAY_RANGE: RangeInclusive<u8> = 1..=90;
+    /// The monitor looks for short presses within a 64 bit window of samples.
+    pub const MONITOR_SHORT_PRESS_RANGE: RangeInclusive<u8> = 20..=63;
+    pub const MONITOR_LONG_PRESS_RANGE: RangeInclusive<u16> = 1000..=2000;
+    /// The monitor samples come out of its glitch filter as late as the longest dropout allowed.
+    pub const DROPOUT_RANGE: RangeInclusive<u8> = 0..=8;
+
+    /// Checks that the timings are within their ranges and that the emulated presses are longer
+    /// than the monitor thresholds, as otherwise the monitor would not register them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the timings are not valid.
+    pub fn validate(&self) -> Result<(), PressTimingsConvError> {
+        let in_range = Self::SHORT_PRESS_RANGE.contains(&self.short_press_ms)
+            && Self::LONG_PRESS_RANGE.contains(&self.long_press_ms)
+            && Self::POST_PRESS_DELAY_RANGE.contains(&self.post_press_delay_ms)
+            && Self::MONITOR_SHORT_PRESS_RANGE.contains(&self.monitor_short_press_ms)
+            && Self::MONITOR_LONG_PRESS_RANGE.contains(&self.monitor_long_press_ms)
+            && Self::DROPOUT_RANGE.contains(&self.sub_threshold_dropout_ms)
+            && Self::DROPOUT_RANGE.contains(&self.within_press_dropout_ms);
+
+        if !in_range {
+            return Err(PressTimingsConvError::Range);
+        }
+
+        if self.short_press_ms <= self.monitor_short_press_ms
+            || self.long_press_ms <= self.monitor_long_press_ms
+        {
+            return Err(PressTimingsConvError::Margin);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PressTimings> for [u8; PressTimings::LEN] {
+    fn from(value: PressTimings) -> Self {
+        let [long_lo, long_hi] = value.long_press_ms.to_le_bytes();
+        let [monitor_long_lo, monitor_long_hi] = value.monitor_long_press_ms.to_le_bytes();
+
+        [
+            value.short_press_ms,
+            long_lo,
+            long_hi,
+            value.post_press_delay_ms,
+            value.monitor_short_press_ms,
+            monitor_long_lo,
+            monitor_long_hi,
+            value.sub_threshold_dropout_ms,
+            value.within_press_dropout_ms,
+        ]
+    }
+}
+
+impl TryFrom<&[u8]> for PressTimings {
+    type Error = PressTimingsConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let [
+            short_press_ms,
+            long_lo,
+            long_hi,
+            post_press_delay_ms,
+            monitor_short_press_ms,
+            monitor_long_lo,
+            monitor_long_hi,
+            sub_threshold_dropout_ms,
+            within_press_dropout_ms,
+            ..,
+        ] = *value
+        else {
+            return Err(PressTimingsConvError::Length);
+        };
+
+        let timings = Self {
+            short_press_ms,
+            long_press_ms: u16::from_le_bytes([long_lo, long_hi]),
+            post_press_delay_ms,
+            monitor_short_press_ms,
+            monitor_long_press_ms: u16::from_le_bytes([monitor_long_lo, monitor_long_hi]),
+            sub_threshold_dropout_ms,
+            within_press_dropout_ms,
+        };
+
+        timings.validate()?;
+        Ok(timings)
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum PressTimingsConvError {
+    #[error("press timings too short")]
+    Length,
+    #[error("press timings out of range")]
+    Range,
+    #[error("emulated presses must be longer than the monitor thresholds")]
+    Margin,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PressTimings, press_timings::PressTimingsConvError};
+
+    #[test]
+    fn test_press_timings_conversion() {
+        let buf = <[u8; PressTimings::LEN]>::from(PressTimings::DEFAULT);
+        assert_eq!(buf[..].try_into(), Ok(PressTimings::DEFAULT));
+        assert_eq!(
+            PressTimings::try_from(&buf[..PressTimings::LEN - 1]),
+            Err(PressTimingsConvError::Length)
+        );
+
+        let timings = PressTimings {
+            within_press_dropout_ms: 9,
+            ..PressTimings::DEFAULT
+        };
+        let buf = <[u8; PressTimings::LEN]>::from(timings);
+        assert_eq!(
+            PressTimings::try_from(&buf[..]),
+            Err(PressTimingsConvError::Range)
+        );
+
+        let timings = PressTimings {
+            short_press_ms: 40,
+            ..PressTimings::DEFAULT
+        };
+        assert_eq!(timings.validate(), Err(PressTimingsConvError::Margin));
+    }
+}
@@ -0,0 +1,498 @@
+use thiserror::Error as ThisError;
+
+use crate::{
+    ButtonEvent, CrashReport, DeviceCommand, DeviceState, FanCurve, FanSenseCalibration, FanSpeed,
+    LedColor, Odometer, button_event::ButtonEventConvError, crash_report::CrashReportConvError,
+    device_command::CommandConvError, device_state::DeviceStateConvError,
+    fan_curve::FanCurveConvError, fan_sense_calibration::FanSenseCalibrationConvError,
+    fan_speed::FanSpeedConvError, led_color::LedColorConvError,
+};
+
+/// Length of both the input and the output HID reports.
+///
+/// The first byte of a report tells its kind while the rest is the kind specific payload, padded
+/// with zeroes.
+pub const REPORT_LEN: usize = 16;
+
+/// Report sent by the device to the host through the input HID report.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum DeviceReport {
+    /// The device state, sent when updated or when a command must be repeated.
+    State(DeviceState),
+    /// A gesture detected on the physical buttons.
+    ButtonEvent(ButtonEvent),
+    /// Single use token to confirm a [`HostReport::EnterBootloader`] with. Sent in response to
+    /// [`HostReport::RequestBootloaderToken`].
+    BootloaderToken(u16),
+    /// A firmware panic that reset the device. Persisted by the device, which sends it in response
+    /// to [`HostReport::ReadCrash`] until the host sends a [`HostReport::AcknowledgeCrash`].
+    Crash(CrashReport),
+    /// The liveness watchdog reset the device after a hang. The device state got reset to the
+    /// power up defaults with the lowest fan speed. Sent in response to [`HostReport::ReadCrash`]
+    /// until the host sends a [`HostReport::AcknowledgeWatchdogRecovery`].
+    WatchdogRecovery,
+    /// The device entered (`true`) or left (`false`) the fail-safe mode, in which it drives the
+    /// cooler to the fail-safe fan speed because the host heartbeats stopped.
+    Failsafe(bool),
+    /// The fan voltage sensing calibration measured in response to
+    /// [`HostReport::CalibrateFanSense`].
+    FanSenseCalibration(FanSenseCalibration),
+    /// Sent instead of the [`DeviceReport::FanSenseCalibration`] when the cooler is powered off,
+    /// as the fans would not be running to measure.
+    FanSenseCalibrationRefused,
+    /// A chunk of the serialized [`Odometer`], sent for every chunk index in response to
+    /// [`HostReport::ReadOdometer`].
+    OdometerChunk {
+        index: u8,
+        chunk: [u8; Odometer::CHUNK_LEN],
+    },
+    /// Whether a power button press wakes up the host while the USB is suspended, sent in response
+    /// to [`HostReport::ReadRemoteWakeup`] and [`HostReport::RemoteWakeup`].
+    RemoteWakeup(bool),
+    /// The temperature measured by the probe of the device, in whole degrees Celsius, sent when
+    /// it changes. `None` if the probe reads out of range, e.g. because it is disconnected.
+    ProbeTemperature(Option<u8>),
+}
+
+impl DeviceReport {
+    const STATE: u8 = 1;
+    const BUTTON_EVENT: u8 = 2;
+    const BOOTLOADER_TOKEN: u8 = 3;
+    const CRASH: u8 = 4;
+    const WATCHDOG_RECOVERY: u8 = 5;
+    const FAILSAFE: u8 = 6;
+    const FAN_SENSE_CALIBRATION: u8 = 7;
+    const ODOMETER_CHUNK: u8 = 8;
+    const REMOTE_WAKEUP: u8 = 10;
+    const PROBE_TEMPERATURE: u8 = 11;
+    const FAN_SENSE_CALIBRATION_REFUSED: u8 = 12;
+}
+
+impl From<DeviceReport> for [u8; REPORT_LEN] {
+    fn from(value: DeviceReport) -> Self {
+        let mut buf = [0; REPORT_LEN];
+
+        match value {
+            DeviceReport::State(state) => {
+                buf[0] = DeviceReport::STATE;
+                buf[1] = state.into();
+                buf[2] = state.uncertain().into();
+                buf[3] = state.led_color().into();
+            }
+            DeviceReport::ButtonEvent(event) => {
+                buf[0] = DeviceReport::BUTTON_EVENT;
+                buf[1] = event.into();
+            }
+            DeviceReport::BootloaderToken(token) => {
+                buf[0] = DeviceReport::BOOTLOADER_TOKEN;
+                buf[1..3].copy_from_slice(&token.to_le_bytes());
+            }
+            DeviceReport::Crash(report) => {
+                buf[0] = DeviceReport::CRASH;
+                buf[1..=CrashReport::LEN].copy_from_slice(&<[u8; CrashReport::LEN]>::from(report));
+            }
+            DeviceReport::WatchdogRecovery => buf[0] = DeviceReport::WATCHDOG_RECOVERY,
+            DeviceReport::Failsafe(active) => {
+                buf[0] = DeviceReport::FAILSAFE;
+                buf[1] = active.into();
+            }
+            DeviceReport::FanSenseCalibration(calibration) => {
+                buf[0] = DeviceReport::FAN_SENSE_CALIBRATION;
+                buf[1..=FanSenseCalibration::LEN]
+                    .copy_from_slice(&<[u8; FanSenseCalibration::LEN]>::from(calibration));
+            }
+            DeviceReport::FanSenseCalibrationRefused => {
+                buf[0] = DeviceReport::FAN_SENSE_CALIBRATION_REFUSED;
+            }
+            DeviceReport::OdometerChunk { index, chunk } => {
+                buf[0] = DeviceReport::ODOMETER_CHUNK;
+                buf[1] = index;
+                buf[2..2 + Odometer::CHUNK_LEN].copy_from_slice(&chunk);
+            }
+            DeviceReport::RemoteWakeup(enabled) => {
+                buf[0] = DeviceReport::REMOTE_WAKEUP;
+                buf[1] = enabled.into();
+            }
+            DeviceReport::ProbeTemperature(temp_c) => {
+                buf[0] = DeviceReport::PROBE_TEMPERATURE;
+                buf[1] = temp_c.is_some().into();
+                buf[2] = temp_c.unwrap_or_default();
+            }
+        }
+
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for DeviceReport {
+    type Error = ReportConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let [kind, payload @ ..] = value else {
+            return Err(ReportConvError::Length);
+        };
+
+        match (*kind, payload) {
+            (Self::STATE, [state, uncertain, led_color, ..]) => {
+                let mut state = DeviceState::try_from(*state)?;
+                state.set_uncertain(*uncertain != 0);
+                state.set_led_color((*led_color).try_into()?);
+                Ok(Self::State(state))
+            }
+            (Self::BUTTON_EVENT, [event, ..]) => Ok(Self::ButtonEvent((*event).try_into()?)),
+            (Self::BOOTLOADER_TOKEN, [lo, hi, ..]) => {
+                Ok(Self::BootloaderToken(u16::from_le_bytes([*lo, *hi])))
+            }
+            (Self::CRASH, report) => Ok(Self::Crash(report.try_into()?)),
+            (Self::WATCHDOG_RECOVERY, _) => Ok(Self::WatchdogRecovery),
+            (Self::FAILSAFE, [active, ..]) => Ok(Self::Failsafe(*active != 0)),
+            (Self::FAN_SENSE_CALIBRATION, calibration) => {
+                Ok(Self::FanSenseCalibration(calibration.try_into()?))
+            }
+            (Self::FAN_SENSE_CALIBRATION_REFUSED, _) => Ok(Self::FanSenseCalibrationRefused),
+            (Self::ODOMETER_CHUNK, [index, chunk @ ..]) => Ok(Self::OdometerChunk {
+                index: *index,
+                chunk: chunk
+                    .get(..Odometer::CHUNK_LEN)
+                    .and_then(|chunk| chunk.try_into().ok())
+                    .ok_or(ReportConvError::Length)?,
+            }),
+            (Self::REMOTE_WAKEUP, [enabled, ..]) => Ok(Self::RemoteWakeup(*enabled != 0)),
+            (Self::PROBE_TEMPERATURE, [present, temp_c, ..]) => {
+                Ok(Self::ProbeTemperature((*present != 0).then_some(*temp_c)))
+            }
+            (
+                Self::STATE
+                | Self::BUTTON_EVENT
+                | Self::BOOTLOADER_TOKEN
+                | Self::FAILSAFE
+                | Self::ODOMETER_CHUNK
+                | Self::REMOTE_WAKEUP
+                | Self::PROBE_TEMPERATURE,
+                _,
+            ) => Err(ReportConvError::Length),
+            _ => Err(ReportConvError::Kind),
+        }
+    }
+}
+
+/// Report sent by the host to the device through the output HID report.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum HostReport {
+    /// A command to execute through the cooler buttons.
+    Command(DeviceCommand),
+    /// Reset the device and leave it in bootloader mode. Only honored if the token matches the
+    /// last [`DeviceReport::BootloaderToken`] sent by the device.
+    EnterBootloader(u16),
+    /// Ask the device for a [`DeviceReport::BootloaderToken`].
+    RequestBootloaderToken,
+    /// Make the next long press on the power button enter bootloader mode.
+    ArmBootloader,
+    /// Keepalive from the host. If no other heartbeat arrives within the timeout while the USB is
+    /// not suspended, the device drives the cooler to the fail-safe fan speed on its own. A zero
+    /// timeout disables the heartbeat monitoring.
+    Heartbeat {
+        timeout_s: u8,
+        failsafe_speed: FanSpeed,
+    },
+    /// The fan curve for the device to pick the fan speed with. Persisted by the device.
+    FanCurve(FanCurve),
+    /// The host temperature, in whole degrees Celsius, for the device to pick the fan speed for
+    /// through the fan curve. Meant to be sent periodically while the automatic fan speed
+    /// adjustment runs on the device.
+    Temperature(u8),
+    /// Measure the fan voltage at every fan speed and persist it as the fan voltage sensing
+    /// calibration. Ignored by devices without fan voltage sensing.
+    CalibrateFanSense,
+    /// Re-home the cooler: find out whether it is powered on, press speed down to the lowest fan
+    /// speed, bring the power and LEDs to the requested state and restore the previous fan speed.
+    /// Clears the uncertainty flag of the device state.
+    Resync {
+        power_enabled: bool,
+        leds_enabled: bool,
+    },
+    /// Ask the device for its [`Odometer`], sent back as [`DeviceReport::OdometerChunk`] reports.
+    ReadOdometer,
+    /// Reset all the [`Odometer`] counters to zero.
+    ResetOdometer,
+    /// Cycle the LEDs to the given color, pressing the LED button as many times as it takes from
+    /// the tracked color.
+    SetLedColor(LedColor),
+    /// Ask the device whether a power button press wakes up the host, sent back as a
+    /// [`DeviceReport::RemoteWakeup`].
+    ReadRemoteWakeup,
+    /// Whether a power button press wakes up the host while the USB is suspended, provided that
+    /// the host enabled remote wakeup for the device. Persisted by the device, which sends it back
+    /// as a [`DeviceReport::RemoteWakeup`] once applied.
+    RemoteWakeup(bool),
+    /// Ask the device for the crash report and the watchdog recovery it keeps, sent back as a
+    /// [`DeviceReport::Crash`] and a [`DeviceReport::WatchdogRecovery`] if there are any.
+    ReadCrash,
+    /// Let the device clear the crash report it sent, as the host took note of it.
+    AcknowledgeCrash,
+    /// Let the device forget the watchdog recovery it sent, as the host took note of it.
+    AcknowledgeWatchdogRecovery,
+}
+
+impl HostReport {
+    const COMMAND: u8 = 1;
+    const ENTER_BOOTLOADER: u8 = 2;
+    const REQUEST_BOOTLOADER_TOKEN: u8 = 3;
+    const ARM_BOOTLOADER: u8 = 4;
+    const HEARTBEAT: u8 = 5;
+    const FAN_CURVE: u8 = 6;
+    const TEMPERATURE: u8 = 7;
+    const CALIBRATE_FAN_SENSE: u8 = 8;
+    const RESYNC: u8 = 9;
+    const READ_ODOMETER: u8 = 10;
+    const RESET_ODOMETER: u8 = 11;
+    const SET_LED_COLOR: u8 = 14;
+    const READ_REMOTE_WAKEUP: u8 = 15;
+    const REMOTE_WAKEUP: u8 = 16;
+    const READ_CRASH: u8 = 17;
+    const ACKNOWLEDGE_CRASH: u8 = 18;
+    const ACKNOWLEDGE_WATCHDOG_RECOVERY: u8 = 19;
+}
+
+impl From<HostReport> for [u8; REPORT_LEN] {
+    fn from(value: HostReport) -> Self {
+        let mut buf = [0; REPORT_LEN];
+
+        match value {
+            HostReport::Command(command) => {
+                buf[0] = HostReport::COMMAND;
+                buf[1] = command.into();
+            }
+            HostReport::EnterBootloader(token) => {
+                buf[0] = HostReport::ENTER_BOOTLOADER;
+                buf[1..3].copy_from_slice(&token.to_le_bytes());
+            }
+            HostReport::RequestBootloaderToken => buf[0] = HostReport::REQUEST_BOOTLOADER_TOKEN,
+            HostReport::ArmBootloader => buf[0] = HostReport::ARM_BOOTLOADER,
+            HostReport::Heartbeat {
+                timeout_s,
+                failsafe_speed,
+            } => {
+                buf[0] = HostReport::HEARTBEAT;
+                buf[1] = timeout_s;
+                buf[2] = failsafe_speed.into();
+            }
+            HostReport::FanCurve(curve) => {
+                buf[0] = HostReport::FAN_CURVE;
+                buf[1..=FanCurve::LEN].copy_from_slice(&<[u8; FanCurve::LEN]>::from(curve));
+            }
+            HostReport::Temperature(temp_c) => {
+                buf[0] = HostReport::TEMPERATURE;
+                buf[1] = temp_c;
+            }
+            HostReport::CalibrateFanSense => buf[0] = HostReport::CALIBRATE_FAN_SENSE,
+            HostReport::Resync {
+                power_enabled,
+                leds_enabled,
+            } => {
+                buf[0] = HostReport::RESYNC;
+                buf[1] = power_enabled.into();
+                buf[2] = leds_enabled.into();
+            }
+            HostReport::ReadOdometer => buf[0] = HostReport::READ_ODOMETER,
+            HostReport::ResetOdometer => buf[0] = HostReport::RESET_ODOMETER,
+            HostReport::SetLedColor(led_color) => {
+                buf[0] = HostReport::SET_LED_COLOR;
+                buf[1] = led_color.into();
+            }
+            HostReport::ReadRemoteWakeup => buf[0] = HostReport::READ_REMOTE_WAKEUP,
+            HostReport::RemoteWakeup(enabled) => {
+                buf[0] = HostReport::REMOTE_WAKEUP;
+                buf[1] = enabled.into();
+            }
+            HostReport::ReadCrash => buf[0] = HostReport::READ_CRASH,
+            HostReport::AcknowledgeCrash => buf[0] = HostReport::ACKNOWLEDGE_CRASH,
+            HostReport::AcknowledgeWatchdogRecovery => {
+                buf[0] = HostReport::ACKNOWLEDGE_WATCHDOG_RECOVERY;
+            }
+        }
+
+        buf
+    }
+}
+
+impl TryFrom<&[u8]> for HostReport {
+    type Error = ReportConvError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let [kind, payload @ ..] = value else {
+            return Err(ReportConvError::Length);
+        };
+
+        match (*kind, payload) {
+            (Self::COMMAND, [command, ..]) => Ok(Self::Command((*command).try_into()?)),
+            (Self::ENTER_BOOTLOADER, [lo, hi, ..]) => {
+                Ok(Self::EnterBootloader(u16::from_le_bytes([*lo, *hi])))
+            }
+            (Self::REQUEST_BOOTLOADER_TOKEN, _) => Ok(Self::RequestBootloaderToken),
+            (Self::ARM_BOOTLOADER, _) => Ok(Self::ArmBootloader),
+            (Self::HEARTBEAT, [timeout_s, failsafe_speed, ..]) => Ok(Self::Heartbeat {
+                timeout_s: *timeout_s,
+                failsafe_speed: (*failsafe_speed).try_into()?,
+            }),
+            (Self::FAN_CURVE, curve) => Ok(Self::FanCurve(curve.try_into()?)),
+            (Self::TEMPERATURE, [temp_c, ..]) => Ok(Self::Temperature(*temp_c)),
+            (Self::CALIBRATE_FAN_SENSE, _) => Ok(Self::CalibrateFanSense),
+            (Self::RESYNC, [power_enabled, leds_enabled, ..]) => Ok(Self::Resync {
+                power_enabled: *power_enabled != 0,
+                leds_enabled: *leds_enabled != 0,
+            }),
+            (Self::READ_ODOMETER, _) => Ok(Self::ReadOdometer),
+            (Self::RESET_ODOMETER, _) => Ok(Self::ResetOdometer),
+            (Self::SET_LED_COLOR, [led_color, ..]) => {
+                Ok(Self::SetLedColor((*led_color).try_into()?))
+            }
+            (Self::READ_REMOTE_WAKEUP, _) => Ok(Self::ReadRemoteWakeup),
+            (Self::REMOTE_WAKEUP, [enabled, ..]) => Ok(Self::RemoteWakeup(*enabled != 0)),
+            (Self::READ_CRASH, _) => Ok(Self::ReadCrash),
+            (Self::ACKNOWLEDGE_CRASH, _) => Ok(Self::AcknowledgeCrash),
+            (Self::ACKNOWLEDGE_WATCHDOG_RECOVERY, _) => Ok(Self::AcknowledgeWatchdogRecovery),
+            (
+                Self::COMMAND
+                | Self::ENTER_BOOTLOADER
+                | Self::HEARTBEAT
+                | Self::TEMPERATURE
+                | Self::RESYNC
+                | Self::SET_LED_COLOR
+                | Self::REMOTE_WAKEUP,
+                _,
+            ) => Err(ReportConvError::Length),
+            _ => Err(ReportConvError::Kind),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ReportConvError {
+    #[error("unknown report kind")]
+    Kind,
+    #[error("report too short")]
+    Length,
+    #[error(transparent)]
+    State(#[from] DeviceStateConvError),
+    #[error(transparent)]
+    Command(#[from] CommandConvError),
+    #[error(transparent)]
+    ButtonEvent(#[from] ButtonEventConvError),
+    #[error(transparent)]
+    Crash(#[from] CrashReportConvError),
+    #[error(transparent)]
+    FanSpeed(#[from] FanSpeedConvError),
+    #[error(transparent)]
+    FanCurve(#[from] FanCurveConvError),
+    #[error(transparent)]
+    FanSenseCalibration(#[from] FanSenseCalibrationConvError),
+    #[error(transparent)]
+    LedColor(#[from] LedColorConvError),
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use crate::{
+        ButtonEvent, CrashReport, DeviceCommand, DeviceReport, DeviceState, FanCurve,
+        FanSenseCalibration, FanSpeed, HostReport, LedColor, Odometer, REPORT_LEN, ResetCause,
+        report::ReportConvError,
+    };
+
+    #[test]
+    fn test_device_report_conversion() {
+        let mut uncertain_state = DeviceState::new();
+        uncertain_state.set_uncertain(true);
+        let mut colored_state = DeviceState::new();
+        colored_state.set_led_color(LedColor::White);
+
+        let reports = ButtonEvent::iter().map(DeviceReport::ButtonEvent).chain([
+            DeviceReport::State(DeviceState::new()),
+            DeviceReport::State(uncertain_state),
+            DeviceReport::State(colored_state),
+            DeviceReport::BootloaderToken(0xBEEF),
+            DeviceReport::Crash(CrashReport {
+                location: 0xBEEF,
+                line: 42,
+                reset_cause: ResetCause::Panic,
+                last_command: Some(DeviceCommand::SpeedUp),
+                uptime_s: u32::MAX,
+            }),
+            DeviceReport::WatchdogRecovery,
+            DeviceReport::Failsafe(true),
+            DeviceReport::Failsafe(false),
+            DeviceReport::FanSenseCalibration(FanSenseCalibration {
+                levels: [300, 380, 460, 540, 620, 700],
+            }),
+            DeviceReport::FanSenseCalibrationRefused,
+            DeviceReport::OdometerChunk {
+                index: Odometer::CHUNKS - 1,
+                chunk: [0xAB; Odometer::CHUNK_LEN],
+            },
+            DeviceReport::RemoteWakeup(true),
+            DeviceReport::RemoteWakeup(false),
+            DeviceReport::ProbeTemperature(Some(34)),
+            DeviceReport::ProbeTemperature(None),
+        ]);
+
+        for report in reports {
+            let buf = <[u8; REPORT_LEN]>::from(report);
+            assert_eq!(buf[..].try_into(), Ok(report));
+        }
+    }
+
+    #[test]
+    fn test_host_report_conversion() {
+        let reports = DeviceCommand::iter()
+            .map(HostReport::Command)
+            .chain(LedColor::iter().map(HostReport::SetLedColor))
+            .chain([
+                HostReport::EnterBootloader(0xBEEF),
+                HostReport::RequestBootloaderToken,
+                HostReport::ArmBootloader,
+                HostReport::Heartbeat {
+                    timeout_s: 10,
+                    failsafe_speed: FanSpeed::Speed6,
+                },
+                HostReport::FanCurve(FanCurve::DEFAULT),
+                HostReport::Temperature(72),
+                HostReport::CalibrateFanSense,
+                HostReport::Resync {
+                    power_enabled: true,
+                    leds_enabled: false,
+                },
+                HostReport::ReadOdometer,
+                HostReport::ResetOdometer,
+                HostReport::ReadRemoteWakeup,
+                HostReport::RemoteWakeup(true),
+                HostReport::RemoteWakeup(false),
+                HostReport::ReadCrash,
+                HostReport::AcknowledgeCrash,
+                HostReport::AcknowledgeWatchdogRecovery,
+            ]);
+
+        for report in reports {
+            let buf = <[u8; REPORT_LEN]>::from(report);
+            assert_eq!(buf[..].try_into(), Ok(report));
+        }
+    }
+
+    #[test]
+    fn test_report_conversion_errors() {
+        assert_eq!(
+            DeviceReport::try_from(&[][..]),
+            Err(ReportConvError::Length)
+        );
+        assert_eq!(DeviceReport::try_from(&[0][..]), Err(ReportConvError::Kind));
+        assert_eq!(HostReport::try_from(&[1][..]), Err(ReportConvError::Length));
+        assert_eq!(
+            HostReport::try_from(&[2, 1][..]),
+            Err(ReportConvError::Length)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use thiserror::Error as ThisError;
+
+/// Cause of the last device reset, as far as the firmware can tell.
+///
+/// The Caterina bootloader clears the MCU reset flags before starting the firmware, so the cause
+/// is mostly derived from a marker the firmware leaves in RAM before resetting.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(test, derive(strum::EnumIter))]
+pub enum ResetCause {
+    /// The device was powered on, or the cause could not be determined.
+    PowerOn = 1,
+    /// The device was reset unexpectedly while running, typically by the watchdog. Resets through
+    /// the reset pin look the same.
+    Watchdog,
+    /// The firmware panicked and reset the device.
+    Panic,
+    /// The device was reset into the bootloader, e.g. for flashing.
+    Bootloader,
+}
+
+impl From<ResetCause> for u8 {
+    fn from(value: ResetCause) -> Self {
+        value as Self
+    }
+}
+
+impl TryFrom<u8> for ResetCause {
+    type Error = ResetCauseConvError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ResetCause::PowerOn),
+            2 => Ok(ResetCause::Watchdog),
+            3 => Ok(ResetCause::Panic),
+            4 => Ok(ResetCause::Bootloader),
+            _ => Err(ResetCauseConvError),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("integer to reset cause conversion failed")]
+pub struct ResetCauseConvError;
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use crate::ResetCause;
+
+    #[test]
+    fn test_reset_cause_conversion() {
+        for cause in ResetCause::iter() {
+            assert_eq!((cause as u8).try_into(), Ok(cause));
+        }
+    }
+}
@@ -1,24 +1,60 @@
 use std::{
-    sync::Arc,
+    pin::pin,
+    sync::{Arc, Mutex, PoisonError},
     task::{Poll, ready},
+    thread,
+    time::Duration,
 };
 
-use anyhow::Context as _;
+use anyhow::{Context as _, bail};
+use futures_channel::oneshot;
 use futures_core::Stream;
-use futures_util::FutureExt;
+use futures_util::{
+    FutureExt, TryStreamExt,
+    future::{self, Either},
+};
+use gtk::glib;
 use rusb::{
-    Device as RusbDevice, DeviceHandle, Direction, LogCallbackMode, LogLevel, TransferType,
-    UsbContext,
+    Device as RusbDevice, DeviceHandle, Direction, LogCallbackMode, LogLevel, Recipient,
+    RequestType, TransferType, UsbContext, Version,
 };
 use rusb_async::{AsyncContext, FdCallbacksEventHandler, InterruptTransfer};
-use shared::{DeviceCommand, DeviceState, USB_MANUFACTURER, USB_PID, USB_PRODUCT, USB_VID};
+use shared::{
+    DeviceCommand, DeviceReport, FanSenseCalibration, HostReport, LedColor, Odometer,
+    OdometerChunks, PressTimings, REPORT_LEN, USB_MANUFACTURER, USB_PID, USB_PRODUCT, USB_VID,
+};
 use tracing::instrument;
 
-use crate::{AnyResult, exactly_one::ExactlyOneIter, fd_callbacks::GlibFdCallbacks};
+use crate::{
+    AnyResult,
+    dispatcher::{Dispatcher, Outcome, Priority},
+    event::{DeviceEventStream, SentReports},
+    exactly_one::ExactlyOneIter,
+    fd_callbacks::GlibFdCallbacks,
+};
+
+/// USB interface class of the HID interface, which firmwares built with the `debug-console` feature
+/// pair with the CDC-ACM ones.
+const HID_INTERFACE_CLASS: u8 = 0x03;
+/// How long to wait for the fan voltage sensing calibration, which lets the fans settle for two
+/// seconds at each of the six speeds.
+const CALIBRATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for the odometer, which the device sends right away.
+const ODOMETER_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait for the remote wakeup setting, which the device sends right away.
+const REMOTE_WAKEUP_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait for the feature report transfers, which the device answers right away.
+const FEATURE_REPORT_TIMEOUT: Duration = Duration::from_millis(500);
+/// HID class requests and the report type of the feature report, as in the HID specification.
+const HID_GET_REPORT: u8 = 0x01;
+const HID_SET_REPORT: u8 = 0x09;
+const HID_FEATURE_REPORT: u16 = 0x0300;
 
 /// Cheaply clonable struct used to represent the physical device to communicate with.
+///
+/// The clones share the opened device, which [`Device::reconnect`] replaces for all of them.
 #[derive(Clone, Debug)]
-pub struct Device(Arc<DeviceInner>);
+pub struct Device(Arc<Mutex<Arc<DeviceInner>>>);
 
 impl Device {
     /// Creates a device instance which can be used for reading and writing.
@@ -29,124 +65,359 @@ impl Device {
     /// could not be set up and claimed.
     #[instrument(err(Debug), ret)]
     pub fn new() -> AnyResult<Self> {
-        let event_handler = FdCallbacksEventHandler::new(GlibFdCallbacks::default());
-        let mut context =
-            AsyncContext::new(event_handler).context("could not initialize libusb")?;
+        let inner = DeviceInner::open()?;
+        Ok(Self(Arc::new(Mutex::new(Arc::new(inner)))))
+    }
 
-        context.set_log_level(LogLevel::Warning);
-        context.set_log_callback(Box::new(Self::log_message), LogCallbackMode::Global);
+    /// Opens the device anew, e.g. once it got plugged back in after a disconnect. All the clones
+    /// then talk to the reopened device, while the streams created before keep reading from the
+    /// old one, so they must be created anew.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device could not be opened, see [`Device::new`].
+    #[instrument(skip(self))]
+    pub fn reconnect(&self) -> AnyResult<()> {
+        let inner = DeviceInner::open()?;
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Arc::new(inner);
+        Ok(())
+    }
 
-        let handle = context
-            .devices()?
-            .iter()
-            .filter_map(Self::device_filter)
-            .exactly_one()
-            .map(Arc::new)
-            .context("could not find a matching device or open it")?;
+    /// Creates a [`DeviceReportStream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`InterruptTransfer::new`] fails.
+    #[instrument(skip(self), err(Debug))]
+    pub fn report_stream(&self) -> AnyResult<DeviceReportStream> {
+        self.inner().report_stream()
+    }
 
-        let device = handle.device();
-        let device_desc = device
-            .device_descriptor()
-            .context("failed to read the device descriptor")?;
+    /// Creates a [`DeviceEventStream`] over a new [`DeviceReportStream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`InterruptTransfer::new`] fails.
+    pub fn event_stream(&self) -> AnyResult<DeviceEventStream> {
+        let inner = self.inner();
+        let reports = inner.report_stream()?;
+        Ok(DeviceEventStream::new(reports, inner.sent_reports.clone()))
+    }
 
-        let config_desc = (0..device_desc.num_configurations())
-            .filter_map(|i| device.config_descriptor(i).ok())
-            .exactly_one()
-            .context("failed to read the config descriptor")?;
+    /// Sends a command to the device on behalf of the user and returns what became of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device was closed.
+    #[instrument(skip(self), err(Debug))]
+    pub async fn send_command(&self, command: DeviceCommand) -> AnyResult<Outcome> {
+        self.send_report(HostReport::Command(command)).await
+    }
 
-        let config_number = config_desc.number();
+    /// Sends a report to the device on behalf of the user and returns what became of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device was closed.
+    pub async fn send_report(&self, report: HostReport) -> AnyResult<Outcome> {
+        self.dispatch(report, Priority::User).await
+    }
 
-        let interface_desc = config_desc
-            .interfaces()
-            .flat_map(|i| i.descriptors())
-            .exactly_one()
-            .context("failed to read the interface descriptor")?;
+    /// Queues a report for the device with the given priority, see [`Priority`], and returns what
+    /// became of it. A failed transfer is not an error but an [`Outcome::Disconnected`], as the
+    /// device may come back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device was closed.
+    #[instrument(skip(self), err(Debug))]
+    pub async fn dispatch(&self, report: HostReport, priority: Priority) -> AnyResult<Outcome> {
+        let dispatcher = &self.inner().dispatcher;
+        dispatcher.dispatch(report, priority).await
+    }
 
-        let interface_number = interface_desc.interface_number();
-        let setting_number = interface_desc.setting_number();
+    /// Makes the device enter bootloader mode by requesting a token and confirming the request with
+    /// it. Other reports received in the meantime are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the reports fails or if the report stream ends or fails.
+    #[instrument(skip_all, err(Debug))]
+    pub async fn enter_bootloader(&self, reports: &mut DeviceReportStream) -> AnyResult<()> {
+        self.send_report(HostReport::RequestBootloaderToken).await?;
+
+        let token = loop {
+            match reports.try_next().await? {
+                Some(DeviceReport::BootloaderToken(token)) => break token,
+                Some(report) => tracing::debug!("discarding report: {report:?}"),
+                None => bail!("report stream ended before receiving the bootloader token"),
+            }
+        };
 
-        let in_endpoint_address = interface_desc
-            .endpoint_descriptors()
-            .filter(|edesc| edesc.direction() == Direction::In)
-            .filter(|edesc| edesc.transfer_type() == TransferType::Interrupt)
-            .exactly_one()
-            .context("failed to read the IN interrupt endpoint descriptor")
-            .map(|e| e.address())?;
+        self.send_report(HostReport::EnterBootloader(token)).await?;
+        Ok(())
+    }
 
-        let out_endpoint_address = interface_desc
-            .endpoint_descriptors()
-            .filter(|edesc| edesc.direction() == Direction::Out)
-            .filter(|edesc| edesc.transfer_type() == TransferType::Interrupt)
-            .exactly_one()
-            .context("failed to read the OUT interrupt endpoint descriptor")
-            .map(|e| e.address())?;
+    /// Makes the next long press on the power button enter bootloader mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails.
+    #[instrument(skip(self), err(Debug))]
+    pub async fn arm_bootloader(&self) -> AnyResult<Outcome> {
+        self.send_report(HostReport::ArmBootloader).await
+    }
 
-        if handle.kernel_driver_active(interface_number)? {
-            tracing::info!("detaching kernel driver");
-            handle
-                .detach_kernel_driver(interface_number)
-                .context("failed to detach kernel driver")?;
-        }
+    /// Makes the device re-home the cooler and bring the power and LEDs to the given state,
+    /// clearing the uncertainty flag of its state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails.
+    #[instrument(skip(self), err(Debug))]
+    pub async fn resync(&self, power_enabled: bool, leds_enabled: bool) -> AnyResult<Outcome> {
+        self.send_report(HostReport::Resync {
+            power_enabled,
+            leds_enabled,
+        })
+        .await
+    }
 
-        handle
-            .set_active_configuration(config_number)
-            .context("failed to set config number")?;
+    /// Makes the device measure the fan voltage at every fan speed and waits for the resulting
+    /// calibration. Other reports received in the meantime are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails, if the report stream ends or fails, if the
+    /// device refuses to calibrate because the cooler is powered off or if the device does not
+    /// report the calibration in time, e.g. because its firmware was not built with the
+    /// `fan-sense` feature.
+    #[instrument(skip_all, err(Debug))]
+    pub async fn calibrate_fan_sense(
+        &self,
+        reports: &mut DeviceReportStream,
+    ) -> AnyResult<FanSenseCalibration> {
+        self.send_report(HostReport::CalibrateFanSense).await?;
+
+        let calibration = pin!(async {
+            loop {
+                match reports.try_next().await? {
+                    Some(DeviceReport::FanSenseCalibration(calibration)) => {
+                        return AnyResult::Ok(calibration);
+                    }
+                    Some(DeviceReport::FanSenseCalibrationRefused) => {
+                        bail!("the cooler is powered off, power it on and try again")
+                    }
+                    Some(report) => tracing::debug!("discarding report: {report:?}"),
+                    None => bail!("report stream ended before receiving the calibration"),
+                }
+            }
+        });
+
+        match future::select(calibration, glib::timeout_future(CALIBRATION_TIMEOUT)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => bail!(
+                "the device did not report the calibration, check that its firmware was built \
+                 with the `fan-sense` feature"
+            ),
+        }
+    }
 
-        handle
-            .claim_interface(interface_number)
-            .context("failed to claim interface")?;
+    /// Reads the lifetime usage counters of the device. Other reports received in the meantime are
+    /// discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails, if the report stream ends or fails or if the
+    /// device does not report the counters in time, e.g. because its firmware predates them.
+    #[instrument(skip_all, err(Debug))]
+    pub async fn read_odometer(&self, reports: &mut DeviceReportStream) -> AnyResult<Odometer> {
+        self.send_report(HostReport::ReadOdometer).await?;
+
+        let odometer = pin!(async {
+            let mut chunks = OdometerChunks::new();
+
+            loop {
+                match reports.try_next().await? {
+                    Some(DeviceReport::OdometerChunk { index, chunk }) => {
+                        if let Some(odometer) = chunks.insert(index, chunk) {
+                            return AnyResult::Ok(odometer);
+                        }
+                    }
+                    Some(report) => tracing::debug!("discarding report: {report:?}"),
+                    None => bail!("report stream ended before receiving the odometer"),
+                }
+            }
+        });
 
-        handle
-            .set_alternate_setting(interface_number, setting_number)
-            .context("failed to choose alternate setting")?;
+        match future::select(odometer, glib::timeout_future(ODOMETER_TIMEOUT)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => bail!(
+                "the device did not report the odometer, check that its firmware is up to date"
+            ),
+        }
+    }
 
-        let inner = DeviceInner {
-            handle,
-            interface_number,
-            in_endpoint_address,
-            out_endpoint_address,
-        };
+    /// Resets the lifetime usage counters of the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails.
+    #[instrument(skip(self), err(Debug))]
+    pub async fn reset_odometer(&self) -> AnyResult<Outcome> {
+        self.send_report(HostReport::ResetOdometer).await
+    }
 
-        Ok(Self(Arc::new(inner)))
+    /// Reads the press timings of the device from its feature report. The control transfer
+    /// bypasses the queued reports, so the device answers right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transfer fails or the device sends invalid timings.
+    #[instrument(skip(self), err(Debug), ret)]
+    pub async fn press_timings(&self) -> AnyResult<PressTimings> {
+        let request_type =
+            rusb::request_type(Direction::In, RequestType::Class, Recipient::Interface);
+
+        let (buf, len) = self
+            .control_transfer(move |handle, interface_number| {
+                let mut buf = [0; PressTimings::LEN];
+                let len = handle.read_control(
+                    request_type,
+                    HID_GET_REPORT,
+                    HID_FEATURE_REPORT,
+                    interface_number.into(),
+                    &mut buf,
+                    FEATURE_REPORT_TIMEOUT,
+                )?;
+
+                Ok((buf, len))
+            })
+            .await
+            .context("failed to read the press timings, check that the firmware is up to date")?;
+
+        Ok(PressTimings::try_from(&buf[..len])?)
     }
 
-    /// Creates a [`DeviceStateStream`].
+    /// Makes the device use and persist the given press timings by writing its feature report.
     ///
     /// # Errors
     ///
-    /// Returns an error if [`InterruptTransfer::new`] fails.
+    /// Returns an error if the transfer fails, which includes the device rejecting the timings as
+    /// not valid.
     #[instrument(skip(self), err(Debug))]
-    pub fn state_stream(&self) -> AnyResult<DeviceStateStream> {
-        let transfer = InterruptTransfer::new(
-            self.0.handle.clone(),
-            self.0.in_endpoint_address,
-            vec![0; 1],
-        )?;
-
-        Ok(DeviceStateStream {
-            transfer,
-            in_endpoint_address: self.0.in_endpoint_address,
+    pub async fn set_press_timings(&self, press_timings: PressTimings) -> AnyResult<()> {
+        let buf = <[u8; PressTimings::LEN]>::from(press_timings);
+        let request_type =
+            rusb::request_type(Direction::Out, RequestType::Class, Recipient::Interface);
+
+        self.control_transfer(move |handle, interface_number| {
+            handle.write_control(
+                request_type,
+                HID_SET_REPORT,
+                HID_FEATURE_REPORT,
+                interface_number.into(),
+                &buf,
+                FEATURE_REPORT_TIMEOUT,
+            )
         })
+        .await
+        .context("failed to write the press timings")?;
+
+        Ok(())
     }
 
-    /// Sends a command to the device.
+    /// Makes the device cycle the LEDs to the given color. The device confirms it with a
+    /// [`DeviceReport::State`] once the LED button got pressed enough times.
     ///
     /// # Errors
     ///
-    /// Returns an error if [`InterruptTransfer::new`] fails
-    /// or if the transfer could not be completed.
+    /// Returns an error if sending the report fails.
     #[instrument(skip(self), err(Debug))]
-    pub async fn send_command(&self, command: DeviceCommand) -> AnyResult<()> {
-        tracing::info!("sending command");
+    pub async fn set_led_color(&self, led_color: LedColor) -> AnyResult<Outcome> {
+        self.send_report(HostReport::SetLedColor(led_color)).await
+    }
 
-        InterruptTransfer::new(
-            self.0.handle.clone(),
-            self.0.out_endpoint_address,
-            vec![command.into(); 1],
-        )?
-        .await?;
+    /// Makes the device persist whether a power button press wakes up the host while suspended, if
+    /// `enabled` is given, and returns the setting the device reports back. Other reports received
+    /// in the meantime are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the report fails, if the report stream ends or fails or if the
+    /// device does not report the setting in time, e.g. because its firmware predates it.
+    #[instrument(skip(self, reports), err(Debug))]
+    pub async fn remote_wakeup(
+        &self,
+        reports: &mut DeviceReportStream,
+        enabled: Option<bool>,
+    ) -> AnyResult<bool> {
+        let report = enabled.map_or(HostReport::ReadRemoteWakeup, HostReport::RemoteWakeup);
+        self.send_report(report).await?;
+
+        let remote_wakeup = pin!(async {
+            loop {
+                match reports.try_next().await? {
+                    Some(DeviceReport::RemoteWakeup(enabled)) => return AnyResult::Ok(enabled),
+                    Some(report) => tracing::debug!("discarding report: {report:?}"),
+                    None => bail!("report stream ended before receiving the remote wakeup setting"),
+                }
+            }
+        });
+
+        match future::select(remote_wakeup, glib::timeout_future(REMOTE_WAKEUP_TIMEOUT)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => bail!(
+                "the device did not report the remote wakeup setting, check that its firmware is \
+                 up to date"
+            ),
+        }
+    }
 
-        Ok(())
+    /// Returns the firmware version, as reported in the USB device descriptor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device descriptor could not be read.
+    pub fn firmware_version(&self) -> AnyResult<Version> {
+        let desc = self
+            .inner()
+            .handle
+            .device()
+            .device_descriptor()
+            .context("failed to read the device descriptor")?;
+
+        Ok(desc.device_version())
+    }
+
+    /// Runs a blocking control transfer on its own thread and waits for it, so that the event loop
+    /// keeps running meanwhile. The transfer gets the handle and the interface number.
+    async fn control_transfer<F, T>(&self, transfer: F) -> AnyResult<T>
+    where
+        F: FnOnce(&DeviceHandle<AsyncContext>, u8) -> rusb::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = self.inner();
+        let handle = inner.handle.clone();
+        let interface_number = inner.interface_number;
+        let (reply, result) = oneshot::channel();
+
+        thread::spawn(move || {
+            // The caller may have stopped waiting.
+            let _ = reply.send(transfer(&handle, interface_number));
+        });
+
+        Ok(result
+            .await
+            .context("the control transfer thread stopped")??)
+    }
+
+    /// The currently opened device.
+    fn inner(&self) -> Arc<DeviceInner> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 
     #[expect(clippy::needless_pass_by_value, reason = "used in a `filter_map`")]
@@ -180,45 +451,156 @@ impl Device {
     }
 }
 
-/// A never ending [`Stream`] that reads and returns the [`DeviceState`].
+/// A never ending [`Stream`] that reads and returns the [`DeviceReport`] values.
+///
+/// Only the transfer failures are yielded as errors. Reports that cannot be parsed, e.g. because a
+/// newer firmware sends kinds this tray does not know about, are logged and skipped.
 #[derive(Debug)]
-pub struct DeviceStateStream {
+pub struct DeviceReportStream {
     transfer: InterruptTransfer<AsyncContext>,
     in_endpoint_address: u8,
 }
 
-impl Stream for DeviceStateStream {
-    type Item = AnyResult<DeviceState>;
+impl Stream for DeviceReportStream {
+    type Item = AnyResult<DeviceReport>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        let state = ready!(self.transfer.poll_unpin(cx))?
-            .into_iter()
-            .exactly_one()?
-            .try_into()?;
+        loop {
+            let buf = ready!(self.transfer.poll_unpin(cx))?;
 
-        let endpoint = self.in_endpoint_address;
-        self.transfer.renew(endpoint, vec![0; 1])?;
+            let endpoint = self.in_endpoint_address;
+            self.transfer.renew(endpoint, vec![0; REPORT_LEN])?;
 
-        Poll::Ready(Some(Ok(state)))
+            match DeviceReport::try_from(buf.as_slice()) {
+                Ok(report) => return Poll::Ready(Some(Ok(report))),
+                Err(e) => tracing::warn!("skipping report {buf:?}: {e}"),
+            }
+        }
     }
 }
 
 /// Inner struct that allows providing a [`Drop`] implementation for the cheaply clonable [`Device`]
 /// wrapper type.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct DeviceInner {
     /// Using an [`Arc`] because that's what the async libusb transfers require.
     handle: Arc<DeviceHandle<AsyncContext>>,
     interface_number: u8,
     in_endpoint_address: u8,
-    out_endpoint_address: u8,
+    /// Owns the OUT endpoint.
+    dispatcher: Dispatcher,
+    sent_reports: Arc<SentReports>,
+}
+
+impl DeviceInner {
+    /// Opens and sets up the device, see [`Device::new`].
+    fn open() -> AnyResult<Self> {
+        let event_handler = FdCallbacksEventHandler::new(GlibFdCallbacks::default());
+        let mut context =
+            AsyncContext::new(event_handler).context("could not initialize libusb")?;
+
+        context.set_log_level(LogLevel::Warning);
+        context.set_log_callback(Box::new(Device::log_message), LogCallbackMode::Global);
+
+        let handle = context
+            .devices()?
+            .iter()
+            .filter_map(Device::device_filter)
+            .exactly_one()
+            .map(Arc::new)
+            .context("could not find a matching device or open it")?;
+
+        let device = handle.device();
+        let device_desc = device
+            .device_descriptor()
+            .context("failed to read the device descriptor")?;
+
+        let config_desc = (0..device_desc.num_configurations())
+            .filter_map(|i| device.config_descriptor(i).ok())
+            .exactly_one()
+            .context("failed to read the config descriptor")?;
+
+        let config_number = config_desc.number();
+
+        let interface_desc = config_desc
+            .interfaces()
+            .flat_map(|i| i.descriptors())
+            .filter(|idesc| idesc.class_code() == HID_INTERFACE_CLASS)
+            .exactly_one()
+            .context("failed to read the interface descriptor")?;
+
+        let interface_number = interface_desc.interface_number();
+        let setting_number = interface_desc.setting_number();
+
+        let in_endpoint_address = interface_desc
+            .endpoint_descriptors()
+            .filter(|edesc| edesc.direction() == Direction::In)
+            .filter(|edesc| edesc.transfer_type() == TransferType::Interrupt)
+            .exactly_one()
+            .context("failed to read the IN interrupt endpoint descriptor")
+            .map(|e| e.address())?;
+
+        let out_endpoint_address = interface_desc
+            .endpoint_descriptors()
+            .filter(|edesc| edesc.direction() == Direction::Out)
+            .filter(|edesc| edesc.transfer_type() == TransferType::Interrupt)
+            .exactly_one()
+            .context("failed to read the OUT interrupt endpoint descriptor")
+            .map(|e| e.address())?;
+
+        if handle.kernel_driver_active(interface_number)? {
+            tracing::info!("detaching kernel driver");
+            handle
+                .detach_kernel_driver(interface_number)
+                .context("failed to detach kernel driver")?;
+        }
+
+        handle
+            .set_active_configuration(config_number)
+            .context("failed to set config number")?;
+
+        handle
+            .claim_interface(interface_number)
+            .context("failed to claim interface")?;
+
+        handle
+            .set_alternate_setting(interface_number, setting_number)
+            .context("failed to choose alternate setting")?;
+
+        let sent_reports = Arc::new(SentReports::default());
+        let dispatcher =
+            Dispatcher::new(handle.clone(), out_endpoint_address, sent_reports.clone());
+
+        Ok(Self {
+            handle,
+            interface_number,
+            in_endpoint_address,
+            dispatcher,
+            sent_reports,
+        })
+    }
+
+    fn report_stream(&self) -> AnyResult<DeviceReportStream> {
+        let transfer = InterruptTransfer::new(
+            self.handle.clone(),
+            self.in_endpoint_address,
+            vec![0; REPORT_LEN],
+        )?;
+
+        Ok(DeviceReportStream {
+            transfer,
+            in_endpoint_address: self.in_endpoint_address,
+        })
+    }
 }
 
 impl Drop for DeviceInner {
     fn drop(&mut self) {
+        self.dispatcher.close();
+
         if let Ok(false) = self.handle.kernel_driver_active(self.interface_number) {
             if let Err(e) = self.handle.attach_kernel_driver(self.interface_number) {
                 tracing::error!("error re-attaching kernel driver: {e}");
@@ -0,0 +1,260 @@
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    future,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use anyhow::{Context as _, bail};
+use futures_channel::oneshot;
+use gtk::glib;
+use rusb::DeviceHandle;
+use rusb_async::{AsyncContext, InterruptTransfer};
+use shared::{DeviceCommand, HostReport, REPORT_LEN};
+
+use crate::{AnyResult, event::SentReports};
+
+/// Priority of a report queued for the device. Higher priority reports get sent first and are
+/// never superseded by lower priority ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Sent by the automatic fan speed adjustment.
+    Auto,
+    /// Sent on behalf of the user, as well as anything else the tray sends.
+    User,
+}
+
+/// What became of a report queued for the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// The report was sent to the device.
+    Sent,
+    /// The report was dropped before being sent, because a newer one made it pointless.
+    Superseded,
+    /// The report was dropped because the transfer failed, e.g. as the device got unplugged.
+    Disconnected,
+}
+
+/// Actor owning the OUT endpoint of the device, through which all the host reports go.
+///
+/// Reports get queued and sent one at a time, the highest priority first, with at least
+/// [`Dispatcher::GAP`] in between. Queueing a report drops the queued ones it supersedes, such as
+/// an older temperature, and every caller gets the [`Outcome`] of its own report.
+#[derive(Debug)]
+pub(crate) struct Dispatcher(Arc<Mutex<Queue>>);
+
+impl Dispatcher {
+    /// Gap between two reports, long enough for the device to emulate a short press and the delay
+    /// after it with the default press timings. This keeps commands from piling up on the device.
+    const GAP: Duration = Duration::from_millis(55);
+
+    /// Creates the dispatcher and spawns its task on the event loop. The task ends once the
+    /// dispatcher is closed.
+    pub(crate) fn new(
+        handle: Arc<DeviceHandle<AsyncContext>>,
+        out_endpoint_address: u8,
+        sent_reports: Arc<SentReports>,
+    ) -> Self {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        let task = Self::dispatch_task(queue.clone(), handle, out_endpoint_address, sent_reports);
+        glib::spawn_future_local(task);
+        Self(queue)
+    }
+
+    /// Queues the report and waits for it to be sent or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dispatcher was closed.
+    pub(crate) async fn dispatch(
+        &self,
+        report: HostReport,
+        priority: Priority,
+    ) -> AnyResult<Outcome> {
+        let (reply, outcome) = oneshot::channel();
+
+        {
+            let mut queue = lock(&self.0);
+
+            if queue.closed {
+                bail!("the device is closed");
+            }
+
+            queue.requests.retain_mut(|queued| {
+                let superseded = priority >= queued.priority
+                    && supersedes(&report, &queued.report, queued.priority);
+
+                if let Some(reply) = queued.reply.take_if(|_| superseded) {
+                    tracing::debug!("dropping superseded report: {:?}", queued.report);
+                    // The caller may have stopped waiting.
+                    let _ = reply.send(Outcome::Superseded);
+                }
+
+                !superseded
+            });
+
+            queue.requests.push_back(Request {
+                report,
+                priority,
+                reply: Some(reply),
+            });
+
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+
+        outcome.await.context("the dispatcher stopped")
+    }
+
+    /// Stops the dispatcher task once the queued reports are sent.
+    pub(crate) fn close(&self) {
+        let mut queue = lock(&self.0);
+        queue.closed = true;
+
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    async fn dispatch_task(
+        queue: Arc<Mutex<Queue>>,
+        handle: Arc<DeviceHandle<AsyncContext>>,
+        out_endpoint_address: u8,
+        sent_reports: Arc<SentReports>,
+    ) {
+        while let Some(request) = future::poll_fn(|cx| Self::next_request(&queue, cx)).await {
+            tracing::info!("sending report: {:?}", request.report);
+            // Recorded beforehand, as the device may confirm the report before the transfer
+            // completes.
+            sent_reports.record(request.report);
+
+            let transfer = async {
+                InterruptTransfer::new(
+                    handle.clone(),
+                    out_endpoint_address,
+                    <[u8; REPORT_LEN]>::from(request.report).to_vec(),
+                )?
+                .await?;
+
+                AnyResult::Ok(Outcome::Sent)
+            };
+
+            let outcome = transfer.await.unwrap_or_else(|e| {
+                tracing::warn!("dropping report {:?}: {e:#}", request.report);
+                Outcome::Disconnected
+            });
+
+            if let Some(reply) = request.reply {
+                // The caller may have stopped waiting.
+                let _ = reply.send(outcome);
+            }
+
+            glib::timeout_future(Self::GAP).await;
+        }
+    }
+
+    /// Pops the highest priority request, the oldest one first, or returns `None` once the queue
+    /// is closed and empty.
+    fn next_request(queue: &Mutex<Queue>, cx: &Context<'_>) -> Poll<Option<Request>> {
+        let mut queue = lock(queue);
+
+        let index = queue
+            .requests
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, request)| (request.priority, Reverse(*index)))
+            .map(|(index, _)| index);
+
+        match index {
+            Some(index) => Poll::Ready(queue.requests.remove(index)),
+            None if queue.closed => Poll::Ready(None),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    requests: VecDeque<Request>,
+    /// Wakes the dispatcher task up when a request is queued or the queue is closed.
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Request {
+    report: HostReport,
+    priority: Priority,
+    /// Taken when the request gets superseded.
+    reply: Option<oneshot::Sender<Outcome>>,
+}
+
+fn lock(queue: &Mutex<Queue>) -> MutexGuard<'_, Queue> {
+    queue.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Whether a new report makes a queued one pointless: reports setting something to a value, like
+/// the temperature or the LEDs state, supersede the queued ones setting the same thing. Fan speed
+/// steps are relative and only supersede the queued steps of the automatic adjustment, which get
+/// recomputed anyway.
+fn supersedes(report: &HostReport, queued: &HostReport, queued_priority: Priority) -> bool {
+    use DeviceCommand::{LedsOff, LedsOn, PowerOff, PowerOn, SpeedDown, SpeedUp};
+    use HostReport::{Command, FanCurve, Heartbeat, SetLedColor, Temperature};
+
+    match (report, queued) {
+        (Command(SpeedUp | SpeedDown), Command(SpeedUp | SpeedDown)) => {
+            queued_priority == Priority::Auto
+        }
+        (Command(PowerOn | PowerOff), Command(PowerOn | PowerOff))
+        | (Command(LedsOn | LedsOff), Command(LedsOn | LedsOff))
+        | (Temperature(_), Temperature(_))
+        | (FanCurve(_), FanCurve(_))
+        | (Heartbeat { .. }, Heartbeat { .. })
+        | (SetLedColor(_), SetLedColor(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::{DeviceCommand, FanSpeed, HostReport, LedColor};
+
+    use super::{Priority, supersedes};
+
+    #[test]
+    fn test_supersedes() {
+        let power_on = HostReport::Command(DeviceCommand::PowerOn);
+        let power_off = HostReport::Command(DeviceCommand::PowerOff);
+        let leds_on = HostReport::Command(DeviceCommand::LedsOn);
+        let red = HostReport::SetLedColor(LedColor::Red);
+        let blue = HostReport::SetLedColor(LedColor::Blue);
+        let heartbeat = HostReport::Heartbeat {
+            timeout_s: 3,
+            failsafe_speed: FanSpeed::Speed6,
+        };
+
+        let speed_up = HostReport::Command(DeviceCommand::SpeedUp);
+        let speed_down = HostReport::Command(DeviceCommand::SpeedDown);
+
+        assert!(supersedes(&power_off, &power_on, Priority::User));
+        assert!(supersedes(&blue, &red, Priority::User));
+        assert!(supersedes(&heartbeat, &heartbeat, Priority::User));
+        assert!(!supersedes(&leds_on, &power_on, Priority::User));
+        assert!(!supersedes(&red, &heartbeat, Priority::User));
+        assert!(!supersedes(
+            &HostReport::ArmBootloader,
+            &HostReport::ArmBootloader,
+            Priority::User
+        ));
+
+        // Fan speed steps add up, unless queued by the automatic adjustment.
+        assert!(supersedes(&speed_down, &speed_up, Priority::Auto));
+        assert!(!supersedes(&speed_up, &speed_up, Priority::User));
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, ready},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+use shared::{DeviceCommand, DeviceReport, DeviceState, FanSpeed, HostReport, LedColor};
+
+use crate::device::DeviceReportStream;
+
+/// A change of the device state, or any other report of the device, as yielded by the
+/// [`DeviceEventStream`].
+///
+/// The changes of a single state report come in a row, followed by [`DeviceEvent::Confirmed`].
+#[derive(Clone, Copy, Debug)]
+pub enum DeviceEvent {
+    /// The device reported its state for the first time.
+    Connected(DeviceState),
+    /// The device stopped responding, e.g. because it got unplugged. Nothing comes after it, a new
+    /// stream must be created once the device is reconnected, see [`Device::reconnect`].
+    ///
+    /// [`Device::reconnect`]: crate::Device::reconnect
+    Disconnected,
+    PowerChanged {
+        enabled: bool,
+        source: ChangeSource,
+    },
+    LedsChanged {
+        enabled: bool,
+        source: ChangeSource,
+    },
+    LedColorChanged {
+        color: LedColor,
+        source: ChangeSource,
+    },
+    SpeedChanged {
+        from: FanSpeed,
+        to: FanSpeed,
+        source: ChangeSource,
+    },
+    /// The device flagged its state as uncertain, or a resync cleared the flag.
+    UncertainChanged(bool),
+    /// The device could not execute the command, e.g. because the backlight was off, and asks for
+    /// it to be sent again.
+    RepeatRequested(DeviceCommand),
+    /// The device sent its state, as a confirmation of the last command, whether it changed or not.
+    Confirmed(DeviceState),
+    /// A report other than the device state.
+    Report(DeviceReport),
+}
+
+/// What caused a change of the device state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeSource {
+    /// A report recently sent by the tray.
+    Tray,
+    /// Anything else: the cooler's buttons, the device picking the fan speed on its own or the
+    /// device correcting its state from what it senses.
+    External,
+}
+
+/// A [`Stream`] of [`DeviceEvent`] values, wrapping a [`DeviceReportStream`] and telling apart the
+/// changes from one state report to the next.
+///
+/// Transfer failures end the stream with [`DeviceEvent::Disconnected`], while reports that cannot
+/// be parsed get skipped by the [`DeviceReportStream`].
+#[derive(Debug)]
+pub struct DeviceEventStream {
+    reports: DeviceReportStream,
+    sent_reports: Arc<SentReports>,
+    last_state: Option<DeviceState>,
+    queued: VecDeque<DeviceEvent>,
+    disconnected: bool,
+}
+
+impl DeviceEventStream {
+    pub(crate) fn new(reports: DeviceReportStream, sent_reports: Arc<SentReports>) -> Self {
+        Self {
+            reports,
+            sent_reports,
+            last_state: None,
+            queued: VecDeque::new(),
+            disconnected: false,
+        }
+    }
+
+    /// The wrapped report stream, for the requests that wait for a specific report. The state
+    /// reports read through it are not turned into events.
+    pub fn reports_mut(&mut self) -> &mut DeviceReportStream {
+        &mut self.reports
+    }
+
+    fn state_reported(&mut self, state: DeviceState) {
+        match self.last_state.replace(state) {
+            Some(last_state) => {
+                let changes = changes(last_state, state, &self.sent_reports);
+                self.queued.extend(changes);
+            }
+            None => {
+                self.queued.push_back(DeviceEvent::Connected(state));
+                self.queued
+                    .extend(state.command_to_repeat().map(DeviceEvent::RepeatRequested));
+                self.queued.push_back(DeviceEvent::Confirmed(state));
+            }
+        }
+    }
+}
+
+impl Stream for DeviceEventStream {
+    type Item = DeviceEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(event) = this.queued.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+
+            if this.disconnected {
+                return Poll::Ready(None);
+            }
+
+            match ready!(this.reports.poll_next_unpin(cx)) {
+                Some(Ok(DeviceReport::State(state))) => this.state_reported(state),
+                Some(Ok(report)) => return Poll::Ready(Some(DeviceEvent::Report(report))),
+                Some(Err(e)) => {
+                    tracing::error!("device transfer failed: {e:#}");
+                    this.disconnected = true;
+                    this.queued.push_back(DeviceEvent::Disconnected);
+                }
+                None => {
+                    this.disconnected = true;
+                    this.queued.push_back(DeviceEvent::Disconnected);
+                }
+            }
+        }
+    }
+}
+
+/// Reports recently sent to the device that can change its state, for attributing the changes
+/// to the tray.
+#[derive(Debug, Default)]
+pub(crate) struct SentReports(Mutex<VecDeque<(Instant, HostReport)>>);
+
+impl SentReports {
+    /// How long a sent report can take to show in the device state, which covers a resync along
+    /// with the commands queued before it.
+    const EXPIRY: Duration = Duration::from_secs(5);
+
+    pub(crate) fn record(&self, report: HostReport) {
+        if matches!(
+            report,
+            HostReport::Command(_) | HostReport::SetLedColor(_) | HostReport::Resync { .. }
+        ) {
+            self.lock().push_back((Instant::now(), report));
+        }
+    }
+
+    /// Removes the oldest sent report that explains a change, returning whether there was one. A
+    /// resync explains all the changes it leads to, so it is kept until it expires.
+    fn claim(&self, explains: impl Fn(&HostReport) -> bool) -> bool {
+        let mut sent = self.lock();
+
+        while let Some((sent_at, _)) = sent.front() {
+            if sent_at.elapsed() < Self::EXPIRY {
+                break;
+            }
+
+            sent.pop_front();
+        }
+
+        let Some(index) = sent.iter().position(|(_, report)| explains(report)) else {
+            return false;
+        };
+
+        if !matches!(sent[index].1, HostReport::Resync { .. }) {
+            sent.remove(index);
+        }
+
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(Instant, HostReport)>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns the events that lead from one device state to the next, attributing each change to the
+/// tray if it sent a report that explains it.
+fn changes(from: DeviceState, to: DeviceState, sent_reports: &SentReports) -> Vec<DeviceEvent> {
+    let source = |explains: &dyn Fn(&HostReport) -> bool| {
+        if sent_reports.claim(explains) {
+            ChangeSource::Tray
+        } else {
+            ChangeSource::External
+        }
+    };
+
+    let mut events = Vec::new();
+
+    if from.power_enabled() != to.power_enabled() {
+        let enabled = to.power_enabled();
+        let source = source(&|report| match *report {
+            HostReport::Command(DeviceCommand::PowerOn) => enabled,
+            HostReport::Command(DeviceCommand::PowerOff) => !enabled,
+            HostReport::Resync { power_enabled, .. } => power_enabled == enabled,
+            _ => false,
+        });
+        events.push(DeviceEvent::PowerChanged { enabled, source });
+    }
+
+    if from.leds_enabled() != to.leds_enabled() {
+        let enabled = to.leds_enabled();
+        let source = source(&|report| match *report {
+            HostReport::Command(DeviceCommand::LedsOn) => enabled,
+            HostReport::Command(DeviceCommand::LedsOff) => !enabled,
+            HostReport::Resync { leds_enabled, .. } => leds_enabled == enabled,
+            _ => false,
+        });
+        events.push(DeviceEvent::LedsChanged { enabled, source });
+    }
+
+    if from.led_color() != to.led_color() {
+        let color = to.led_color();
+        let source = source(&|report| match *report {
+            HostReport::Command(DeviceCommand::LedsColorChange) => true,
+            HostReport::SetLedColor(led_color) => led_color == color,
+            _ => false,
+        });
+        events.push(DeviceEvent::LedColorChanged { color, source });
+    }
+
+    if from.fan_speed() != to.fan_speed() {
+        let (from, to) = (from.fan_speed(), to.fan_speed());
+        let up = to as u8 > from as u8;
+        let source = source(&|report| match *report {
+            HostReport::Command(DeviceCommand::SpeedUp) => up,
+            HostReport::Command(DeviceCommand::SpeedDown) => !up,
+            HostReport::Resync { .. } => true,
+            _ => false,
+        });
+        events.push(DeviceEvent::SpeedChanged { from, to, source });
+    }
+
+    if from.uncertain() != to.uncertain() {
+        events.push(DeviceEvent::UncertainChanged(to.uncertain()));
+    }
+
+    if let Some(command) = to.command_to_repeat() {
+        // The device bounced the command back instead of executing it.
+        sent_reports.claim(|report| {
+            matches!(*report, HostReport::Command(sent) if u8::from(sent) == u8::from(command))
+        });
+        events.push(DeviceEvent::RepeatRequested(command));
+    }
+
+    events.push(DeviceEvent::Confirmed(to));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::{DeviceCommand, DeviceState, FanSpeed, HostReport};
+
+    use super::{ChangeSource, DeviceEvent, SentReports, changes};
+
+    #[test]
+    fn test_changes_source() {
+        let sent_reports = SentReports::default();
+        let from = DeviceState::new();
+        let mut to = from;
+        to.increase_fan_speed();
+
+        sent_reports.record(HostReport::Command(DeviceCommand::SpeedUp));
+        assert!(matches!(
+            changes(from, to, &sent_reports)[..],
+            [
+                DeviceEvent::SpeedChanged {
+                    from: FanSpeed::Speed1,
+                    to: FanSpeed::Speed2,
+                    source: ChangeSource::Tray,
+                },
+                DeviceEvent::Confirmed(_),
+            ]
+        ));
+
+        // The command was claimed by the first change.
+        assert!(matches!(
+            changes(from, to, &sent_reports)[..],
+            [
+                DeviceEvent::SpeedChanged {
+                    source: ChangeSource::External,
+                    ..
+                },
+                DeviceEvent::Confirmed(_),
+            ]
+        ));
+
+        // A command in the opposite direction does not explain the change.
+        sent_reports.record(HostReport::Command(DeviceCommand::SpeedDown));
+        assert!(matches!(
+            changes(from, to, &sent_reports)[..],
+            [
+                DeviceEvent::SpeedChanged {
+                    source: ChangeSource::External,
+                    ..
+                },
+                DeviceEvent::Confirmed(_),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_changes_resync() {
+        let sent_reports = SentReports::default();
+        let from = DeviceState::new();
+        let mut to = from;
+        to.toggle_power();
+        to.set_uncertain(true);
+
+        sent_reports.record(HostReport::Resync {
+            power_enabled: false,
+            leds_enabled: true,
+        });
+
+        // The resync explains every change it leads to.
+        for _ in 0..2 {
+            assert!(matches!(
+                changes(from, to, &sent_reports)[..],
+                [
+                    DeviceEvent::PowerChanged {
+                        enabled: false,
+                        source: ChangeSource::Tray,
+                    },
+                    DeviceEvent::UncertainChanged(true),
+                    DeviceEvent::Confirmed(_),
+                ]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_changes_repeat() {
+        let sent_reports = SentReports::default();
+        let from = DeviceState::new();
+        let mut to = from;
+        to.set_repeat_command(Some(DeviceCommand::SpeedUp));
+
+        sent_reports.record(HostReport::Command(DeviceCommand::SpeedUp));
+        assert!(matches!(
+            changes(from, to, &sent_reports)[..],
+            [
+                DeviceEvent::RepeatRequested(DeviceCommand::SpeedUp),
+                DeviceEvent::Confirmed(_),
+            ]
+        ));
+
+        // The bounced command no longer explains a change.
+        to.set_repeat_command(None);
+        to.increase_fan_speed();
+        assert!(matches!(
+            changes(from, to, &sent_reports)[..],
+            [
+                DeviceEvent::SpeedChanged {
+                    source: ChangeSource::External,
+                    ..
+                },
+                DeviceEvent::Confirmed(_),
+            ]
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+use std::io::{self, Read, Write};
+
+use thiserror::Error as ThisError;
+
+/// Software identifier returned by the Caterina bootloader.
+const CATERINA_ID: &[u8; 7] = b"CATERIN";
+
+/// Size of the `ATmega32u4` flash available to the application, the rest being taken by the
+/// bootloader.
+pub const APPLICATION_SIZE: usize = 0x7000;
+
+/// Minimal AVR109 programmer, implementing the subset of the protocol needed to write and verify
+/// the flash through the Caterina bootloader.
+#[derive(Debug)]
+pub struct Programmer<P> {
+    port: P,
+    block_size: usize,
+}
+
+impl<P> Programmer<P>
+where
+    P: Read + Write,
+{
+    const CARRIAGE_RETURN: u8 = b'\r';
+    const FLASH_MEMORY: u8 = b'F';
+
+    /// Checks that the bootloader on the other end of the port is supported and enters
+    /// programming mode.
+    pub fn new(mut port: P) -> Result<Self, Avr109Error> {
+        port.write_all(b"S")?;
+        let mut id = [0; CATERINA_ID.len()];
+        port.read_exact(&mut id)?;
+
+        if &id != CATERINA_ID {
+            return Err(Avr109Error::Bootloader(
+                String::from_utf8_lossy(&id).into_owned(),
+            ));
+        }
+
+        port.write_all(b"b")?;
+        let mut block_support = [0; 3];
+        port.read_exact(&mut block_support)?;
+
+        let [b'Y', hi, lo] = block_support else {
+            return Err(Avr109Error::Response);
+        };
+
+        let mut programmer = Self {
+            port,
+            block_size: usize::from(u16::from_be_bytes([hi, lo])),
+        };
+
+        programmer.command(b"P")?;
+        Ok(programmer)
+    }
+
+    /// Erases the application section of the flash.
+    pub fn erase(&mut self) -> Result<(), Avr109Error> {
+        self.command(b"e")
+    }
+
+    /// Writes the image to flash, starting at address 0. Flash is written in words, so the image
+    /// length must be even.
+    pub fn write(&mut self, image: &[u8]) -> Result<(), Avr109Error> {
+        Self::check_size(image)?;
+        self.set_address(0)?;
+
+        for block in image.chunks(self.block_size) {
+            let mut command = Self::block_command(b'B', block.len());
+            command.extend_from_slice(block);
+            self.command(&command)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the flash back and compares it to the image.
+    pub fn verify(&mut self, image: &[u8]) -> Result<(), Avr109Error> {
+        Self::check_size(image)?;
+        self.set_address(0)?;
+
+        let mut buf = vec![0; self.block_size];
+
+        for (index, block) in image.chunks(self.block_size).enumerate() {
+            let command = Self::block_command(b'g', block.len());
+            self.port.write_all(&command)?;
+
+            let flash = &mut buf[..block.len()];
+            self.port.read_exact(flash)?;
+
+            if let Some(offset) = flash.iter().zip(block).position(|(a, b)| a != b) {
+                return Err(Avr109Error::Verify(index * self.block_size + offset));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Leaves programming mode and exits the bootloader, starting the application.
+    pub fn exit(mut self) -> Result<(), Avr109Error> {
+        self.command(b"L")?;
+        self.command(b"E")
+    }
+
+    fn check_size(image: &[u8]) -> Result<(), Avr109Error> {
+        if image.len() > APPLICATION_SIZE {
+            return Err(Avr109Error::Size(image.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the address of the next block operation. The address is in words.
+    fn set_address(&mut self, address: u16) -> Result<(), Avr109Error> {
+        let [hi, lo] = address.to_be_bytes();
+        self.command(&[b'A', hi, lo])
+    }
+
+    fn block_command(command: u8, len: usize) -> Vec<u8> {
+        // Blocks are never larger than the `u16` block size reported by the bootloader.
+        let [hi, lo] = u16::try_from(len).unwrap_or(u16::MAX).to_be_bytes();
+        vec![command, hi, lo, Self::FLASH_MEMORY]
+    }
+
+    /// Sends a command that is acknowledged with a carriage return.
+    fn command(&mut self, command: &[u8]) -> Result<(), Avr109Error> {
+        self.port.write_all(command)?;
+
+        let mut ack = [0];
+        self.port.read_exact(&mut ack)?;
+
+        if ack[0] != Self::CARRIAGE_RETURN {
+            return Err(Avr109Error::Response);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, ThisError)]
+pub enum Avr109Error {
+    #[error("bootloader communication failed")]
+    Io(#[from] io::Error),
+    #[error("unsupported bootloader: {0}")]
+    Bootloader(String),
+    #[error("unexpected bootloader response")]
+    Response,
+    #[error("firmware of {0} bytes does not fit in the flash")]
+    Size(usize),
+    #[error("flash verification failed at address {0:#06x}")]
+    Verify(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        thread,
+    };
+
+    use nix::pty;
+
+    use super::{APPLICATION_SIZE, Avr109Error, Programmer};
+    use crate::flash::serial::SerialPort;
+
+    const BLOCK_SIZE: u16 = 128;
+
+    /// Stand-in for the Caterina bootloader on the other end of a pseudo-terminal. Returns the
+    /// flash contents after the programmer exits the bootloader.
+    fn bootloader(mut port: File, corrupt_writes: bool) -> Vec<u8> {
+        fn read_byte(port: &mut File) -> u8 {
+            let mut buf = [0];
+            port.read_exact(&mut buf).unwrap();
+            buf[0]
+        }
+
+        let mut flash = vec![0; APPLICATION_SIZE];
+        let mut address = 0;
+
+        loop {
+            match read_byte(&mut port) {
+                b'S' => port.write_all(b"CATERIN").unwrap(),
+                b'b' => {
+                    let [hi, lo] = BLOCK_SIZE.to_be_bytes();
+                    port.write_all(&[b'Y', hi, lo]).unwrap();
+                }
+                b'P' | b'L' => port.write_all(b"\r").unwrap(),
+                b'e' => {
+                    flash.fill(0xFF);
+                    port.write_all(b"\r").unwrap();
+                }
+                b'A' => {
+                    let word = u16::from_be_bytes([read_byte(&mut port), read_byte(&mut port)]);
+                    address = usize::from(word) * 2;
+                    port.write_all(b"\r").unwrap();
+                }
+                command @ (b'B' | b'g') => {
+                    let len = u16::from_be_bytes([read_byte(&mut port), read_byte(&mut port)]);
+                    let block = &mut flash[address..address + usize::from(len)];
+                    assert_eq!(read_byte(&mut port), b'F');
+
+                    if command == b'B' {
+                        port.read_exact(block).unwrap();
+                        block[0] ^= u8::from(corrupt_writes);
+                        port.write_all(b"\r").unwrap();
+                    } else {
+                        port.write_all(block).unwrap();
+                    }
+
+                    address += block.len();
+                }
+                b'E' => {
+                    port.write_all(b"\r").unwrap();
+                    // Closing the master end early discards the acknowledgement, so wait for the
+                    // programmer to close the port first.
+                    let _ = port.read(&mut [0]);
+                    return flash;
+                }
+                command => panic!("unexpected command {command}"),
+            }
+        }
+    }
+
+    fn programmer(corrupt_writes: bool) -> (Programmer<SerialPort>, thread::JoinHandle<Vec<u8>>) {
+        let pty = pty::openpty(None, None).unwrap();
+        let port = SerialPort::new(File::from(pty.slave)).unwrap();
+        let master = File::from(pty.master);
+        let handle = thread::spawn(move || bootloader(master, corrupt_writes));
+
+        (Programmer::new(port).unwrap(), handle)
+    }
+
+    #[test]
+    fn test_write_and_verify() {
+        let image = (0..=u8::MAX).cycle().take(1000).collect::<Vec<_>>();
+        let (mut programmer, handle) = programmer(false);
+
+        programmer.erase().unwrap();
+        programmer.write(&image).unwrap();
+        programmer.verify(&image).unwrap();
+        programmer.exit().unwrap();
+
+        let flash = handle.join().unwrap();
+        assert_eq!(flash[..image.len()], image);
+        assert!(flash[image.len()..].iter().all(|b| *b == 0xFF));
+    }
+
+    #[test]
+    fn test_verify_failure() {
+        let image = vec![0x55; 300];
+        let (mut programmer, handle) = programmer(true);
+
+        programmer.erase().unwrap();
+        programmer.write(&image).unwrap();
+        let result = programmer.verify(&image);
+        assert!(matches!(result, Err(Avr109Error::Verify(0))));
+
+        programmer.exit().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_oversized_image() {
+        let (mut programmer, handle) = programmer(false);
+
+        let result = programmer.write(&vec![0; APPLICATION_SIZE + 1]);
+        assert!(matches!(result, Err(Avr109Error::Size(_))));
+
+        programmer.exit().unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use thiserror::Error as ThisError;
+
+/// Parses an Intel HEX file into a flash image starting at address 0. Gaps between records are
+/// filled with `0xFF`, the value of erased flash.
+pub fn parse(text: &str) -> Result<Vec<u8>, HexError> {
+    let mut image = Vec::new();
+    let mut base_address = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let record = Record::parse(line).map_err(|kind| HexError {
+            line: index + 1,
+            kind,
+        })?;
+
+        match record.kind {
+            Record::DATA => {
+                let start = base_address + usize::from(record.address);
+                let end = start + record.data.len();
+
+                if image.len() < end {
+                    image.resize(end, 0xFF);
+                }
+
+                image[start..end].copy_from_slice(&record.data);
+            }
+            Record::END_OF_FILE => return Ok(image),
+            Record::EXTENDED_SEGMENT_ADDRESS | Record::EXTENDED_LINEAR_ADDRESS => {
+                let [hi, lo] = record.data[..] else {
+                    return Err(HexError {
+                        line: index + 1,
+                        kind: HexErrorKind::Length,
+                    });
+                };
+
+                let shift = if record.kind == Record::EXTENDED_SEGMENT_ADDRESS {
+                    4
+                } else {
+                    16
+                };
+
+                base_address = usize::from(u16::from_be_bytes([hi, lo])) << shift;
+            }
+            // Start addresses are meaningless for an AVR flash image.
+            Record::START_SEGMENT_ADDRESS | Record::START_LINEAR_ADDRESS => (),
+            _ => {
+                return Err(HexError {
+                    line: index + 1,
+                    kind: HexErrorKind::RecordType(record.kind),
+                });
+            }
+        }
+    }
+
+    Err(HexError {
+        line: text.lines().count(),
+        kind: HexErrorKind::MissingEndOfFile,
+    })
+}
+
+/// A single `:LLAAAATT<data>CC` line of an Intel HEX file.
+struct Record {
+    kind: u8,
+    address: u16,
+    data: Vec<u8>,
+}
+
+impl Record {
+    const DATA: u8 = 0x00;
+    const END_OF_FILE: u8 = 0x01;
+    const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+    const START_SEGMENT_ADDRESS: u8 = 0x03;
+    const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+    const START_LINEAR_ADDRESS: u8 = 0x05;
+
+    fn parse(line: &str) -> Result<Self, HexErrorKind> {
+        let digits = line.strip_prefix(':').ok_or(HexErrorKind::StartCode)?;
+
+        if digits.len() % 2 != 0 {
+            return Err(HexErrorKind::Length);
+        }
+
+        let bytes = (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| HexErrorKind::Digit)?;
+
+        let [len, addr_hi, addr_lo, kind, ref rest @ ..] = bytes[..] else {
+            return Err(HexErrorKind::Length);
+        };
+
+        if rest.len() != usize::from(len) + 1 {
+            return Err(HexErrorKind::Length);
+        }
+
+        if bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+            return Err(HexErrorKind::Checksum);
+        }
+
+        Ok(Self {
+            kind,
+            address: u16::from_be_bytes([addr_hi, addr_lo]),
+            data: rest[..rest.len() - 1].to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("invalid firmware file at line {line}: {kind}")]
+pub struct HexError {
+    line: usize,
+    kind: HexErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, ThisError)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum HexErrorKind {
+    #[error("record does not start with ':'")]
+    StartCode,
+    #[error("invalid hex digit")]
+    Digit,
+    #[error("record length mismatch")]
+    Length,
+    #[error("checksum mismatch")]
+    Checksum,
+    #[error("unsupported record type {0:#04x}")]
+    RecordType(u8),
+    #[error("missing end of file record")]
+    MissingEndOfFile,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HexError, HexErrorKind, parse};
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+            :0400000001020304F2\n:02000600AABB93\n:020000040000FA\n:00000001FF\n";
+
+        let image = parse(text).unwrap();
+        assert_eq!(image, [1, 2, 3, 4, 0xFF, 0xFF, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line, kind| Err(HexError { line, kind });
+
+        assert_eq!(parse("0000000001FF"), error(1, HexErrorKind::StartCode));
+        assert_eq!(parse(":0000000001FG"), error(1, HexErrorKind::Digit));
+        assert_eq!(parse(":0200000001FF"), error(1, HexErrorKind::Length));
+        assert_eq!(parse(":00000001FE"), error(1, HexErrorKind::Checksum));
+        assert_eq!(parse(":00000006FA"), error(1, HexErrorKind::RecordType(6)));
+        assert_eq!(
+            parse(":0100000001FE\n"),
+            error(1, HexErrorKind::MissingEndOfFile)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+mod avr109;
+mod hex;
+mod serial;
+
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+
+use crate::{
+    AnyResult, Device, block_on,
+    flash::{avr109::Programmer, serial::SerialPort},
+};
+
+/// How long to wait for the bootloader serial port to show up. Caterina leaves bootloader mode on
+/// its own after 8 seconds without being talked to.
+const BOOTLOADER_TIMEOUT: Duration = Duration::from_secs(8);
+/// How long to wait for the device to reconnect after starting the new firmware.
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between checks for the bootloader port or the reconnected device.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Flashes the firmware from an Intel HEX file: puts the running device in bootloader mode, writes
+/// and verifies the flash through the bootloader, then waits for the device to come back and
+/// prints its firmware version.
+///
+/// The version is not expected to match the tray's own, as any image can be flashed.
+///
+/// # Errors
+///
+/// Returns an error if any of the steps fails.
+pub fn flash(firmware: &Path) -> AnyResult<()> {
+    let text = fs::read_to_string(firmware)
+        .with_context(|| format!("could not read {}", firmware.display()))?;
+    let mut image = hex::parse(&text)?;
+    // Flash is written in words.
+    image.resize(image.len().next_multiple_of(2), 0xFF);
+
+    println!("Entering bootloader mode");
+    let device = Device::new()?;
+    let mut reports = device.report_stream()?;
+    block_on(device.enter_bootloader(&mut reports))?;
+    drop((reports, device));
+
+    // The port can show up before udev grants access to it, so keep trying to open it.
+    let port = wait_for(BOOTLOADER_TIMEOUT, || {
+        SerialPort::find_bootloader().and_then(|path| SerialPort::open(&path).ok())
+    })
+    .context("could not open the bootloader serial port")?;
+    flash_image(port, &image)?;
+
+    println!("Waiting for the device to reconnect");
+    let device = wait_for(RECONNECT_TIMEOUT, || Device::new().ok())
+        .context("the device did not reconnect after flashing")?;
+
+    let version = device.firmware_version()?;
+    let version = format!(
+        "{}.{}.{}",
+        version.major(),
+        version.minor(),
+        version.sub_minor()
+    );
+
+    println!("Device running firmware version {version}");
+
+    if version != env!("CARGO_PKG_VERSION") {
+        println!(
+            "Note: the tray version is {}, make sure the two are compatible",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    Ok(())
+}
+
+fn flash_image(port: SerialPort, image: &[u8]) -> AnyResult<()> {
+    let mut programmer = Programmer::new(port)?;
+
+    println!("Erasing flash");
+    programmer.erase()?;
+    println!("Writing {} bytes", image.len());
+    programmer.write(image)?;
+    println!("Verifying flash");
+    programmer.verify(image)?;
+    programmer.exit()?;
+
+    Ok(())
+}
+
+/// Polls until `f` returns a value or the timeout expires.
+fn wait_for<T>(timeout: Duration, mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(value) = f() {
+            return Some(value);
+        }
+
+        if Instant::now() >= deadline {
+            return None;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    fcntl::OFlag,
+    sys::termios::{self, BaudRate, FlushArg, SetArg, SpecialCharacterIndices},
+};
+
+/// USB vendor and product IDs of the Caterina bootloader on the supported boards.
+const BOOTLOADER_IDS: [(u16, u16); 3] = [
+    // SparkFun Pro Micro 5V
+    (0x1B4F, 0x9205),
+    // SparkFun Pro Micro 3.3V
+    (0x1B4F, 0x9203),
+    // Arduino Leonardo
+    (0x2341, 0x0036),
+];
+
+/// Serial port in raw mode, used to talk to the bootloader.
+///
+/// Reads time out after a second without data, returning zero bytes.
+#[derive(Debug)]
+pub struct SerialPort(File);
+
+impl SerialPort {
+    /// Opens and configures the serial port at the given path.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(OFlag::O_NOCTTY.bits())
+            .open(path)?;
+
+        Self::new(file)
+    }
+
+    /// Configures an already opened terminal device.
+    pub fn new(file: File) -> io::Result<Self> {
+        let mut termios = termios::tcgetattr(&file)?;
+        termios::cfmakeraw(&mut termios);
+        // Ignored by CDC-ACM devices, but set for consistency with `avrdude`.
+        termios::cfsetspeed(&mut termios, BaudRate::B57600)?;
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 10;
+        termios::tcsetattr(&file, SetArg::TCSANOW, &termios)?;
+        termios::tcflush(&file, FlushArg::TCIOFLUSH)?;
+
+        Ok(Self(file))
+    }
+
+    /// Looks for the serial port of a board in bootloader mode.
+    pub fn find_bootloader() -> Option<PathBuf> {
+        fs::read_dir("/sys/class/tty")
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|entry| {
+                let is_acm = entry.file_name().to_string_lossy().starts_with("ttyACM");
+                is_acm
+                    && Self::usb_ids(&entry.path()).is_some_and(|ids| BOOTLOADER_IDS.contains(&ids))
+            })
+            .map(|entry| Path::new("/dev").join(entry.file_name()))
+    }
+
+    /// Reads the vendor and product IDs of the USB device a `/sys/class/tty` entry belongs to.
+    fn usb_ids(tty: &Path) -> Option<(u16, u16)> {
+        // The `device` link points to the USB interface, whose parent is the USB device.
+        let interface = fs::canonicalize(tty.join("device")).ok()?;
+        let device = interface.parent()?;
+
+        let read_id = |name| {
+            let id = fs::read_to_string(device.join(name)).ok()?;
+            u16::from_str_radix(id.trim(), 16).ok()
+        };
+
+        Some((read_id("idVendor")?, read_id("idProduct")?))
+    }
+}
+
+impl Read for SerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for SerialPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
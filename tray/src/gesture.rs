@@ -0,0 +1,75 @@
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use shared::ButtonEvent;
+
+use crate::AnyResult;
+
+/// Action performed by the tray when the device reports a [`ButtonEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum GestureAction {
+    /// Do nothing.
+    None,
+    /// Toggle the automatic fan speed adjustment.
+    ToggleSpeedAuto,
+    /// Disable the automatic fan speed adjustment and go to the highest fan speed.
+    Boost,
+    /// Put the device in bootloader mode, ready to be flashed.
+    EnterBootloader,
+}
+
+/// Mapping of the [`ButtonEvent`] values to the [`GestureAction`] to perform.
+#[derive(Clone, Debug)]
+pub struct Gestures(Vec<(ButtonEvent, GestureAction)>);
+
+impl Gestures {
+    /// Command line names of the gestures. [`ButtonEvent::PowerLedsChord`] is left out, as the
+    /// device always enters bootloader mode on it.
+    const NAMES: [(&str, ButtonEvent); 6] = [
+        ("power-long-press", ButtonEvent::PowerLongPress),
+        ("power-double-press", ButtonEvent::PowerDoublePress),
+        ("leds-double-press", ButtonEvent::LedsDoublePress),
+        ("speed-up-double-press", ButtonEvent::SpeedUpDoublePress),
+        ("speed-down-double-press", ButtonEvent::SpeedDownDoublePress),
+        ("speed-chord", ButtonEvent::SpeedChord),
+    ];
+
+    /// Creates the gestures mapping. Later mappings take precedence over earlier ones.
+    #[must_use]
+    pub fn new(mappings: Vec<(ButtonEvent, GestureAction)>) -> Self {
+        Self(mappings)
+    }
+
+    /// Returns the action mapped to the gesture.
+    #[must_use]
+    pub fn action(&self, event: ButtonEvent) -> GestureAction {
+        self.0
+            .iter()
+            .rev()
+            .find(|(e, _)| *e == event)
+            .map_or(GestureAction::None, |(_, action)| *action)
+    }
+
+    /// Parses a `<GESTURE>=<ACTION>` command line mapping.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the gesture or the action are unknown.
+    pub fn parse_mapping(arg: &str) -> AnyResult<(ButtonEvent, GestureAction)> {
+        let (gesture, action) = arg
+            .split_once('=')
+            .context("expecting a <GESTURE>=<ACTION> mapping")?;
+
+        let event = Self::NAMES
+            .iter()
+            .find(|(name, _)| *name == gesture)
+            .map(|(_, event)| *event)
+            .with_context(|| {
+                let names = Self::NAMES.map(|(name, _)| name).join(", ");
+                format!("unknown gesture '{gesture}', expecting one of: {names}")
+            })?;
+
+        let action = GestureAction::from_str(action, true).map_err(|e| anyhow!(e))?;
+
+        Ok((event, action))
+    }
+}
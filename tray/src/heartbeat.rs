@@ -0,0 +1,58 @@
+use futures_util::StreamExt;
+use gtk::glib;
+use shared::{FanSpeed, HostReport};
+use tracing::instrument;
+
+use crate::{AnyResult, Device};
+
+/// Host heartbeat settings. While the heartbeats keep coming, the device leaves the fan speed to
+/// the host. Once they stop for longer than the timeout, the device drives the cooler to the
+/// fail-safe fan speed on its own.
+#[derive(Clone, Copy, Debug)]
+pub struct Heartbeat {
+    timeout_s: u8,
+    failsafe_speed: FanSpeed,
+}
+
+impl Heartbeat {
+    /// Creates the heartbeat settings. The timeout must be at least 3 seconds, so that at least two
+    /// heartbeats fit in it despite the jitter against the device's countdown.
+    #[must_use]
+    pub fn new(timeout_s: u8, failsafe_speed: FanSpeed) -> Self {
+        Self {
+            timeout_s,
+            failsafe_speed,
+        }
+    }
+
+    /// Sends heartbeats a few times per timeout, so that a single late transfer does not trigger
+    /// the fail-safe mode.
+    #[instrument(skip(device), err(Debug))]
+    pub(crate) async fn heartbeat_task(self, device: Device) -> AnyResult<()> {
+        let mut ticker = glib::interval_stream_seconds((u32::from(self.timeout_s) / 3).max(1));
+
+        // The first heartbeat is sent right away, not after the first interval.
+        loop {
+            device.send_report(self.report(self.timeout_s)).await?;
+
+            if ticker.next().await.is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Disables the heartbeat monitoring on the device, so that a deliberate exit does not trigger
+    /// the fail-safe mode.
+    #[instrument(skip(device), err(Debug))]
+    pub(crate) async fn disable(self, device: &Device) -> AnyResult<()> {
+        device.send_report(self.report(0)).await?;
+        Ok(())
+    }
+
+    fn report(self, timeout_s: u8) -> HostReport {
+        HostReport::Heartbeat {
+            timeout_s,
+            failsafe_speed: self.failsafe_speed,
+        }
+    }
+}
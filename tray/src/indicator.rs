@@ -1,15 +1,18 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{fmt::Debug, rc::Rc, time::Duration};
 
-use futures_util::TryStreamExt;
+use futures_util::StreamExt;
 use gtk::{
-    Menu, SeparatorMenuItem,
+    Menu, SeparatorMenuItem, glib,
     traits::{MenuShellExt, WidgetExt},
 };
 use libappindicator::{AppIndicator as LibAppIndicator, AppIndicatorStatus};
-use shared::DeviceCommand;
+use shared::{CrashReport, DeviceCommand, DeviceReport, FanSpeed, HostReport};
 use tracing::instrument;
 
-use crate::{AnyResult, Device, menu::MenuItems};
+use crate::{
+    AnyResult, AutoSettings, ChangeSource, Device, DeviceEvent, DeviceEventStream, GestureAction,
+    Gestures, Heartbeat, menu::MenuItems, notification,
+};
 
 /// The system tray icon UI indicator.
 ///
@@ -18,7 +21,9 @@ use crate::{AnyResult, Device, menu::MenuItems};
 #[derive(Debug)]
 pub struct Indicator {
     app_indicator: AppIndicator,
-    fan_curve: [f32; 5],
+    auto_settings: AutoSettings,
+    gestures: Gestures,
+    heartbeat: Option<Heartbeat>,
 }
 
 impl Indicator {
@@ -28,7 +33,11 @@ impl Indicator {
     ///
     /// Returns an error if [`gtk::init`] fails.
     #[instrument(err(Debug))]
-    pub fn new(fan_curve: [f32; 5]) -> AnyResult<Self> {
+    pub fn new(
+        auto_settings: AutoSettings,
+        gestures: Gestures,
+        heartbeat: Option<Heartbeat>,
+    ) -> AnyResult<Self> {
         gtk::init()?;
 
         let mut app_indicator = LibAppIndicator::new("cooler-than-you-tray", "cooler-than-you");
@@ -36,41 +45,67 @@ impl Indicator {
 
         Ok(Self {
             app_indicator: AppIndicator(app_indicator),
-            fan_curve,
+            auto_settings,
+            gestures,
+            heartbeat,
         })
     }
 
     /// Blocks the current thread by calling [`gtk::main`] to run the event loop.
+    ///
+    /// The heartbeats, if any, are disabled on the way out so that the device does not enter the
+    /// fail-safe mode when the tray is quit.
     pub fn run(mut self, device: Device) {
         let mut menu = Menu::new();
-        let menu_items = MenuItems::new(device.clone(), self.fan_curve);
+        let menu_items = MenuItems::new(device.clone(), self.auto_settings);
 
         menu.append(menu_items.speed_label.as_ref());
+        menu.append(menu_items.probe_label.as_ref());
         menu.append(&SeparatorMenuItem::new());
         menu.append(menu_items.speed_auto.as_ref());
         menu.append(menu_items.speed_up.as_ref());
         menu.append(menu_items.speed_down.as_ref());
         menu.append(&SeparatorMenuItem::new());
         menu.append(menu_items.leds.as_ref());
-        menu.append(menu_items.leds_change_color.as_ref());
+        menu.append(menu_items.leds_color.as_ref());
         menu.append(&SeparatorMenuItem::new());
         menu.append(menu_items.power.as_ref());
+        menu.append(menu_items.resync.as_ref());
         menu.append(&SeparatorMenuItem::new());
+        menu.append(menu_items.remote_wakeup.as_ref());
+        menu.append(menu_items.odometer.as_ref());
+        menu.append(menu_items.advanced.as_ref());
         menu.append(menu_items.quit.as_ref());
 
         // We send the commands this way so that the time between them being sent and read is
         // minimal and happens as soon as the event loop is started.
         crate::spawn_local(Self::power_cycle_device(device.clone()));
 
+        if let Some(heartbeat) = self.heartbeat {
+            crate::spawn_local(heartbeat.heartbeat_task(device.clone()));
+        }
+
         // Spawn background task.
-        crate::spawn_local(Self::background_task(device, menu_items));
+        crate::spawn_local(Self::background_task(
+            device.clone(),
+            menu_items,
+            self.gestures,
+        ));
 
         menu.show_all();
         self.app_indicator.0.set_menu(&mut menu);
 
         gtk::main();
+
+        if let Some(heartbeat) = self.heartbeat {
+            // Errors are already logged and there's nothing else to do about them when exiting.
+            let _ = crate::block_on(heartbeat.disable(&device));
+        }
     }
 
+    /// How often to try opening the device again after it got disconnected.
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
     /// Power cycle the device to ensure it's on.
     /// If it's already off, the first command will be a no-op.
     #[instrument(skip_all, err(Debug))]
@@ -80,33 +115,252 @@ impl Indicator {
         Ok(())
     }
 
-    /// The main background tasks, meant to continuously read the device state and adjust the UI
-    /// according to it.
+    /// The main background tasks, meant to continuously read the device events, adjust the UI
+    /// according to the device state changes and act on the button gestures.
+    ///
+    /// When the device stops responding, e.g. because it got unplugged, the menu gets disabled
+    /// until the device can be opened again, which gets retried every
+    /// [`Indicator::RECONNECT_INTERVAL`]. The menu gets enabled again once the reconnected device
+    /// reports its state.
     #[instrument(skip_all, err(Debug))]
-    async fn background_task(device: Device, menu_items: Rc<MenuItems>) -> AnyResult<()> {
-        let mut state_stream = device.state_stream()?;
+    async fn background_task(
+        device: Device,
+        menu_items: Rc<MenuItems>,
+        gestures: Gestures,
+    ) -> AnyResult<()> {
+        loop {
+            let mut events = device.event_stream()?;
+            Self::handle_events(&device, &menu_items, &gestures, &mut events).await?;
+
+            menu_items.disable();
+            menu_items.remote_wakeup.set_sensitive(false);
+            menu_items.speed_auto.register_disconnected();
+            notification::notify(
+                "Cooler disconnected",
+                "The device stopped responding. The tray reconnects once it is plugged back in.",
+            );
+
+            while device.reconnect().is_err() {
+                glib::timeout_future(Self::RECONNECT_INTERVAL).await;
+            }
 
-        while let Some(device_state) = state_stream.try_next().await? {
-            tracing::info!("received state: {device_state:?}");
+            tracing::info!("device reconnected");
+        }
+    }
+
+    /// Acts on the device events until the stream ends, which only happens after a
+    /// [`DeviceEvent::Disconnected`].
+    async fn handle_events(
+        device: &Device,
+        menu_items: &MenuItems,
+        gestures: &Gestures,
+        events: &mut DeviceEventStream,
+    ) -> AnyResult<()> {
+        let mut fan_speed = None;
+
+        while let Some(event) = events.next().await {
+            match event {
+                DeviceEvent::Connected(device_state) => {
+                    tracing::info!("received initial state: {device_state:?}");
+                    fan_speed = Some(device_state.fan_speed());
+                    menu_items.power.set_active(device_state.power_enabled());
+                    menu_items.leds.set_active(device_state.leds_enabled());
+                    menu_items.leds_color.set_color(device_state.led_color());
+
+                    // The device keeps the report of a crash or a watchdog recovery until it is
+                    // acknowledged.
+                    let device = device.clone();
+                    crate::spawn_local(async move {
+                        device.send_report(HostReport::ReadCrash).await?;
+                        device.send_report(HostReport::ReadRemoteWakeup).await
+                    });
+                }
+                DeviceEvent::Disconnected => tracing::warn!("device disconnected"),
+                DeviceEvent::PowerChanged { enabled, source } => {
+                    tracing::info!("power enabled: {enabled}, changed by: {source:?}");
+                    menu_items.power.set_active(enabled);
+                }
+                DeviceEvent::LedsChanged { enabled, source } => {
+                    tracing::info!("LEDs enabled: {enabled}, changed by: {source:?}");
+                    menu_items.leds.set_active(enabled);
+                }
+                DeviceEvent::LedColorChanged { color, source } => {
+                    tracing::info!("LEDs color: {color:?}, changed by: {source:?}");
+                    menu_items.leds_color.set_color(color);
+                }
+                DeviceEvent::SpeedChanged { from, to, source } => {
+                    tracing::info!("fan speed: {from:?} -> {to:?}, changed by: {source:?}");
+                    fan_speed = Some(to);
+
+                    if source == ChangeSource::External {
+                        menu_items.speed_auto.register_external_speed_change();
+                    }
+                }
+                DeviceEvent::UncertainChanged(uncertain) => {
+                    tracing::warn!("device state uncertain: {uncertain}");
+                }
+                DeviceEvent::RepeatRequested(command) => {
+                    // Sent from its own task, so that the reports keep being read meanwhile.
+                    let device = device.clone();
+                    crate::spawn_local(async move { device.send_command(command).await });
+                }
+                DeviceEvent::Confirmed(device_state) => {
+                    menu_items.speed_auto.register_state(device_state);
+                    // Reverts a color the device ignored because the LEDs were off.
+                    menu_items.leds_color.set_color(device_state.led_color());
+                    menu_items
+                        .speed_label
+                        .update_label(device_state.fan_speed(), device_state.uncertain());
+
+                    // Do not refresh sensitivity if we need to repeat a command first.
+                    if device_state.command_to_repeat().is_none() {
+                        menu_items.refresh_sensitivity();
+                    }
+                }
+                DeviceEvent::Report(report) => {
+                    Self::handle_report(device, menu_items, gestures, events, fan_speed, report)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 
-            let speed = device_state.fan_speed();
-            menu_items.speed_label.update_label(speed);
-            menu_items.speed_auto.register_speed(speed);
+    /// Acts on the reports other than the device state.
+    async fn handle_report(
+        device: &Device,
+        menu_items: &MenuItems,
+        gestures: &Gestures,
+        events: &mut DeviceEventStream,
+        fan_speed: Option<FanSpeed>,
+        report: DeviceReport,
+    ) -> AnyResult<()> {
+        match report {
+            DeviceReport::State(_) => unreachable!("state reports are turned into events"),
+            DeviceReport::ButtonEvent(event) => {
+                tracing::info!("received button event: {event:?}");
+                let action = gestures.action(event);
 
-            menu_items.power.set_active(device_state.power_enabled());
-            menu_items.leds.set_active(device_state.leds_enabled());
+                if action == GestureAction::EnterBootloader {
+                    // Needs the report stream for the device response.
+                    device.enter_bootloader(events.reports_mut()).await?;
+                } else {
+                    Self::handle_gesture(device, menu_items, fan_speed, action);
+                }
+            }
+            DeviceReport::FanSenseCalibration(calibration) => {
+                tracing::info!("fan voltage calibrated: {:?}", calibration.levels);
+            }
+            DeviceReport::FanSenseCalibrationRefused => {
+                tracing::warn!("fan voltage calibration refused, the cooler is powered off");
+            }
+            DeviceReport::OdometerChunk { index, chunk } => {
+                menu_items.odometer.register_chunk(index, chunk);
+            }
+            DeviceReport::RemoteWakeup(enabled) => {
+                tracing::info!("remote wakeup enabled: {enabled}");
+                menu_items.remote_wakeup.register_setting(enabled);
+            }
+            DeviceReport::BootloaderToken(_) => (),
+            DeviceReport::Crash(report) => {
+                tracing::error!("device firmware crashed: {report:?}");
+                Self::notify_crash(&report);
 
-            if let Some(command) = device_state.command_to_repeat() {
-                device.send_command(command).await?;
-                // Do not refresh sensitivity if we need to repeat a command first.
-                continue;
+                let device = device.clone();
+                crate::spawn_local(async move {
+                    device.send_report(HostReport::AcknowledgeCrash).await
+                });
             }
+            DeviceReport::WatchdogRecovery => {
+                tracing::error!("device firmware hung and was reset by the watchdog");
+                notification::notify(
+                    "Cooler firmware recovered",
+                    "The device hung and was reset by the watchdog. Check the power and LEDs \
+                     state of the cooler.",
+                );
 
-            menu_items.refresh_sensitivity();
+                let device = device.clone();
+                crate::spawn_local(async move {
+                    device
+                        .send_report(HostReport::AcknowledgeWatchdogRecovery)
+                        .await
+                });
+            }
+            DeviceReport::Failsafe(active) => Self::notify_failsafe(active),
+            DeviceReport::ProbeTemperature(temp_c) => {
+                tracing::debug!("received probe temperature: {temp_c:?}");
+                menu_items.probe_label.update_label(temp_c);
+                menu_items.speed_auto.register_probe_temp(temp_c);
+            }
         }
 
         Ok(())
     }
+
+    /// Performs the action mapped to a button gesture, except for
+    /// [`GestureAction::EnterBootloader`].
+    #[instrument(skip(device, menu_items))]
+    fn handle_gesture(
+        device: &Device,
+        menu_items: &MenuItems,
+        fan_speed: Option<FanSpeed>,
+        action: GestureAction,
+    ) {
+        match action {
+            GestureAction::None | GestureAction::EnterBootloader => (),
+            GestureAction::ToggleSpeedAuto => menu_items.speed_auto.toggle(),
+            GestureAction::Boost => {
+                menu_items.speed_auto.set_enabled(false);
+
+                // Without a known fan speed, pressing all the way up is the only option.
+                let speed = fan_speed.map_or(FanSpeed::Speed1 as u8, u8::from);
+                let device = device.clone();
+
+                // The presses take a while, so the reports keep being read meanwhile.
+                crate::spawn_local(async move {
+                    for _ in speed..FanSpeed::Speed6 as u8 {
+                        device.send_command(DeviceCommand::SpeedUp).await?;
+                    }
+
+                    AnyResult::Ok(())
+                });
+            }
+        }
+    }
+
+    /// Lets the user know that the device took over the fan speed because the heartbeats stopped
+    /// or that it gave control back once they resumed.
+    fn notify_failsafe(active: bool) {
+        if active {
+            tracing::warn!("device entered the fail-safe mode");
+            notification::notify(
+                "Cooler in fail-safe mode",
+                "The device stopped receiving heartbeats and switched to the fail-safe fan speed.",
+            );
+        } else {
+            tracing::info!("device left the fail-safe mode");
+            notification::notify(
+                "Cooler fail-safe mode ended",
+                "The device is receiving heartbeats again and the fan speed is back under control.",
+            );
+        }
+    }
+
+    /// Lets the user know that the device firmware panicked and was reset.
+    fn notify_crash(report: &CrashReport) {
+        let last_command = report
+            .last_command
+            .map_or_else(|| "none".to_owned(), |command| format!("{command:?}"));
+
+        let body = format!(
+            "The device recovered by resetting after {}s of uptime.\nLocation: {:04x}:{}, last \
+             command: {last_command}, reset cause: {:?}.",
+            report.uptime_s, report.location, report.line, report.reset_cause
+        );
+
+        notification::notify("Cooler firmware crashed", &body);
+    }
 }
 
 struct AppIndicator(LibAppIndicator);
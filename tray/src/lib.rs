@@ -1,16 +1,28 @@
 #![doc = include_str!("../README.md")]
 
 mod device;
+mod dispatcher;
+mod event;
 mod exactly_one;
 mod fd_callbacks;
+mod flash;
+mod gesture;
+mod heartbeat;
 mod indicator;
 mod menu;
+mod notification;
 
 pub use anyhow::Result as AnyResult;
 pub use device::Device;
+pub use dispatcher::{Outcome, Priority};
+pub use event::{ChangeSource, DeviceEvent, DeviceEventStream};
+pub use flash::flash;
 use futures_util::TryFutureExt;
+pub use gesture::{GestureAction, Gestures};
 use gtk::glib::{self, JoinHandle};
+pub use heartbeat::Heartbeat;
 pub use indicator::Indicator;
+pub use menu::item::{AutoMode, AutoSettings};
 
 /// Spawns a fallible future on the event loop, quiting it by calling [`gtk::main_quit`] if the
 /// future returns an error.
@@ -20,3 +32,9 @@ where
 {
     glib::spawn_future_local(fut.inspect_err(|_| gtk::main_quit()))
 }
+
+/// Runs a future to completion on the default main context, which also drives the USB transfers.
+/// Used by the command line subcommands, which do not run the event loop.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    glib::MainContext::default().block_on(fut)
+}
@@ -1,18 +1,165 @@
 //! System tray for the `CoolerThanYou` device.
 #![doc = include_str!("../README.md")]
 
-use anyhow::anyhow;
-use clap::{Parser, builder::ValueParser};
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, ensure};
+use clap::{Parser, Subcommand, builder::ValueParser};
+use shared::{ButtonEvent, FanSpeed, Odometer};
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
-use tray::{AnyResult, Device, Indicator};
+use tray::{
+    AnyResult, AutoMode, AutoSettings, Device, GestureAction, Gestures, Heartbeat, Indicator,
+    Outcome,
+};
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None, bin_name = "cooler-than-you")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Comma-separated list of temperatures for the fan curve
     #[arg(default_value = "60,65,70,75,80")]
     #[arg(value_parser = ValueParser::new(Opts::parse_fan_curve))]
     fan_curve: [f32; 5],
+    /// Degrees Celsius the temperature must drop below a fan curve threshold before the device
+    /// lowers the fan speed. Only used with `--auto-mode device`
+    #[arg(long, default_value_t = 2)]
+    hysteresis: u8,
+    /// Where the fan curve gets evaluated while the automatic fan speed adjustment is active
+    #[arg(long, value_enum, default_value_t = AutoMode::Host)]
+    auto_mode: AutoMode,
+    /// Also drive the automatic fan speed adjustment with the temperature probe of the device,
+    /// offset by this many degrees Celsius, whichever of it and the CPU temperature is hotter.
+    /// Requires a firmware built with the `temp-probe` feature
+    #[arg(long, value_name = "DEGREES")]
+    probe_offset: Option<u8>,
+    /// Seconds the automatic fan speed adjustment stays paused after the fan speed got changed on
+    /// the cooler, 0 for not pausing it
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    manual_hold: u16,
+    /// Maps a button gesture to an action, can be used multiple times.
+    ///
+    /// Gestures: power-long-press, power-double-press, leds-double-press, speed-up-double-press,
+    /// speed-down-double-press, speed-chord.
+    ///
+    /// Actions: none, toggle-speed-auto, boost, enter-bootloader.
+    #[arg(long = "gesture", value_name = "GESTURE=ACTION")]
+    #[arg(value_parser = ValueParser::new(Gestures::parse_mapping))]
+    gestures: Vec<(ButtonEvent, GestureAction)>,
+    /// Send heartbeats to the device, which drives the cooler to the fail-safe fan speed if they
+    /// stop for longer than this many seconds while the host is not suspended, at least 3
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(value_parser = clap::value_parser!(u8).range(3..))]
+    heartbeat_timeout: Option<u8>,
+    /// Fan speed the device drives the cooler to when the heartbeats stop
+    #[arg(long, default_value_t = 6, requires = "heartbeat_timeout")]
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=6))]
+    failsafe_speed: u8,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Put the device in bootloader mode, ready to be flashed. The tray must not be running.
+    Bootloader {
+        /// Make the next long press on the power button enter bootloader mode instead
+        #[arg(long)]
+        arm: bool,
+    },
+    /// Measure the fan voltage at every fan speed and persist it on the device as the calibration
+    /// of the fan speed sensing. Requires a firmware built with the `fan-sense` feature and the
+    /// cooler to be powered on. The tray must not be running.
+    CalibrateFanSense,
+    /// Print the lifetime usage counters of the device, also shown by the `Usage counters` menu
+    /// item. The tray must not be running.
+    Odometer {
+        /// Reset the counters to zero instead
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Print whether a power button press on the cooler wakes up the suspended host, as also set by
+    /// the `Wake up on power press` menu item. The tray must not be running.
+    RemoteWakeup {
+        /// Persist on the device that a power button press wakes up the host
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+        /// Persist on the device that a power button press does not wake up the host
+        #[arg(long)]
+        disable: bool,
+    },
+    /// Flash the firmware from an Intel HEX file, then print the version the device reports. The
+    /// tray must not be running.
+    Flash {
+        /// Firmware file, e.g. converted from the device ELF with `avr-objcopy -O ihex`
+        firmware: PathBuf,
+    },
+}
+
+impl Command {
+    fn run(self) -> AnyResult<()> {
+        match self {
+            Self::Bootloader { arm: true } => {
+                let outcome = tray::block_on(Device::new()?.arm_bootloader())?;
+                ensure!(outcome == Outcome::Sent, "the device got disconnected");
+                Ok(())
+            }
+            Self::Bootloader { arm: false } => {
+                let device = Device::new()?;
+                let mut reports = device.report_stream()?;
+                tray::block_on(device.enter_bootloader(&mut reports))
+            }
+            Self::CalibrateFanSense => {
+                let device = Device::new()?;
+                let mut reports = device.report_stream()?;
+                let calibration = tray::block_on(device.calibrate_fan_sense(&mut reports))?;
+                println!(
+                    "Fan voltage readings, from the lowest to the highest speed: {:?}",
+                    calibration.levels
+                );
+                Ok(())
+            }
+            Self::Odometer { reset: true } => {
+                let outcome = tray::block_on(Device::new()?.reset_odometer())?;
+                ensure!(outcome == Outcome::Sent, "the device got disconnected");
+                Ok(())
+            }
+            Self::Odometer { reset: false } => {
+                let device = Device::new()?;
+                let mut reports = device.report_stream()?;
+                let odometer = tray::block_on(device.read_odometer(&mut reports))?;
+                Self::print_odometer(&odometer);
+                Ok(())
+            }
+            Self::RemoteWakeup { enable, disable } => {
+                let device = Device::new()?;
+                let mut reports = device.report_stream()?;
+                let enabled = (enable || disable).then_some(enable);
+                let enabled = tray::block_on(device.remote_wakeup(&mut reports, enabled))?;
+                println!(
+                    "Remote wakeup: {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+                Ok(())
+            }
+            Self::Flash { firmware } => tray::flash(&firmware),
+        }
+    }
+
+    fn print_odometer(odometer: &Odometer) {
+        let hours = |seconds: u32| f64::from(seconds) / 3600.0;
+
+        println!("Powered on: {:.1}h", hours(odometer.powered_on_s));
+
+        for (speed, seconds) in (1..).zip(odometer.fan_speed_s) {
+            println!("  at fan speed {speed}: {:.1}h", hours(seconds));
+        }
+
+        println!("LEDs on: {:.1}h", hours(odometer.leds_on_s));
+        println!("Physical presses: {}", odometer.physical_presses);
+        println!("Emulated presses: {}", odometer.emulated_presses);
+        println!("Suspends: {}", odometer.suspends);
+        println!("Rejected glitches: {}", odometer.rejected_glitches);
+    }
 }
 
 impl Opts {
@@ -26,14 +173,44 @@ impl Opts {
 }
 
 fn main() -> AnyResult<()> {
-    let Opts { fan_curve } = Opts::parse();
+    let Opts {
+        command,
+        fan_curve,
+        hysteresis,
+        auto_mode,
+        probe_offset,
+        manual_hold,
+        gestures,
+        heartbeat_timeout,
+        failsafe_speed,
+    } = Opts::parse();
 
     let journald_layer = tracing_journald::Layer::new()?
         .with_syslog_identifier("cooler-than-you".to_owned())
         .with_filter(EnvFilter::from_default_env());
     tracing_subscriber::registry().with(journald_layer).init();
 
-    Indicator::new(fan_curve)?.run(Device::new()?);
+    if let Some(command) = command {
+        return command.run();
+    }
+
+    let auto_settings = AutoSettings {
+        fan_curve,
+        hysteresis,
+        auto_mode,
+        probe_offset,
+        manual_hold: Duration::from_secs(manual_hold.into()),
+    };
+
+    if auto_mode == AutoMode::Device {
+        // Fail right away rather than in the speed auto task.
+        auto_settings.device_fan_curve()?;
+    }
+
+    let failsafe_speed = FanSpeed::try_from(failsafe_speed)?;
+    let heartbeat = heartbeat_timeout.map(|timeout_s| Heartbeat::new(timeout_s, failsafe_speed));
+
+    Indicator::new(auto_settings, Gestures::new(gestures), heartbeat)?.run(Device::new()?);
 
     Ok(())
 }
@@ -0,0 +1,210 @@
+use std::ops::RangeInclusive;
+
+use gtk::{
+    Align, Dialog, DialogFlags, Grid, Label, MenuItem, ResponseType, SpinButton, Window, glib,
+    traits::{
+        ContainerExt, DialogExt, GridExt, GtkMenuItemExt, GtkWindowExt, SpinButtonExt, WidgetExt,
+    },
+};
+use shared::PressTimings;
+
+use crate::{AnyResult, Device, menu::item::CustomMenuItem, notification};
+
+/// Actionable item that opens the advanced settings dialog when clicked, for tuning the
+/// [`PressTimings`] of the device.
+///
+/// The timings get read from and written to the feature report of the device, see
+/// [`Device::press_timings`].
+pub type AdvancedItem = CustomMenuItem<MenuItem, Advanced>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Advanced;
+
+impl AdvancedItem {
+    pub fn new(device: Device) -> Self {
+        let inner = MenuItem::with_label("Advanced settings");
+
+        inner.connect_activate(move |_| {
+            let device = device.clone();
+
+            glib::spawn_future_local(async move {
+                match device.press_timings().await {
+                    Ok(press_timings) => AdvancedDialog::show(device, press_timings),
+                    Err(e) => {
+                        notification::notify("Could not read the press timings", &format!("{e:#}"));
+                    }
+                }
+            });
+        });
+
+        Self {
+            inner,
+            kind: Advanced,
+        }
+    }
+}
+
+/// Dialog with a spin button for every press timing, ranged as the device accepts them.
+struct AdvancedDialog {
+    short_press: SpinButton,
+    long_press: SpinButton,
+    post_press_delay: SpinButton,
+    monitor_short_press: SpinButton,
+    monitor_long_press: SpinButton,
+    sub_threshold_dropout: SpinButton,
+    within_press_dropout: SpinButton,
+}
+
+impl AdvancedDialog {
+    /// Response of the button restoring the default timings, which keeps the dialog open.
+    const RESTORE_DEFAULTS: u16 = 1;
+
+    fn show(device: Device, press_timings: PressTimings) {
+        let dialog = Dialog::with_buttons(
+            Some("Advanced settings"),
+            None::<&Window>,
+            DialogFlags::empty(),
+            &[
+                (
+                    "Restore defaults",
+                    ResponseType::Other(Self::RESTORE_DEFAULTS),
+                ),
+                ("Cancel", ResponseType::Cancel),
+                ("Apply", ResponseType::Apply),
+            ],
+        );
+
+        let grid = Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_border_width(12);
+
+        let spin_button = |row, label, range| Self::spin_button(&grid, row, label, range);
+
+        let form = Self {
+            short_press: spin_button(
+                0,
+                "Short press (ms)",
+                to_f64(PressTimings::SHORT_PRESS_RANGE),
+            ),
+            long_press: spin_button(1, "Long press (ms)", to_f64(PressTimings::LONG_PRESS_RANGE)),
+            post_press_delay: spin_button(
+                2,
+                "Delay after a press (ms)",
+                to_f64(PressTimings::POST_PRESS_DELAY_RANGE),
+            ),
+            monitor_short_press: spin_button(
+                3,
+                "Monitored short press (ms)",
+                to_f64(PressTimings::MONITOR_SHORT_PRESS_RANGE),
+            ),
+            monitor_long_press: spin_button(
+                4,
+                "Monitored long press (ms)",
+                to_f64(PressTimings::MONITOR_LONG_PRESS_RANGE),
+            ),
+            sub_threshold_dropout: spin_button(
+                5,
+                "Dropout tolerated before a short press (ms)",
+                to_f64(PressTimings::DROPOUT_RANGE),
+            ),
+            within_press_dropout: spin_button(
+                6,
+                "Dropout tolerated within a press (ms)",
+                to_f64(PressTimings::DROPOUT_RANGE),
+            ),
+        };
+
+        form.set(press_timings);
+        dialog.content_area().add(&grid);
+        dialog.set_default_response(ResponseType::Apply);
+
+        dialog.connect_response(move |dialog, response| match response {
+            ResponseType::Other(Self::RESTORE_DEFAULTS) => form.set(PressTimings::DEFAULT),
+            ResponseType::Apply => match form.get() {
+                Ok(press_timings) => {
+                    // Kept from being applied twice while the transfer is ongoing.
+                    dialog.set_sensitive(false);
+                    let device = device.clone();
+                    let dialog = dialog.clone();
+
+                    glib::spawn_future_local(async move {
+                        match device.set_press_timings(press_timings).await {
+                            Ok(()) => {
+                                tracing::info!("device applied press timings: {press_timings:?}");
+                                dialog.close();
+                            }
+                            Err(e) => {
+                                dialog.set_sensitive(true);
+                                notification::notify(
+                                    "Could not apply the press timings",
+                                    &format!("{e:#}"),
+                                );
+                            }
+                        }
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("invalid press timings: {e}");
+                    notification::notify("Invalid press timings", &e.to_string());
+                }
+            },
+            _ => dialog.close(),
+        });
+
+        dialog.show_all();
+    }
+
+    /// Adds a labeled spin button to the given row of the grid.
+    fn spin_button(grid: &Grid, row: i32, label: &str, range: RangeInclusive<f64>) -> SpinButton {
+        let label = Label::new(Some(label));
+        label.set_halign(Align::Start);
+        let spin_button = SpinButton::with_range(*range.start(), *range.end(), 1.0);
+
+        grid.attach(&label, 0, row, 1, 1);
+        grid.attach(&spin_button, 1, row, 1, 1);
+        spin_button
+    }
+
+    /// Fills the spin buttons in with the given timings.
+    fn set(&self, press_timings: PressTimings) {
+        self.short_press
+            .set_value(press_timings.short_press_ms.into());
+        self.long_press
+            .set_value(press_timings.long_press_ms.into());
+        self.post_press_delay
+            .set_value(press_timings.post_press_delay_ms.into());
+        self.monitor_short_press
+            .set_value(press_timings.monitor_short_press_ms.into());
+        self.monitor_long_press
+            .set_value(press_timings.monitor_long_press_ms.into());
+        self.sub_threshold_dropout
+            .set_value(press_timings.sub_threshold_dropout_ms.into());
+        self.within_press_dropout
+            .set_value(press_timings.within_press_dropout_ms.into());
+    }
+
+    /// Returns the timings filled in, checked the same as the device does.
+    fn get(&self) -> AnyResult<PressTimings> {
+        let press_timings = PressTimings {
+            short_press_ms: self.short_press.value_as_int().try_into()?,
+            long_press_ms: self.long_press.value_as_int().try_into()?,
+            post_press_delay_ms: self.post_press_delay.value_as_int().try_into()?,
+            monitor_short_press_ms: self.monitor_short_press.value_as_int().try_into()?,
+            monitor_long_press_ms: self.monitor_long_press.value_as_int().try_into()?,
+            sub_threshold_dropout_ms: self.sub_threshold_dropout.value_as_int().try_into()?,
+            within_press_dropout_ms: self.within_press_dropout.value_as_int().try_into()?,
+        };
+
+        press_timings.validate()?;
+        Ok(press_timings)
+    }
+}
+
+/// Converts a range of timings to the range of a spin button.
+fn to_f64<T>(range: RangeInclusive<T>) -> RangeInclusive<f64>
+where
+    T: Into<f64> + Copy,
+{
+    (*range.start()).into()..=(*range.end()).into()
+}
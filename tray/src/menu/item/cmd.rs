@@ -22,10 +22,6 @@ pub type SpeedUpItem = CustomMenuItem<MenuItem, SpeedUp>;
 /// Disabled when [`crate::menu::item::SpeedAutoItem`] is active or when [`PowerToggleItem`] is
 /// inactive.
 pub type SpeedDownItem = CustomMenuItem<MenuItem, SpeedDown>;
-/// Actionable item that sends a [`DeviceCommand::LedsColorChange`] when clicked.
-///
-/// Disabled when [`LedsToggleItem`] is inactive.
-pub type LedsChangeColorItem = CustomMenuItem<MenuItem, LedsChangeColor>;
 /// Actionable checkbox item that sends a [`DeviceCommand::LedsOn`]/[`DeviceCommand::LedsOff`] if
 /// checked/unchecked, respectively.
 pub type LedsToggleItem = CustomMenuItem<CheckMenuItem, LedsToggle>;
@@ -60,15 +56,6 @@ impl CommandItemKind for SpeedDown {
     const THIS: Self = Self;
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct LedsChangeColor;
-
-impl CommandItemKind for LedsChangeColor {
-    const LABEL: &str = "Change lights color";
-    const COMMAND: DeviceCommand = DeviceCommand::LedsColorChange;
-    const THIS: Self = Self;
-}
-
 impl<MI, K> CustomMenuItem<MI, K>
 where
     K: CommandItemKind,
@@ -0,0 +1,100 @@
+use std::{
+    cell::OnceCell,
+    rc::{Rc, Weak},
+};
+
+use gtk::{
+    Menu, MenuItem, RadioMenuItem,
+    glib::{ObjectExt, SignalHandlerId},
+    traits::{CheckMenuItemExt, GtkMenuItemExt, MenuShellExt, RadioMenuItemExt},
+};
+use shared::LedColor;
+
+use crate::{
+    Device,
+    menu::{MenuItems, item::CustomMenuItem},
+};
+
+/// Item with a submenu of radio items, one for every [`LedColor`], that makes the device cycle the
+/// LEDs to the color clicked. The active radio item follows the color tracked by the device.
+///
+/// Disabled when [`crate::menu::item::LedsToggleItem`] is inactive.
+pub type LedsColorItem = CustomMenuItem<MenuItem, LedsColor>;
+
+/// The radio items, in palette order, along with their callback [`SignalHandlerId`].
+#[derive(Debug)]
+pub struct LedsColor(Vec<(RadioMenuItem, SignalHandlerId)>);
+
+impl LedsColorItem {
+    pub fn new(menu_items: Weak<MenuItems>, device: Device) -> Self {
+        let inner = MenuItem::with_label("Lights color");
+        let submenu = Menu::new();
+        // Shared by all the radio items callbacks.
+        let cache = Rc::new(OnceCell::new());
+        let mut radio_items: Vec<(RadioMenuItem, SignalHandlerId)> = Vec::new();
+
+        for led_color in LedColor::ALL {
+            let radio_item = RadioMenuItem::default();
+            radio_item.set_label(label(led_color));
+            radio_item.join_group(radio_items.first().map(|(first, _)| first));
+            submenu.append(&radio_item);
+
+            let menu_items = menu_items.clone();
+            let device = device.clone();
+            let cache = cache.clone();
+
+            let signal_handler_id = radio_item.connect_activate(move |mi| {
+                // The radio item getting deactivated gets activated as well.
+                if !mi.is_active() {
+                    return;
+                }
+
+                // Cache the weak pointer upgrade so as not to do it every time.
+                let cache_fn = || menu_items.upgrade().expect("menu items are never dropped");
+                cache.get_or_init(cache_fn).disable();
+
+                let device = device.clone();
+                crate::spawn_local(async move { device.set_led_color(led_color).await });
+            });
+
+            radio_items.push((radio_item, signal_handler_id));
+        }
+
+        inner.set_submenu(Some(&submenu));
+
+        Self {
+            inner,
+            kind: LedsColor(radio_items),
+        }
+    }
+
+    /// Activates the radio item of the given color, without triggering its callback.
+    pub fn set_color(&self, led_color: LedColor) {
+        let (radio_item, _) = &self.kind.0[usize::from(u8::from(led_color))];
+
+        // We must block the callback signals before tweaking the state to avoid unwantedly
+        // triggering them, including the one of the radio item getting deactivated.
+        for (radio_item, signal_handler_id) in &self.kind.0 {
+            radio_item.block_signal(signal_handler_id);
+        }
+
+        radio_item.set_active(true);
+
+        for (radio_item, signal_handler_id) in &self.kind.0 {
+            radio_item.unblock_signal(signal_handler_id);
+        }
+    }
+}
+
+/// Returns the name of the color, as shown in the menu.
+fn label(led_color: LedColor) -> &'static str {
+    match led_color {
+        LedColor::Red => "Red",
+        LedColor::Green => "Green",
+        LedColor::Blue => "Blue",
+        LedColor::Yellow => "Yellow",
+        LedColor::Cyan => "Cyan",
+        LedColor::Purple => "Purple",
+        LedColor::White => "White",
+    }
+}
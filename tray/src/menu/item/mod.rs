@@ -1,16 +1,28 @@
+mod advanced;
 mod cmd;
+mod leds_color;
+mod odometer;
+mod probe_label;
 mod quit;
+mod remote_wakeup;
+mod resync;
 mod speed_auto;
 mod speed_label;
 
-pub use cmd::{LedsChangeColorItem, LedsToggleItem, PowerToggleItem, SpeedDownItem, SpeedUpItem};
+pub use advanced::AdvancedItem;
+pub use cmd::{LedsToggleItem, PowerToggleItem, SpeedDownItem, SpeedUpItem};
 use gtk::{
     CheckMenuItem,
     glib::{ObjectExt, SignalHandlerId},
     traits::{CheckMenuItemExt, WidgetExt},
 };
+pub use leds_color::LedsColorItem;
+pub use odometer::OdometerItem;
+pub use probe_label::ProbeLabelItem;
 pub use quit::QuitItem;
-pub use speed_auto::SpeedAutoItem;
+pub use remote_wakeup::RemoteWakeupItem;
+pub use resync::ResyncItem;
+pub use speed_auto::{AutoMode, AutoSettings, SpeedAutoItem};
 pub use speed_label::SpeedLabelItem;
 
 /// A custom menu item that wraps a `gtk` menu item and further specializes its behavior based on
@@ -0,0 +1,154 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gtk::{
+    Align, Dialog, DialogFlags, Grid, Label, MenuItem, ResponseType, Window, glib,
+    traits::{ContainerExt, DialogExt, GridExt, GtkMenuItemExt, GtkWindowExt, WidgetExt},
+};
+use shared::{HostReport, Odometer, OdometerChunks};
+
+use crate::{AnyResult, Device, menu::item::CustomMenuItem, notification};
+
+/// Actionable item that asks the device for its lifetime usage counters when clicked and shows
+/// them in a dialog, from which they can be reset.
+///
+/// The counters come in chunks through the main background task, see
+/// [`OdometerItem::register_chunk`].
+pub type OdometerItem = CustomMenuItem<MenuItem, UsageCounters>;
+
+/// The device the counters are read from and the chunks received so far, while they are read.
+#[derive(Clone, Debug)]
+pub struct UsageCounters {
+    device: Device,
+    chunks: Rc<RefCell<Option<OdometerChunks>>>,
+}
+
+impl OdometerItem {
+    /// How long to wait for the counters, which the device sends right away.
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn new(device: Device) -> Self {
+        let inner = MenuItem::with_label("Usage counters");
+        let kind = UsageCounters {
+            device,
+            chunks: Rc::default(),
+        };
+
+        let UsageCounters { device, chunks } = kind.clone();
+
+        inner.connect_activate(move |_| {
+            chunks.replace(Some(OdometerChunks::new()));
+
+            let device = device.clone();
+            let chunks = chunks.clone();
+
+            crate::spawn_local(async move {
+                device.send_report(HostReport::ReadOdometer).await?;
+                glib::timeout_future(Self::TIMEOUT).await;
+
+                // Still waiting for the chunks.
+                if chunks.take().is_some() {
+                    notification::notify(
+                        "Could not read the usage counters",
+                        "The device did not report them, check that its firmware is up to date.",
+                    );
+                }
+
+                AnyResult::Ok(())
+            });
+        });
+
+        Self { inner, kind }
+    }
+
+    /// Registers an odometer chunk sent by the device, showing the counters once all the chunks
+    /// were received. Chunks that were not asked for get ignored.
+    pub fn register_chunk(&self, index: u8, chunk: [u8; Odometer::CHUNK_LEN]) {
+        let mut chunks = self.kind.chunks.borrow_mut();
+
+        let Some(odometer) = chunks
+            .as_mut()
+            .and_then(|chunks| chunks.insert(index, chunk))
+        else {
+            return;
+        };
+
+        *chunks = None;
+        drop(chunks);
+
+        tracing::info!("received odometer: {odometer:?}");
+        OdometerDialog::show(self.kind.device.clone(), &odometer);
+    }
+}
+
+/// Dialog listing the usage counters, with a button resetting them.
+struct OdometerDialog;
+
+impl OdometerDialog {
+    /// Response of the button resetting the counters.
+    const RESET: u16 = 1;
+
+    fn show(device: Device, odometer: &Odometer) {
+        let dialog = Dialog::with_buttons(
+            Some("Usage counters"),
+            None::<&Window>,
+            DialogFlags::empty(),
+            &[
+                ("Reset", ResponseType::Other(Self::RESET)),
+                ("Close", ResponseType::Close),
+            ],
+        );
+
+        let grid = Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_border_width(12);
+
+        let hours = |seconds: u32| format!("{:.1}h", f64::from(seconds) / 3600.0);
+        let mut rows = vec![("Powered on".to_owned(), hours(odometer.powered_on_s))];
+
+        for (speed, seconds) in (1..).zip(odometer.fan_speed_s) {
+            rows.push((format!("  at fan speed {speed}"), hours(seconds)));
+        }
+
+        rows.extend([
+            ("LEDs on".to_owned(), hours(odometer.leds_on_s)),
+            (
+                "Physical presses".to_owned(),
+                odometer.physical_presses.to_string(),
+            ),
+            (
+                "Emulated presses".to_owned(),
+                odometer.emulated_presses.to_string(),
+            ),
+            ("Suspends".to_owned(), odometer.suspends.to_string()),
+            (
+                "Rejected glitches".to_owned(),
+                odometer.rejected_glitches.to_string(),
+            ),
+        ]);
+
+        for (row, (name, value)) in (0..).zip(rows) {
+            let name = Label::new(Some(&name));
+            name.set_halign(Align::Start);
+            let value = Label::new(Some(&value));
+            value.set_halign(Align::End);
+
+            grid.attach(&name, 0, row, 1, 1);
+            grid.attach(&value, 1, row, 1, 1);
+        }
+
+        dialog.content_area().add(&grid);
+        dialog.set_default_response(ResponseType::Close);
+
+        dialog.connect_response(move |dialog, response| {
+            if response == ResponseType::Other(Self::RESET) {
+                let device = device.clone();
+                crate::spawn_local(async move { device.reset_odometer().await });
+            }
+
+            dialog.close();
+        });
+
+        dialog.show_all();
+    }
+}
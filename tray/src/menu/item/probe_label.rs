@@ -0,0 +1,39 @@
+use gtk::{
+    MenuItem,
+    traits::{GtkMenuItemExt, WidgetExt},
+};
+
+use crate::menu::item::CustomMenuItem;
+
+/// Non-actionable item that displays the temperature measured by the probe of the device. This
+/// item is purely meant for display and is never UI sensitive. It stays hidden until the device
+/// reports a first reading, as only firmwares built with the `temp-probe` feature have a probe.
+pub type ProbeLabelItem = CustomMenuItem<MenuItem, ProbeLabel>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProbeLabel;
+
+impl ProbeLabelItem {
+    pub fn update_label(&self, temp_c: Option<u8>) {
+        let label = match temp_c {
+            Some(temp_c) => format!("Cooler probe: {temp_c}°C"),
+            None => "Cooler probe: N/A".to_owned(),
+        };
+
+        self.inner.set_label(&label);
+        self.inner.set_visible(true);
+    }
+}
+
+impl Default for ProbeLabelItem {
+    fn default() -> Self {
+        let inner = MenuItem::with_label("Cooler probe: N/A");
+        inner.set_sensitive(false);
+        // Keeps the item hidden when the whole menu is shown.
+        inner.set_no_show_all(true);
+        Self {
+            inner,
+            kind: ProbeLabel,
+        }
+    }
+}
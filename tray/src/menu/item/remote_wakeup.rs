@@ -0,0 +1,49 @@
+use gtk::{
+    CheckMenuItem,
+    glib::SignalHandlerId,
+    traits::{CheckMenuItemExt, GtkMenuItemExt, WidgetExt},
+};
+use shared::HostReport;
+
+use crate::{Device, menu::item::CustomMenuItem};
+
+/// Actionable checkbox item that makes the device persist whether a power button press on the
+/// cooler wakes up the suspended host, if checked/unchecked, respectively.
+///
+/// The item follows the setting the device reports, which gets read whenever the device connects,
+/// and stays disabled until then, e.g. for firmwares that predate the setting.
+pub type RemoteWakeupItem = CustomMenuItem<CheckMenuItem, RemoteWakeup>;
+
+#[derive(Debug)]
+pub struct RemoteWakeup(SignalHandlerId);
+
+impl RemoteWakeupItem {
+    pub fn new(device: Device) -> Self {
+        let inner = CheckMenuItem::with_label("Wake up on power press");
+        inner.set_sensitive(false);
+
+        let signal_handler_id = inner.connect_activate(move |mi| {
+            let report = HostReport::RemoteWakeup(mi.is_active());
+            let device = device.clone();
+            crate::spawn_local(async move { device.send_report(report).await });
+        });
+
+        Self {
+            inner,
+            kind: RemoteWakeup(signal_handler_id),
+        }
+    }
+
+    /// Checks the item according to the setting reported by the device, without triggering its
+    /// callback, and enables it.
+    pub fn register_setting(&self, enabled: bool) {
+        self.set_active(enabled);
+        self.set_sensitive(true);
+    }
+}
+
+impl AsRef<SignalHandlerId> for RemoteWakeup {
+    fn as_ref(&self) -> &SignalHandlerId {
+        &self.0
+    }
+}
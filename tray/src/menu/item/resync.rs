@@ -0,0 +1,43 @@
+use std::{cell::OnceCell, rc::Weak};
+
+use gtk::{MenuItem, traits::GtkMenuItemExt};
+
+use crate::{
+    Device,
+    menu::{MenuItems, item::CustomMenuItem},
+};
+
+/// Actionable item that makes the device re-home the cooler when clicked, keeping the power and
+/// LEDs state of [`crate::menu::item::PowerToggleItem`] and [`crate::menu::item::LedsToggleItem`].
+///
+/// Meant for fixing a drift of the tracked state, e.g. while it is uncertain, without unplugging
+/// the cooler.
+pub type ResyncItem = CustomMenuItem<MenuItem, Resync>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Resync;
+
+impl ResyncItem {
+    pub fn new(menu_items: Weak<MenuItems>, device: Device) -> Self {
+        let inner = MenuItem::with_label("Resync");
+        let cache = OnceCell::new();
+
+        inner.connect_activate(move |_| {
+            // Cache the weak pointer upgrade so as not to do it every time.
+            let cache_fn = || menu_items.upgrade().expect("menu items are never dropped");
+            let menu_items = cache.get_or_init(cache_fn);
+            menu_items.disable();
+
+            let power_enabled = menu_items.power.is_active();
+            let leds_enabled = menu_items.leds.is_active();
+
+            let device = device.clone();
+            crate::spawn_local(async move { device.resync(power_enabled, leds_enabled).await });
+        });
+
+        Self {
+            inner,
+            kind: Resync,
+        }
+    }
+}
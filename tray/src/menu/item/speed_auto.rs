@@ -1,44 +1,169 @@
 use std::{
-    cell::{Cell, OnceCell},
+    cell::{Cell, OnceCell, RefCell},
+    cmp::Ordering,
+    future,
+    pin::pin,
     rc::{Rc, Weak},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
 };
 
-use futures_util::StreamExt;
+use anyhow::bail;
+use clap::ValueEnum;
+use futures_util::{
+    StreamExt,
+    future::{Either, select},
+};
 use gtk::{
     CheckMenuItem,
     glib::{self, JoinHandle},
     traits::{CheckMenuItemExt, GtkMenuItemExt},
 };
-use shared::{DeviceCommand, FanSpeed};
+use shared::{DeviceCommand, DeviceState, FanCurve, FanSpeed, HostReport, PressTimings};
 use systemstat::{Platform, System};
 use tracing::instrument;
 
 use crate::{
-    AnyResult, Device,
+    AnyResult, Device, Outcome, Priority,
     menu::{MenuItems, item::CustomMenuItem},
 };
 
 /// Actionable checkbox item that enables/disables the fan speed auto adjustment based on
 /// temperature. This item is already active on start-up.
+///
+/// With [`AutoMode::Host`], the task works out the target fan speed from the fan curve, see
+/// [`AutoSettings::fan_curve`], and steps towards it, waiting for the device to confirm each step.
+/// It only ever acts on the state the device last confirmed, so nothing is sent until the device
+/// reported its state and after a step that is not confirmed in time, which also gets the device to
+/// resync. How long a step gets to be confirmed follows the press timings of the device, see
+/// [`step_timeout`].
+///
+/// A fan speed change that the tray did not cause, e.g. a speed button pressed on the cooler,
+/// pauses the adjustment for [`AutoSettings::manual_hold`], which the item label counts down.
 pub type SpeedAutoItem = CustomMenuItem<CheckMenuItem, SpeedAuto>;
 
 #[derive(Clone, Debug)]
-pub struct SpeedAuto(Rc<Cell<FanSpeed>>);
+pub struct SpeedAuto {
+    state: Rc<AutoState>,
+    settings: AutoSettings,
+}
+
+/// Where the fan curve gets evaluated while the fan speed auto adjustment is active.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum AutoMode {
+    /// The tray picks the fan speed and sends the speed commands.
+    Host,
+    /// The tray sends the fan curve and the temperatures, and the device picks the fan speed.
+    Device,
+}
+
+/// Settings of the fan speed auto adjustment.
+#[derive(Clone, Copy, Debug)]
+pub struct AutoSettings {
+    /// Temperature thresholds, one for each speed step. The fan speed goes up a step once the
+    /// temperature is over the threshold of the current speed and down a step once it is back
+    /// below the threshold it went over.
+    pub fan_curve: [f32; 5],
+    /// Degrees Celsius the temperature must drop below a threshold before the device lowers the
+    /// fan speed. Only applies to [`AutoMode::Device`].
+    pub hysteresis: u8,
+    pub auto_mode: AutoMode,
+    /// The probe temperature is only taken into account if an offset was provided.
+    pub probe_offset: Option<u8>,
+    /// How long the adjustment stays paused after a fan speed change the tray did not cause. Only
+    /// applies to [`AutoMode::Host`], as the tray cannot tell the changes made on the cooler apart
+    /// from the ones the device picks in [`AutoMode::Device`].
+    pub manual_hold: Duration,
+}
+
+impl AutoSettings {
+    /// Returns the fan curve for the device to evaluate, which works in whole degrees Celsius.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a threshold is not a whole number of degrees that fits a [`u8`] or if
+    /// the thresholds are not in ascending order.
+    pub fn device_fan_curve(&self) -> AnyResult<FanCurve> {
+        let mut thresholds = [0; 5];
+
+        for (threshold, temp) in thresholds.iter_mut().zip(self.fan_curve) {
+            *threshold = SpeedAutoItem::whole_degrees(temp);
+
+            if (f32::from(*threshold) - temp).abs() > f32::EPSILON {
+                bail!("the device fan curve requires whole degrees from 0 to 255, got {temp}");
+            }
+        }
+
+        Ok(FanCurve::new(thresholds, self.hysteresis)?)
+    }
+}
+
+/// State shared between the speed auto task and the main background task.
+///
+/// Cells are used because of the `gtk` callbacks trait bounds, as the task gets respawned from the
+/// item callback.
+#[derive(Debug, Default)]
+struct AutoState {
+    /// The state last confirmed by the device, `None` until the first one or while a resync is
+    /// pending.
+    device_state: Cell<Option<DeviceState>>,
+    /// Wakes the task up when the device confirms its state.
+    waker: RefCell<Option<Waker>>,
+    probe_temp_c: Cell<Option<u8>>,
+    /// Until when the adjustment is paused after a fan speed change the tray did not cause.
+    paused_until: Cell<Option<Instant>>,
+}
+
+impl AutoState {
+    /// Returns how long the adjustment stays paused for, clearing the pause once it is over.
+    fn paused_for(&self) -> Option<Duration> {
+        let remaining = self
+            .paused_until
+            .get()?
+            .checked_duration_since(Instant::now());
+
+        if remaining.is_none() {
+            self.paused_until.set(None);
+        }
+
+        remaining
+    }
+
+    /// Waits for the device to confirm a state the predicate holds for.
+    async fn confirmed<F>(&self, f: F) -> DeviceState
+    where
+        F: Fn(DeviceState) -> bool,
+    {
+        future::poll_fn(|cx| match self.device_state.get() {
+            Some(device_state) if f(device_state) => Poll::Ready(device_state),
+            _ => {
+                *self.waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
 
 impl SpeedAutoItem {
+    const LABEL: &str = "Auto fan speed";
+
     // NOTE: Used this name to be consistent with the other checkbox items
     //       construction method.
-    pub fn new_checkbox(menu_items: Weak<MenuItems>, device: Device, fan_curve: [f32; 5]) -> Self {
-        // This will self-adjust, we just start with the lowest speed.
-        // An [`Rc<Cell<FanSpeed>>`] is used here to share the value between the speed auto task and
-        // the main background task because of `gtk` callbacks trait bounds and because [`FanSpeed`]
-        // is [`Copy`].
-        let fan_speed = Rc::new(Cell::new(FanSpeed::Speed1));
-        let kind = SpeedAuto(fan_speed.clone());
-
-        let inner = CheckMenuItem::with_label("Auto fan speed");
+    pub fn new_checkbox(
+        menu_items: Weak<MenuItems>,
+        device: Device,
+        settings: AutoSettings,
+    ) -> Self {
+        let state = Rc::new(AutoState::default());
+        let kind = SpeedAuto {
+            state: state.clone(),
+            settings,
+        };
+
+        let inner = CheckMenuItem::with_label(Self::LABEL);
         inner.set_active(true);
-        let fut = Self::speed_auto_task(device.clone(), fan_speed.clone(), fan_curve);
+        let fut = Self::speed_auto_task(device.clone(), inner.clone(), state.clone(), settings);
         let join_handle: Cell<Option<JoinHandle<_>>> = Cell::new(Some(crate::spawn_local(fut)));
         let cache = OnceCell::new();
 
@@ -47,11 +172,15 @@ impl SpeedAutoItem {
                 Some(h) => {
                     tracing::debug!("stopping speed auto task");
                     h.abort();
+                    // Whatever the pause was for, the adjustment starts afresh when re-enabled.
+                    state.paused_until.set(None);
+                    mi.set_label(Self::LABEL);
                 }
                 // Ensure the task is only spawned on activation.
                 None if mi.is_active() => {
                     tracing::debug!("spawning speed auto task");
-                    let fut = Self::speed_auto_task(device.clone(), fan_speed.clone(), fan_curve);
+                    let fut =
+                        Self::speed_auto_task(device.clone(), mi.clone(), state.clone(), settings);
                     join_handle.set(Some(crate::spawn_local(fut)));
                 }
                 _ => tracing::warn!("no task found on item de-activation"),
@@ -67,47 +196,312 @@ impl SpeedAutoItem {
         Self { inner, kind }
     }
 
-    pub fn register_speed(&self, speed: FanSpeed) {
-        self.kind.0.set(speed);
+    /// Registers the state confirmed by the device, which the task acts on.
+    pub fn register_state(&self, device_state: DeviceState) {
+        self.kind.state.device_state.set(Some(device_state));
+
+        if let Some(waker) = self.kind.state.waker.take() {
+            waker.wake();
+        }
     }
 
+    /// Forgets the state confirmed by the device, so that nothing is sent until the reconnected
+    /// device reports its state.
+    pub fn register_disconnected(&self) {
+        self.kind.state.device_state.set(None);
+    }
+
+    pub fn register_probe_temp(&self, temp_c: Option<u8>) {
+        self.kind.state.probe_temp_c.set(temp_c);
+    }
+
+    /// Pauses the adjustment for the hold period, as the fan speed got changed by something else
+    /// than the tray. Another change restarts the hold period.
+    pub fn register_external_speed_change(&self) {
+        let settings = self.kind.settings;
+
+        if self.inner.is_active()
+            && settings.auto_mode == AutoMode::Host
+            && !settings.manual_hold.is_zero()
+        {
+            tracing::info!("pausing the auto fan speed for {:?}", settings.manual_hold);
+            let paused_until = Instant::now() + settings.manual_hold;
+            self.kind.state.paused_until.set(Some(paused_until));
+        }
+    }
+
+    /// Enables/disables the fan speed auto adjustment the same way clicking the item would.
+    pub fn set_enabled(&self, enabled: bool) {
+        // Changing the state activates the item, running its callback.
+        self.inner.set_active(enabled);
+    }
+
+    /// Toggles the fan speed auto adjustment the same way clicking the item would.
+    pub fn toggle(&self) {
+        self.set_enabled(!self.inner.is_active());
+    }
+
+    /// The fan curve is fed the CPU temperature or, if the probe is taken into account and its
+    /// offset temperature is hotter, the probe one.
     #[instrument(skip_all, err(Debug))]
     async fn speed_auto_task(
         device: Device,
-        fan_speed: Rc<Cell<FanSpeed>>,
-        fan_curve: [f32; 5],
+        item: CheckMenuItem,
+        state: Rc<AutoState>,
+        settings: AutoSettings,
     ) -> AnyResult<()> {
         let system = System::new();
         let mut ticker = glib::interval_stream_seconds(1);
 
+        // Read once, as the task gets respawned anyway when the item gets toggled.
+        let press_timings = device.press_timings().await.unwrap_or_else(|e| {
+            tracing::warn!("assuming the longest press timings: {e:#}");
+            LONGEST_PRESS_TIMINGS
+        });
+        let step_timeout = step_timeout(press_timings);
+
+        if settings.auto_mode == AutoMode::Device {
+            let fan_curve = settings.device_fan_curve()?;
+            device
+                .dispatch(HostReport::FanCurve(fan_curve), Priority::Auto)
+                .await?;
+        }
+
         while let Some(()) = ticker.next().await {
-            if let (Ok(temp), fan_speed) = (system.cpu_temp(), fan_speed.get()) {
-                let command = match fan_speed {
-                    FanSpeed::Speed1 if temp > fan_curve[0] => Some(DeviceCommand::SpeedUp),
-                    FanSpeed::Speed2 if temp > fan_curve[1] => Some(DeviceCommand::SpeedUp),
-                    FanSpeed::Speed3 if temp > fan_curve[2] => Some(DeviceCommand::SpeedUp),
-                    FanSpeed::Speed4 if temp > fan_curve[3] => Some(DeviceCommand::SpeedUp),
-                    FanSpeed::Speed5 if temp > fan_curve[4] => Some(DeviceCommand::SpeedUp),
-                    FanSpeed::Speed6 if temp > fan_curve[4] => None,
-                    FanSpeed::Speed6 => Some(DeviceCommand::SpeedDown),
-                    FanSpeed::Speed5 if temp < fan_curve[3] => Some(DeviceCommand::SpeedDown),
-                    FanSpeed::Speed4 if temp < fan_curve[2] => Some(DeviceCommand::SpeedDown),
-                    FanSpeed::Speed3 if temp < fan_curve[1] => Some(DeviceCommand::SpeedDown),
-                    FanSpeed::Speed2 if temp < fan_curve[0] => Some(DeviceCommand::SpeedDown),
-                    FanSpeed::Speed5
-                    | FanSpeed::Speed4
-                    | FanSpeed::Speed3
-                    | FanSpeed::Speed2
-                    | FanSpeed::Speed1 => None,
-                };
-
-                if let Some(command) = command {
-                    tracing::info!("CPU temp: {temp}, fan speed: {fan_speed:?}");
-                    device.send_command(command).await?;
-                }
+            if let Some(remaining) = state.paused_for() {
+                let remaining_s = remaining.as_secs();
+                let label = format!(
+                    "{} (paused {}:{:02})",
+                    Self::LABEL,
+                    remaining_s / 60,
+                    remaining_s % 60
+                );
+                item.set_label(&label);
+                continue;
+            }
+
+            if item
+                .label()
+                .is_some_and(|label| label.as_str() != Self::LABEL)
+            {
+                tracing::info!("resuming the auto fan speed");
+                item.set_label(Self::LABEL);
+            }
+
+            let Ok(cpu_temp) = system.cpu_temp() else {
+                continue;
+            };
+
+            let probe_temp_c = settings
+                .probe_offset
+                .and_then(|offset| Some(state.probe_temp_c.get()?.saturating_add(offset)));
+            let temp = probe_temp_c.map_or(cpu_temp, |probe_temp_c| {
+                cpu_temp.max(f32::from(probe_temp_c))
+            });
+
+            if settings.auto_mode == AutoMode::Device {
+                let temp_c = Self::whole_degrees(temp);
+                device
+                    .dispatch(HostReport::Temperature(temp_c), Priority::Auto)
+                    .await?;
+                continue;
+            }
+
+            // Never act on a stale state, nor on one where the speed buttons do nothing.
+            let Some(device_state) = state.device_state.get() else {
+                continue;
+            };
+
+            if !device_state.power_enabled() {
+                continue;
+            }
+
+            let target = target_speed(settings.fan_curve, device_state.fan_speed(), temp);
+
+            if target != device_state.fan_speed() {
+                tracing::info!("CPU temp: {cpu_temp}, probe: {probe_temp_c:?}, target: {target:?}");
+                Self::converge(&device, &state, target, step_timeout).await?;
             }
         }
 
         Ok(())
     }
+
+    /// Steps the fan speed towards the target, one confirmed step at a time. Gives up if a step
+    /// gets superseded or the adjustment paused, as the user took over, if the device got
+    /// disconnected or if the device does not
+    /// confirm a step in time, in which case the device gets resynced.
+    async fn converge(
+        device: &Device,
+        state: &AutoState,
+        target: FanSpeed,
+        step_timeout: Duration,
+    ) -> AnyResult<()> {
+        while let Some(device_state) = state.device_state.get() {
+            if state.paused_for().is_some() {
+                break;
+            }
+
+            let from = device_state.fan_speed();
+
+            let command = match (target as u8).cmp(&(from as u8)) {
+                Ordering::Greater => DeviceCommand::SpeedUp,
+                Ordering::Less => DeviceCommand::SpeedDown,
+                Ordering::Equal => break,
+            };
+
+            let outcome = device
+                .dispatch(HostReport::Command(command), Priority::Auto)
+                .await?;
+
+            if outcome != Outcome::Sent {
+                break;
+            }
+
+            // Any change counts, as the next step is worked out from the confirmed speed anyway.
+            let step = pin!(state.confirmed(|device_state| device_state.fan_speed() != from));
+
+            if let Either::Right(_) = select(step, glib::timeout_future(step_timeout)).await {
+                tracing::warn!("fan speed step from {from:?} not confirmed, resyncing");
+                // Nothing gets sent until the device confirms its state after the resync.
+                state.device_state.set(None);
+                device
+                    .resync(device_state.power_enabled(), device_state.leds_enabled())
+                    .await?;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rounds the temperature to the whole degrees the device fan curve works with.
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "clamped to the u8 range"
+    )]
+    fn whole_degrees(temp: f32) -> u8 {
+        temp.round().clamp(0.0, f32::from(u8::MAX)) as u8
+    }
+}
+
+/// The press timings assumed when the device cannot tell its own, as long as the device accepts.
+const LONGEST_PRESS_TIMINGS: PressTimings = PressTimings {
+    short_press_ms: *PressTimings::SHORT_PRESS_RANGE.end(),
+    long_press_ms: *PressTimings::LONG_PRESS_RANGE.end(),
+    post_press_delay_ms: *PressTimings::POST_PRESS_DELAY_RANGE.end(),
+    ..PressTimings::DEFAULT
+};
+
+/// Returns how long to wait for the device to confirm a fan speed step.
+///
+/// The device may still be busy with the presses queued ahead of the step, the longest run of which
+/// is a resync: up to 14 short presses and the LEDs long press. The step itself takes a short press
+/// and another one if it only wakes up the backlight. The rest of the margin covers the USB polling
+/// and the delays in between the presses.
+fn step_timeout(press_timings: PressTimings) -> Duration {
+    const SHORT_PRESSES: u64 = 14 + 2;
+    const MARGIN: Duration = Duration::from_secs(1);
+
+    let delay_ms = u64::from(press_timings.post_press_delay_ms);
+    let short_press_ms = u64::from(press_timings.short_press_ms) + delay_ms;
+    let long_press_ms = u64::from(press_timings.long_press_ms) + delay_ms;
+
+    Duration::from_millis(SHORT_PRESSES * short_press_ms + long_press_ms) + MARGIN
+}
+
+/// Returns the fan speed the host fan curve calls for, coming from the current one, by taking
+/// steps until the curve calls for none.
+fn target_speed(fan_curve: [f32; 5], current: FanSpeed, temp: f32) -> FanSpeed {
+    let mut speed = current;
+
+    // A step up and the step back down never hold at the same temperature, so this ends.
+    while let Some(command) = next_step(fan_curve, speed, temp) {
+        match command {
+            DeviceCommand::SpeedUp => speed.increase(),
+            _ => speed.decrease(),
+        }
+    }
+
+    speed
+}
+
+/// Returns the fan speed step the host fan curve calls for at the given speed, if any.
+fn next_step(fan_curve: [f32; 5], fan_speed: FanSpeed, temp: f32) -> Option<DeviceCommand> {
+    match fan_speed {
+        FanSpeed::Speed1 if temp > fan_curve[0] => Some(DeviceCommand::SpeedUp),
+        FanSpeed::Speed2 if temp > fan_curve[1] => Some(DeviceCommand::SpeedUp),
+        FanSpeed::Speed3 if temp > fan_curve[2] => Some(DeviceCommand::SpeedUp),
+        FanSpeed::Speed4 if temp > fan_curve[3] => Some(DeviceCommand::SpeedUp),
+        FanSpeed::Speed5 if temp > fan_curve[4] => Some(DeviceCommand::SpeedUp),
+        FanSpeed::Speed6 if temp > fan_curve[4] => None,
+        FanSpeed::Speed6 => Some(DeviceCommand::SpeedDown),
+        FanSpeed::Speed5 if temp < fan_curve[3] => Some(DeviceCommand::SpeedDown),
+        FanSpeed::Speed4 if temp < fan_curve[2] => Some(DeviceCommand::SpeedDown),
+        FanSpeed::Speed3 if temp < fan_curve[1] => Some(DeviceCommand::SpeedDown),
+        FanSpeed::Speed2 if temp < fan_curve[0] => Some(DeviceCommand::SpeedDown),
+        FanSpeed::Speed5
+        | FanSpeed::Speed4
+        | FanSpeed::Speed3
+        | FanSpeed::Speed2
+        | FanSpeed::Speed1 => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use shared::{FanSpeed, PressTimings};
+
+    use super::{LONGEST_PRESS_TIMINGS, step_timeout, target_speed};
+
+    #[test]
+    fn test_target_speed() {
+        let fan_curve = [60.0, 65.0, 70.0, 75.0, 80.0];
+
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed1, 60.0),
+            FanSpeed::Speed1
+        );
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed1, 72.5),
+            FanSpeed::Speed4
+        );
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed2, 90.0),
+            FanSpeed::Speed6
+        );
+
+        // Going down once back below the threshold the speed went over.
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed4, 70.0),
+            FanSpeed::Speed4
+        );
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed4, 69.5),
+            FanSpeed::Speed3
+        );
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed6, 80.0),
+            FanSpeed::Speed5
+        );
+        assert_eq!(
+            target_speed(fan_curve, FanSpeed::Speed6, 20.0),
+            FanSpeed::Speed1
+        );
+    }
+
+    #[test]
+    fn test_step_timeout() {
+        assert_eq!(
+            step_timeout(PressTimings::DEFAULT),
+            Duration::from_millis(3315)
+        );
+        assert_eq!(
+            step_timeout(LONGEST_PRESS_TIMINGS),
+            Duration::from_millis(6630)
+        );
+    }
 }
@@ -6,8 +6,8 @@ use shared::FanSpeed;
 
 use crate::menu::item::CustomMenuItem;
 
-/// Non-actionable item that displays the current fan speed. This item is purely meant for display
-/// and is never UI sensitive.
+/// Non-actionable item that displays the current fan speed, followed by `(?)` while the device
+/// state is uncertain. This item is purely meant for display and is never UI sensitive.
 pub type SpeedLabelItem = CustomMenuItem<MenuItem, SpeedLabel>;
 
 #[derive(Clone, Copy, Debug)]
@@ -36,20 +36,29 @@ macro_rules! make_label {
 }
 
 impl SpeedLabelItem {
-    pub fn update_label(&self, fan_speed: FanSpeed) {
+    pub fn update_label(&self, fan_speed: FanSpeed, uncertain: bool) {
         macro_rules! make_speed_label {
             ($speed:expr) => {
                 make_label!(&[$speed as u8 + b'0'])
             };
+            ($speed:expr,uncertain) => {
+                make_label!(&[$speed as u8 + b'0', b' ', b'(', b'?', b')'])
+            };
         }
 
-        let label = match fan_speed {
-            FanSpeed::Speed1 => make_speed_label!(FanSpeed::Speed1),
-            FanSpeed::Speed2 => make_speed_label!(FanSpeed::Speed2),
-            FanSpeed::Speed3 => make_speed_label!(FanSpeed::Speed3),
-            FanSpeed::Speed4 => make_speed_label!(FanSpeed::Speed4),
-            FanSpeed::Speed5 => make_speed_label!(FanSpeed::Speed5),
-            FanSpeed::Speed6 => make_speed_label!(FanSpeed::Speed6),
+        let label = match (fan_speed, uncertain) {
+            (FanSpeed::Speed1, false) => make_speed_label!(FanSpeed::Speed1),
+            (FanSpeed::Speed2, false) => make_speed_label!(FanSpeed::Speed2),
+            (FanSpeed::Speed3, false) => make_speed_label!(FanSpeed::Speed3),
+            (FanSpeed::Speed4, false) => make_speed_label!(FanSpeed::Speed4),
+            (FanSpeed::Speed5, false) => make_speed_label!(FanSpeed::Speed5),
+            (FanSpeed::Speed6, false) => make_speed_label!(FanSpeed::Speed6),
+            (FanSpeed::Speed1, true) => make_speed_label!(FanSpeed::Speed1, uncertain),
+            (FanSpeed::Speed2, true) => make_speed_label!(FanSpeed::Speed2, uncertain),
+            (FanSpeed::Speed3, true) => make_speed_label!(FanSpeed::Speed3, uncertain),
+            (FanSpeed::Speed4, true) => make_speed_label!(FanSpeed::Speed4, uncertain),
+            (FanSpeed::Speed5, true) => make_speed_label!(FanSpeed::Speed5, uncertain),
+            (FanSpeed::Speed6, true) => make_speed_label!(FanSpeed::Speed6, uncertain),
         };
 
         self.inner.set_label(label);
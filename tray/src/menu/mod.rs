@@ -5,8 +5,9 @@ use std::rc::Rc;
 use crate::{
     Device,
     menu::item::{
-        LedsChangeColorItem, LedsToggleItem, PowerToggleItem, QuitItem, SpeedAutoItem,
-        SpeedDownItem, SpeedLabelItem, SpeedUpItem,
+        AdvancedItem, AutoSettings, LedsColorItem, LedsToggleItem, OdometerItem, PowerToggleItem,
+        ProbeLabelItem, QuitItem, RemoteWakeupItem, ResyncItem, SpeedAutoItem, SpeedDownItem,
+        SpeedLabelItem, SpeedUpItem,
     },
 };
 
@@ -17,12 +18,17 @@ use crate::{
 #[derive(Debug)]
 pub struct MenuItems {
     pub speed_label: SpeedLabelItem,
+    pub probe_label: ProbeLabelItem,
+    pub resync: ResyncItem,
     pub speed_auto: SpeedAutoItem,
     pub speed_up: SpeedUpItem,
     pub speed_down: SpeedDownItem,
     pub leds: LedsToggleItem,
-    pub leds_change_color: LedsChangeColorItem,
+    pub leds_color: LedsColorItem,
     pub power: PowerToggleItem,
+    pub remote_wakeup: RemoteWakeupItem,
+    pub odometer: OdometerItem,
+    pub advanced: AdvancedItem,
     pub quit: QuitItem,
     // Ensures this struct cannot be constructed from scratch.
     _private: (),
@@ -33,7 +39,7 @@ impl MenuItems {
     ///
     /// The struct is wrapped because it is self referential and meant to be shared and cloned,
     /// since the items' activation callbacks alter the state of other items.
-    pub fn new(device: Device, fan_curve: [f32; 5]) -> Rc<Self> {
+    pub fn new(device: Device, auto_settings: AutoSettings) -> Rc<Self> {
         // Not particularly fond of this, but a compromise had to be made:
         // - The cyclic definition allows for items to be valid on construction and for those that
         //   need to store their callback [`SignalHandlerId`] to be able to do so.
@@ -42,12 +48,21 @@ impl MenuItems {
         //   the items and introduces room for mistakes.
         Rc::new_cyclic(move |menu_items| Self {
             speed_label: SpeedLabelItem::default(),
-            speed_auto: SpeedAutoItem::new_checkbox(menu_items.clone(), device.clone(), fan_curve),
+            probe_label: ProbeLabelItem::default(),
+            resync: ResyncItem::new(menu_items.clone(), device.clone()),
+            speed_auto: SpeedAutoItem::new_checkbox(
+                menu_items.clone(),
+                device.clone(),
+                auto_settings,
+            ),
             speed_up: SpeedUpItem::new(menu_items.clone(), device.clone()),
             speed_down: SpeedDownItem::new(menu_items.clone(), device.clone()),
             leds: LedsToggleItem::new_checkbox(menu_items.clone(), device.clone()),
-            leds_change_color: LedsChangeColorItem::new(menu_items.clone(), device.clone()),
-            power: PowerToggleItem::new_checkbox(menu_items.clone(), device),
+            leds_color: LedsColorItem::new(menu_items.clone(), device.clone()),
+            power: PowerToggleItem::new_checkbox(menu_items.clone(), device.clone()),
+            remote_wakeup: RemoteWakeupItem::new(device.clone()),
+            odometer: OdometerItem::new(device.clone()),
+            advanced: AdvancedItem::new(device),
             quit: QuitItem::default(),
             _private: (),
         })
@@ -81,8 +96,9 @@ impl MenuItems {
 
         let enable_leds_ctrl = flag && self.leds.is_active();
         self.leds.set_sensitive(flag);
-        self.leds_change_color.set_sensitive(enable_leds_ctrl);
+        self.leds_color.set_sensitive(enable_leds_ctrl);
 
         self.power.set_sensitive(flag);
+        self.resync.set_sensitive(flag);
     }
 }
@@ -0,0 +1,19 @@
+use std::{process::Command, thread};
+
+/// Raises a desktop notification through `notify-send`, without blocking the event loop. Failures
+/// are only logged, as notifications are not essential.
+pub fn notify(summary: &str, body: &str) {
+    let mut command = Command::new("notify-send");
+    command
+        .args([
+            "--app-name=cooler-than-you",
+            "--icon=cooler-than-you-symbolic",
+        ])
+        .args([summary, body]);
+
+    thread::spawn(move || {
+        if let Err(e) = command.status() {
+            tracing::warn!("could not raise notification: {e}");
+        }
+    });
+}